@@ -0,0 +1,224 @@
+use pulsar_core::prelude::*;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use onvif::{FpsValue, OnvifConnection};
+
+use crate::{
+    AdditionalConfiguration, CachedState, CameraModelHttp, CancellationToken, DayNightMode,
+    FocusCapabilities, FocusContinuous, FocusSettings, FocusValue, GrayScaleMode, ImageAdjustment,
+    ImageProfile, ImageSettings, IpCamerasError, MulticastConfig, PtzVector, SpotlightMode,
+    SupplementMode, SystemStatus, TempRangeMode, TransportInfo, VendorImageSettings,
+    WhiteBalanceMode,
+};
+
+enum Executor {
+    Owned(Runtime),
+    Handle(Handle),
+}
+
+impl Executor {
+    fn block_on<F: std::future::Future>(&self, future: F) -> F::Output {
+        match self {
+            Executor::Owned(runtime) => runtime.block_on(future),
+            Executor::Handle(handle) => handle.block_on(future),
+        }
+    }
+}
+
+// Synchronous facade over `CameraModelHttp`.
+// Must not be used from inside an async context: `block_on` panics there.
+pub struct BlockingCamera {
+    camera: CameraModelHttp,
+    executor: Executor,
+}
+
+macro_rules! implement_blocking {
+    ( $fun:ident $(| $args:ident: $type:ty |)* => $ret:ty ) => {
+        pub fn $fun(&self $(, $args:$type )*) -> Result<$ret, IpCamerasError> {
+            self.executor.block_on(self.camera.$fun($( $args ),*))
+        }
+    };
+}
+
+impl BlockingCamera {
+    // Builds a private current-thread runtime
+    pub fn new(camera: CameraModelHttp) -> Result<Self, IpCamerasError> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+
+        Ok(Self {
+            camera,
+            executor: Executor::Owned(runtime),
+        })
+    }
+
+    // Reuses a runtime owned by the caller
+    pub fn with_handle(camera: CameraModelHttp, handle: Handle) -> Self {
+        Self {
+            camera,
+            executor: Executor::Handle(handle),
+        }
+    }
+
+    pub fn inner(&self) -> &CameraModelHttp {
+        &self.camera
+    }
+
+    pub fn into_inner(self) -> CameraModelHttp {
+        self.camera
+    }
+
+    pub fn can_write(&self) -> bool {
+        self.camera.can_write()
+    }
+
+    pub fn cached_state(&self) -> CachedState {
+        self.camera.cached_state()
+    }
+
+    pub fn set_credentials(&mut self, username: Option<String>, password: Option<String>) {
+        self.camera.set_credentials(username, password)
+    }
+
+    // function_name | arg: type | => return_type
+    implement_blocking!(get_vendor_image_settings => VendorImageSettings);
+    implement_blocking!(try_get_fps => Option<FpsValue>);
+    implement_blocking!(try_switch_spotlight |enabled: bool| => Option<()>);
+
+    implement_blocking!(init  => ());
+    implement_blocking!(ensure_initialized => ());
+    implement_blocking!(init_onvif_cancellable |cancel: &CancellationToken| => OnvifConnection);
+
+    implement_blocking!(set_fps |fps: FpsValue| => ());
+    implement_blocking!(get_fps => FpsValue);
+    implement_blocking!(get_supported_fps => Vec<FpsValue>);
+
+    implement_blocking!(switch_spotlight |enabled: bool| => ());
+    implement_blocking!(switch_spotlight_if_changed |enabled: bool| => ());
+    implement_blocking!(set_spotlight_mode |mode: SpotlightMode| => ());
+    implement_blocking!(get_spotlight_state => bool);
+    implement_blocking!(set_supplement_light |mode: SupplementMode| |brightness: Option<i32>| => ());
+
+    implement_blocking!(get_focus_capabilities => FocusCapabilities);
+    implement_blocking!(get_focus_continuous => FocusContinuous);
+    implement_blocking!(set_focus_continuous |focus: FocusContinuous| => ());
+    implement_blocking!(get_focus_absolute => FocusValue);
+    implement_blocking!(get_focus_absolute_cancellable |cancel: &CancellationToken| => FocusValue);
+    implement_blocking!(set_focus_absolute |focus: FocusValue| => ());
+    implement_blocking!(get_focus_normalized => FocusValue);
+    implement_blocking!(apply_and_confirm_focus |settings: FocusSettings| => FocusValue);
+    implement_blocking!(apply_and_confirm_focus_cancellable |settings: FocusSettings| |cancel: &CancellationToken| => FocusValue);
+    implement_blocking!(get_focus_relative => FocusValue);
+    implement_blocking!(set_focus_relative |focus: FocusValue| => ());
+
+    implement_blocking!(is_ptz => bool);
+    implement_blocking!(ptz_absolute_move |position: PtzVector| => ());
+    implement_blocking!(ptz_relative_move |translation: PtzVector| => ());
+    implement_blocking!(ptz_continuous_move |speed: PtzVector| => ());
+    implement_blocking!(ptz_stop => ());
+    implement_blocking!(get_proportional_pan => bool);
+    implement_blocking!(set_proportional_pan |enabled: bool| => ());
+
+    implement_blocking!(verify_credentials => bool);
+    implement_blocking!(measure_latency => std::time::Duration);
+    implement_blocking!(set_device_name |name: &str| => ());
+    implement_blocking!(set_device_location |location: &str| => ());
+    implement_blocking!(wait_until_online |timeout: std::time::Duration| => ());
+    implement_blocking!(wait_until_online_cancellable |timeout: std::time::Duration| |cancel: &CancellationToken| => ());
+
+    implement_blocking!(get_snapshot => Vec<u8>);
+    implement_blocking!(set_jpeg_params |size: Option<i32>| |exif: Option<bool>| |merge_size: Option<i32>| => ());
+    implement_blocking!(set_overexpose_suppress |enabled: bool| |distance_level: Option<i32>| => ());
+    implement_blocking!(set_face_exposure |enabled: bool| |sensitivity: Option<i32>| => ());
+    implement_blocking!(get_bright_enhance => i32);
+    implement_blocking!(set_bright_enhance |level: i32| => ());
+    implement_blocking!(set_car_window_enhancement |enabled: bool| |brighten: i32| |defog: i32| => ());
+    implement_blocking!(get_iris => i32);
+    implement_blocking!(set_iris |level: i32| => ());
+    implement_blocking!(set_image_loss_detection |enabled: bool| => ());
+    implement_blocking!(set_mirror |enabled: bool| => ());
+    implement_blocking!(set_rotation |degrees: i32| => ());
+    implement_blocking!(get_capture_mode => String);
+    implement_blocking!(set_capture_mode |mode: String| => ());
+    implement_blocking!(set_dss |enabled: bool| |level: Option<String>| => ());
+    implement_blocking!(set_plate_bright |enabled: bool| |sensitivity: Option<i32>| |correction: Option<i32>| => ());
+    implement_blocking!(set_white_balance |mode: WhiteBalanceMode| |red: Option<i32>| |blue: Option<i32>| => ());
+    implement_blocking!(set_day_night_mode |mode: DayNightMode| => ());
+    implement_blocking!(get_image_settings => ImageSettings);
+    implement_blocking!(set_image_adjustment |adjustment: ImageAdjustment| => ());
+    implement_blocking!(set_gray_scale |mode: GrayScaleMode| => ());
+    implement_blocking!(set_temp_range |mode: TempRangeMode| |upper: Option<i32>| |lower: Option<i32>| => ());
+
+    implement_blocking!(get_transport => TransportInfo);
+    implement_blocking!(set_transport |tcp: bool| |multicast: Option<MulticastConfig>| => ());
+    implement_blocking!(get_rtsp_port => u32);
+    implement_blocking!(set_rtsp_port |port: u32| => ());
+    implement_blocking!(probe_stream => bool);
+
+    implement_blocking!(set_date_time |date_time: chrono::DateTime<chrono::FixedOffset>| => ());
+    implement_blocking!(sync_time_to_host => ());
+    implement_blocking!(get_time_zone => String);
+    implement_blocking!(set_time_zone |tz: String| => ());
+
+    implement_blocking!(get_system_status => SystemStatus);
+    implement_blocking!(get_settings_json => serde_json::Value);
+
+    implement_blocking!(get_additional_configuration => AdditionalConfiguration);
+    implement_blocking!(set_additional_configuration |configuration: AdditionalConfiguration| => ());
+    implement_blocking!(get_default_configuration => AdditionalConfiguration);
+    implement_blocking!(apply_profile |profile: ImageProfile| => ());
+    implement_blocking!(apply_role_defaults => ());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{utils::mock::MockCamera, AxisHttp};
+
+    // The mock runs on its own runtime, the blocking calls must come from outside one
+    fn axis_camera(routes: Vec<(&'static str, u16, String)>) -> (Runtime, CameraModelHttp) {
+        let runtime = Builder::new_multi_thread().enable_all().build().unwrap();
+        let mock = runtime.block_on(MockCamera::start(routes));
+        let camera = CameraModelHttp::Axis(AxisHttp {
+            host: Some(mock.host),
+            ..Default::default()
+        });
+
+        (runtime, camera)
+    }
+
+    #[test]
+    fn get_fps_on_own_runtime() {
+        let (_mock_runtime, camera) = axis_camera(vec![(
+            "GET /axis-cgi/param.cgi?action=list&group=Image.I0.Stream.FPS",
+            200,
+            "root.Image.I0.Stream.FPS=25\n".to_string(),
+        )]);
+
+        let camera = BlockingCamera::new(camera).unwrap();
+
+        assert_eq!(camera.get_fps().unwrap(), FpsValue::from(25u32));
+    }
+
+    #[test]
+    fn switch_spotlight_on_provided_handle() {
+        let (mock_runtime, camera) = axis_camera(vec![(
+            "POST /axis-cgi/io/portmanagement.cgi",
+            200,
+            r#"{"apiVersion": "1.0", "method": "setPorts", "data": {"ports": ["2"]}}"#.to_string(),
+        )]);
+
+        let camera = BlockingCamera::with_handle(camera, mock_runtime.handle().clone());
+
+        camera.switch_spotlight(true).unwrap();
+    }
+
+    #[test]
+    fn errors_are_passed_through() {
+        let camera = BlockingCamera::new(CameraModelHttp::Unknown).unwrap();
+
+        assert!(matches!(
+            camera.get_fps(),
+            Err(IpCamerasError::NotAvialiableApi)
+        ));
+    }
+}
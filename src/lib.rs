@@ -1,16 +1,33 @@
 mod models;
 mod utils;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
 use models::{axis::*, dahua::*, hikvision::*, stilsoft::*};
 use utils::handler::*;
 
+use common::CameraRole;
 use domain::stream::Resource;
-use domain::CameraModelName;
-use onvif::FpsValue;
+use domain::{CameraId, CameraModelName};
+use onvif::{ok_or_explain, FpsValue, OnvifConnection};
+use pulsar_core::prelude::*;
+
+pub use tokio_util::sync::CancellationToken;
 
-pub use crate::utils::{error::IpCamerasError, focus::*, serde::external::*};
+pub use crate::utils::{
+    diff::diff_settings,
+    discovery::DiscoveredCamera,
+    error::IpCamerasError,
+    focus::*,
+    ptz::*,
+    rate_limit::{remove_rate_limit, set_rate_limit, RateLimit},
+    registry::CameraRegistry,
+    serde::external::*,
+    status::{CachedState, SystemStatus},
+    transport::*,
+};
 
-#[derive(Debug)]
 pub enum CameraModelHttp {
     Dahua(DahuaHttp),
     Axis(AxisHttp),
@@ -64,23 +81,86 @@ impl From<Resource> for CameraModelHttp {
     }
 }
 
+impl std::fmt::Display for CameraModelHttp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}({}@{})",
+            self.name(),
+            self.id().unwrap_or_default(),
+            self.host().unwrap_or("unknown")
+        )
+    }
+}
+
+// Model structs hold plaintext credentials, so only identification is printed
+impl std::fmt::Debug for CameraModelHttp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CameraModelHttp")
+            .field("name", &self.name())
+            .field("id", &self.id())
+            .field("host", &self.host())
+            .finish()
+    }
+}
+
 macro_rules! implement_inner {
     ( $fun:ident $(| $args:ident: $type:ty |)* => $ret:ty ) => {
         pub async fn $fun(&self $(, $args:$type )*) -> Result<$ret, IpCamerasError> {
             use CameraModelHttp::*;
 
-            match self {
-                Axis(c) => c.$fun($( $args ),*).await,
-                Stilsoft(c) => c.$fun($( $args ),*).await,
-                Dahua(c) => c.$fun($( $args ),*).await,
-                Hikvision(c) => c.$fun($( $args ),*).await,
-                _ => Err(IpCamerasError::NotAvialiableApi),
+            utils::instrument::traced(self, stringify!($fun), async move {
+                match self {
+                    Axis(c) => c.$fun($( $args ),*).await,
+                    Stilsoft(c) => c.$fun($( $args ),*).await,
+                    Dahua(c) => c.$fun($( $args ),*).await,
+                    Hikvision(c) => c.$fun($( $args ),*).await,
+                    _ => Err(IpCamerasError::NotAvialiableApi),
+                }
+            })
+            .await
+        }
+    };
+}
+
+// Same as `implement_inner`, but an unsupported vendor call is retried over ONVIF
+// when the model has `onvif_fallback` enabled. Only used for the fps calls: the other
+// getters have no `OnvifConnection` counterpart with the same signature.
+macro_rules! implement_onvif_fallback {
+    ( @call $model:expr, $c:ident, $fun:ident $(, $args:ident)* ) => {
+        match $c.$fun($( $args ),*).await {
+            Err(IpCamerasError::NotAvialiableApi) if $c.onvif_fallback() => {
+                trace!("{} falls back to ONVIF for {}", $model, stringify!($fun));
+                Ok(ok_or_explain!($c.init_onvif().await?.$fun($( $args ),*).await))
             }
+            result => result,
+        }
+    };
+    ( $fun:ident $(| $args:ident: $type:ty |)* => $ret:ty ) => {
+        pub async fn $fun(&self $(, $args:$type )*) -> Result<$ret, IpCamerasError> {
+            use CameraModelHttp::*;
+
+            utils::instrument::traced(self, stringify!($fun), async move {
+                match self {
+                    Axis(c) => implement_onvif_fallback!(@call self, c, $fun $(, $args)*),
+                    Stilsoft(c) => implement_onvif_fallback!(@call self, c, $fun $(, $args)*),
+                    Dahua(c) => implement_onvif_fallback!(@call self, c, $fun $(, $args)*),
+                    Hikvision(c) => implement_onvif_fallback!(@call self, c, $fun $(, $args)*),
+                    _ => Err(IpCamerasError::NotAvialiableApi),
+                }
+            })
+            .await
         }
     };
 }
 
 impl CameraModelHttp {
+    pub async fn discover(
+        timeout: std::time::Duration,
+    ) -> Result<Vec<DiscoveredCamera>, IpCamerasError> {
+        utils::discovery::discover(timeout).await
+    }
+
     pub fn name(&self) -> String {
         use CameraModelHttp::*;
 
@@ -92,26 +172,417 @@ impl CameraModelHttp {
             _ => "Unknown".to_string(),
         }
     }
+
+    pub fn id(&self) -> Option<CameraId> {
+        use CameraModelHttp::*;
+
+        match self {
+            Axis(c) => Some(c.id),
+            Dahua(c) => Some(c.id),
+            Stilsoft(c) => Some(c.id),
+            Hikvision(c) => Some(c.id),
+            _ => None,
+        }
+    }
+
+    pub fn host(&self) -> Option<&str> {
+        use CameraModelHttp::*;
+
+        match self {
+            Axis(c) => c.host.as_deref(),
+            Dahua(c) => c.host.as_deref(),
+            Stilsoft(c) => c.host.as_deref(),
+            Hikvision(c) => c.host.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn can_write(&self) -> bool {
+        use CameraModelHttp::*;
+
+        match self {
+            Axis(c) => c.can_write(),
+            Dahua(c) => c.can_write(),
+            Stilsoft(c) => c.can_write(),
+            Hikvision(c) => c.can_write(),
+            _ => false,
+        }
+    }
+
+    pub fn cached_state(&self) -> CachedState {
+        use CameraModelHttp::*;
+
+        match self {
+            Axis(c) => c.cached_state(),
+            Dahua(c) => c.cached_state(),
+            Stilsoft(c) => c.cached_state(),
+            Hikvision(c) => c.cached_state(),
+            _ => CachedState::default(),
+        }
+    }
+
+    // Timeout in seconds
+    pub fn with_timeout(self, timeout: u64) -> Self {
+        use CameraModelHttp::*;
+
+        match self {
+            Axis(c) => Axis(c.with_timeout(timeout)),
+            Dahua(c) => Dahua(c.with_timeout(timeout)),
+            Stilsoft(c) => Stilsoft(c.with_timeout(timeout)),
+            Hikvision(c) => Hikvision(c.with_timeout(timeout)),
+            other => other,
+        }
+    }
+
+    pub fn with_user_agent(self, agent: impl Into<String>) -> Self {
+        use CameraModelHttp::*;
+
+        let agent = agent.into();
+        match self {
+            Axis(c) => Axis(c.with_user_agent(agent)),
+            Dahua(c) => Dahua(c.with_user_agent(agent)),
+            Stilsoft(c) => Stilsoft(c.with_user_agent(agent)),
+            Hikvision(c) => Hikvision(c.with_user_agent(agent)),
+            other => other,
+        }
+    }
+
+    // Extra `(name, value)` headers sent with every request
+    pub fn with_headers(self, headers: Vec<(String, String)>) -> Self {
+        use CameraModelHttp::*;
+
+        match self {
+            Axis(c) => Axis(c.with_headers(headers)),
+            Dahua(c) => Dahua(c.with_headers(headers)),
+            Stilsoft(c) => Stilsoft(c.with_headers(headers)),
+            Hikvision(c) => Hikvision(c.with_headers(headers)),
+            other => other,
+        }
+    }
+
+    // Asks for plain answers (`Accept-Encoding: identity`) from firmwares that break compression
+    pub fn without_compression(self) -> Self {
+        use CameraModelHttp::*;
+
+        match self {
+            Axis(c) => Axis(c.without_compression()),
+            Dahua(c) => Dahua(c.without_compression()),
+            Stilsoft(c) => Stilsoft(c.without_compression()),
+            Hikvision(c) => Hikvision(c.without_compression()),
+            other => other,
+        }
+    }
+
+    pub fn with_credentials(mut self, username: Option<String>, password: Option<String>) -> Self {
+        self.set_credentials(username, password);
+        self
+    }
+
+    // Only Hikvision tunes on the role, other models are returned unchanged
+    pub fn with_role(self, role: CameraRole) -> Self {
+        match self {
+            CameraModelHttp::Hikvision(c) => CameraModelHttp::Hikvision(c.with_role(role)),
+            other => other,
+        }
+    }
+
+    // Axis and Stilsoft APIs address a single video input, they are returned unchanged
+    pub fn with_channel(self, channel: u32) -> Self {
+        use CameraModelHttp::*;
+
+        match self {
+            Dahua(c) => Dahua(c.with_channel(channel)),
+            Hikvision(c) => Hikvision(c.with_channel(channel)),
+            other => other,
+        }
+    }
+
+    pub fn set_credentials(&mut self, username: Option<String>, password: Option<String>) {
+        use CameraModelHttp::*;
+
+        match self {
+            Axis(c) => {
+                c.username = username;
+                c.password = password;
+            }
+            Dahua(c) => {
+                c.username = username;
+                c.password = password;
+            }
+            Stilsoft(c) => {
+                c.username = username;
+                c.password = password;
+            }
+            Hikvision(c) => {
+                c.username = username;
+                c.password = password;
+                // State cached under the old account is read again on the next call
+                c.initialized
+                    .store(false, std::sync::atomic::Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    pub async fn get_vendor_image_settings(&self) -> Result<VendorImageSettings, IpCamerasError> {
+        match self {
+            CameraModelHttp::Hikvision(c) => {
+                Ok(VendorImageSettings::Hikvision(c.get_image_channel().await?))
+            }
+            _ => Err(IpCamerasError::NotAvialiableApi),
+        }
+    }
+
+    pub async fn try_get_fps(&self) -> Result<Option<FpsValue>, IpCamerasError> {
+        IpCamerasError::unsupported_as_none(self.get_fps().await)
+    }
+
+    pub async fn try_switch_spotlight(&self, enabled: bool) -> Result<Option<()>, IpCamerasError> {
+        IpCamerasError::unsupported_as_none(self.switch_spotlight(enabled).await)
+    }
+
     // function_name | arg: type | => return_type
     implement_inner!(init  => ());
+    implement_inner!(ensure_initialized => ());
+    // ONVIF session with the model credentials, firing `cancel` stops the connect
+    implement_inner!(init_onvif_cancellable |cancel: &CancellationToken| => OnvifConnection);
 
-    implement_inner!(set_fps |fps: FpsValue| => ());
-    implement_inner!(get_fps => FpsValue);
+    implement_onvif_fallback!(set_fps |fps: FpsValue| => ());
+    implement_onvif_fallback!(get_fps => FpsValue);
+    implement_inner!(get_supported_fps => Vec<FpsValue>);
 
     implement_inner!(switch_spotlight |enabled: bool| => ());
+    implement_inner!(switch_spotlight_if_changed |enabled: bool| => ());
+    implement_inner!(set_spotlight_mode |mode: SpotlightMode| => ());
     implement_inner!(get_spotlight_state => bool);
+    implement_inner!(set_supplement_light |mode: SupplementMode| |brightness: Option<i32>| => ());
 
     implement_inner!(get_focus_capabilities => FocusCapabilities);
     implement_inner!(get_focus_continuous => FocusContinuous);
     implement_inner!(set_focus_continuous |focus: FocusContinuous| => ());
     implement_inner!(get_focus_absolute => FocusValue);
+    implement_inner!(get_focus_absolute_cancellable |cancel: &CancellationToken| => FocusValue);
     implement_inner!(set_focus_absolute |focus: FocusValue| => ());
+    implement_inner!(get_focus_normalized => FocusValue);
+    implement_inner!(apply_and_confirm_focus |settings: FocusSettings| => FocusValue);
+    implement_inner!(apply_and_confirm_focus_cancellable |settings: FocusSettings| |cancel: &CancellationToken| => FocusValue);
     implement_inner!(get_focus_relative => FocusValue);
     implement_inner!(set_focus_relative |focus: FocusValue| => ());
 
-    implement_inner!(set_date_time |date_time: chrono::NaiveDateTime| => ());
+    implement_inner!(is_ptz => bool);
+    implement_inner!(ptz_absolute_move |position: PtzVector| => ());
+    implement_inner!(ptz_relative_move |translation: PtzVector| => ());
+    implement_inner!(ptz_continuous_move |speed: PtzVector| => ());
+    implement_inner!(ptz_stop => ());
+    implement_inner!(get_proportional_pan => bool);
+    implement_inner!(set_proportional_pan |enabled: bool| => ());
+
+    implement_inner!(verify_credentials => bool);
+    implement_inner!(measure_latency => std::time::Duration);
+    implement_inner!(set_device_name |name: &str| => ());
+    implement_inner!(set_device_location |location: &str| => ());
+    implement_inner!(wait_until_online |timeout: std::time::Duration| => ());
+    implement_inner!(wait_until_online_cancellable |timeout: std::time::Duration| |cancel: &CancellationToken| => ());
+
+    implement_inner!(get_snapshot => Vec<u8>);
+    implement_inner!(set_jpeg_params |size: Option<i32>| |exif: Option<bool>| |merge_size: Option<i32>| => ());
+    implement_inner!(set_overexpose_suppress |enabled: bool| |distance_level: Option<i32>| => ());
+    implement_inner!(set_face_exposure |enabled: bool| |sensitivity: Option<i32>| => ());
+    implement_inner!(get_bright_enhance => i32);
+    implement_inner!(set_bright_enhance |level: i32| => ());
+    implement_inner!(set_car_window_enhancement |enabled: bool| |brighten: i32| |defog: i32| => ());
+    implement_inner!(get_iris => i32);
+    implement_inner!(set_iris |level: i32| => ());
+    implement_inner!(set_image_loss_detection |enabled: bool| => ());
+    implement_inner!(set_mirror |enabled: bool| => ());
+    implement_inner!(set_rotation |degrees: i32| => ());
+    implement_inner!(get_capture_mode => String);
+    implement_inner!(set_capture_mode |mode: String| => ());
+    implement_inner!(set_dss |enabled: bool| |level: Option<String>| => ());
+    implement_inner!(set_plate_bright |enabled: bool| |sensitivity: Option<i32>| |correction: Option<i32>| => ());
+    implement_inner!(set_white_balance |mode: WhiteBalanceMode| |red: Option<i32>| |blue: Option<i32>| => ());
+    implement_inner!(set_day_night_mode |mode: DayNightMode| => ());
+    implement_inner!(get_image_settings => ImageSettings);
+    implement_inner!(set_image_adjustment |adjustment: ImageAdjustment| => ());
+    implement_inner!(set_gray_scale |mode: GrayScaleMode| => ());
+    implement_inner!(set_temp_range |mode: TempRangeMode| |upper: Option<i32>| |lower: Option<i32>| => ());
+
+    implement_inner!(get_transport => TransportInfo);
+    implement_inner!(set_transport |tcp: bool| |multicast: Option<MulticastConfig>| => ());
+    implement_inner!(get_rtsp_port => u32);
+    implement_inner!(set_rtsp_port |port: u32| => ());
+    implement_inner!(probe_stream => bool);
+
+    implement_inner!(set_date_time |date_time: chrono::DateTime<chrono::FixedOffset>| => ());
+    implement_inner!(sync_time_to_host => ());
+    implement_inner!(get_time_zone => String);
+    implement_inner!(set_time_zone |tz: String| => ());
+
+    implement_inner!(get_system_status => SystemStatus);
+    implement_inner!(get_settings_json => serde_json::Value);
 
     implement_inner!(get_additional_configuration => AdditionalConfiguration);
     implement_inner!(set_additional_configuration |configuration: AdditionalConfiguration| => ());
     implement_inner!(get_default_configuration => AdditionalConfiguration);
+    implement_inner!(apply_profile |profile: ImageProfile| => ());
+    implement_inner!(apply_role_defaults => ());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::mock::MockCamera;
+
+    #[test]
+    fn display_identifies_the_camera() {
+        let camera = CameraModelHttp::Axis(AxisHttp {
+            id: 7,
+            host: Some("10.0.0.2".to_string()),
+            password: Some("hunter2".to_string()),
+            ..Default::default()
+        });
+
+        assert_eq!(camera.to_string(), "Axis(7@10.0.0.2)");
+        assert_eq!(CameraModelHttp::Unknown.to_string(), "Unknown(0@unknown)");
+
+        let debug = format!("{camera:?}");
+        assert!(debug.contains("10.0.0.2"), "{debug}");
+        assert!(!debug.contains("hunter2"), "{debug}");
+    }
+
+    #[tokio::test]
+    async fn new_credentials_are_used_on_the_next_request() {
+        let mock = MockCamera::start(vec![(
+            "GET /axis-cgi/param.cgi",
+            200,
+            "root.Image.I0.Stream.FPS=25\n".to_string(),
+        )])
+        .await;
+        let mut camera = CameraModelHttp::Axis(AxisHttp {
+            host: Some(mock.host.clone()),
+            username: Some("old".to_string()),
+            password: Some("old".to_string()),
+            ..Default::default()
+        });
+
+        camera.get_fps().await.unwrap();
+        camera.set_credentials(Some("new".to_string()), Some("new".to_string()));
+        camera.get_fps().await.unwrap();
+
+        let usernames: Vec<_> = mock.requests().into_iter().map(|r| r.username).collect();
+        assert_eq!(
+            usernames,
+            [Some("old".to_string()), Some("new".to_string())]
+        );
+    }
+
+    #[test]
+    fn new_credentials_reset_hikvision_init() {
+        let hikvision = HikvisionHttp::default();
+        hikvision
+            .initialized
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        let mut camera = CameraModelHttp::Hikvision(hikvision);
+
+        camera.set_credentials(Some("admin".to_string()), Some("new".to_string()));
+
+        let CameraModelHttp::Hikvision(hikvision) = camera else {
+            unreachable!()
+        };
+        assert_eq!(hikvision.password.as_deref(), Some("new"));
+        assert!(!hikvision
+            .initialized
+            .load(std::sync::atomic::Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn unsupported_calls_are_none() {
+        let camera = CameraModelHttp::Unknown;
+
+        assert!(matches!(camera.try_get_fps().await, Ok(None)));
+        assert!(matches!(camera.try_switch_spotlight(true).await, Ok(None)));
+    }
+
+    #[tokio::test]
+    async fn image_settings_are_vendor_tagged() {
+        let mock = MockCamera::start(vec![(
+            "GET /ISAPI/Image/channels/1",
+            200,
+            "<ImageChannel><id>1</id><enabled>true</enabled></ImageChannel>".to_string(),
+        )])
+        .await;
+        let hikvision = HikvisionHttp {
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        };
+        hikvision
+            .initialized
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        let camera = CameraModelHttp::Hikvision(hikvision);
+
+        let settings = camera.get_vendor_image_settings().await.unwrap();
+
+        let json = serde_json::to_value(&settings).unwrap();
+        assert_eq!(json["vendor"], "hikvision");
+        assert_eq!(json["settings"]["id"], 1);
+        assert!(matches!(
+            CameraModelHttp::Axis(AxisHttp::default())
+                .get_vendor_image_settings()
+                .await,
+            Err(IpCamerasError::NotAvialiableApi)
+        ));
+    }
+
+    fn assert_redacted(debug: String) {
+        assert!(debug.contains(r#"password: Some("***")"#), "{debug}");
+        assert!(!debug.contains("hunter2"), "{debug}");
+    }
+
+    #[test]
+    fn models_redact_passwords() {
+        let password = Some("hunter2".to_string());
+
+        assert_redacted(format!(
+            "{:?}",
+            AxisHttp {
+                password: password.clone(),
+                ..Default::default()
+            }
+        ));
+        assert_redacted(format!(
+            "{:?}",
+            DahuaHttp {
+                password: password.clone(),
+                ..Default::default()
+            }
+        ));
+        assert_redacted(format!(
+            "{:?}",
+            StilsoftHttp {
+                password: password.clone(),
+                ..Default::default()
+            }
+        ));
+        assert_redacted(format!(
+            "{:?}",
+            HikvisionHttp {
+                password,
+                ..Default::default()
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn unknown_model_has_no_fps_fallback() {
+        let camera = CameraModelHttp::Unknown;
+
+        assert!(matches!(
+            camera.get_fps().await,
+            Err(IpCamerasError::NotAvialiableApi)
+        ));
+        assert!(matches!(
+            camera.set_fps(FpsValue::from(25u32)).await,
+            Err(IpCamerasError::NotAvialiableApi)
+        ));
+    }
 }
@@ -1,26 +1,55 @@
 use async_trait::*;
 
-use std::io::ErrorKind;
+use std::{collections::HashMap, io::ErrorKind, sync::OnceLock};
 
 use serde::{de::DeserializeOwned, Serialize};
 
 use domain::{stream::Resource, CameraId};
+use futures::Stream;
 use pulsar_core::prelude::*;
+use tokio::time::Duration;
 
 use crate::{
-    utils::{focus::*, handler::*, request::*, serde::axis::*},
+    utils::{
+        focus::*, handler::*, poll::poll_stream, ptz::*, request::*, serde::axis::*,
+        status::SystemStatus, time_zone::*,
+    },
     IpCamerasError, DEFAULT_TIMEOUT,
 };
 
 use onvif::FpsValue;
 
-#[derive(Debug)]
+pub const DEFAULT_SPOTLIGHT_PORT: &str = "2";
+
 pub struct AxisHttp {
     pub id: CameraId,
     pub host: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
     pub timeout: u64,
+    pub onvif_fallback: bool,
+    // Sent with every request, e.g. the User-Agent some firmwares check
+    pub headers: Vec<Header>,
+    // I/O port wired to the spotlight, site specific
+    pub spotlight_port: String,
+    // Probed once on the first PTZ call, a camera doesn't grow a PTZ head
+    pub is_ptz: OnceLock<bool>,
+}
+
+impl std::fmt::Debug for AxisHttp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AxisHttp")
+            .field("id", &self.id)
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("password", &redact(&self.password))
+            .field("timeout", &self.timeout)
+            .field("onvif_fallback", &self.onvif_fallback)
+            .field("headers", &redact_headers(&self.headers))
+            .field("spotlight_port", &self.spotlight_port)
+            .field("is_ptz", &self.is_ptz.get())
+            .finish()
+    }
 }
 
 impl From<Resource> for AxisHttp {
@@ -45,6 +74,10 @@ impl Default for AxisHttp {
             username: None,
             password: None,
             timeout: DEFAULT_TIMEOUT,
+            onvif_fallback: false,
+            headers: Vec::new(),
+            spotlight_port: DEFAULT_SPOTLIGHT_PORT.to_string(),
+            is_ptz: OnceLock::new(),
         }
     }
 }
@@ -70,10 +103,22 @@ impl ApiHandler for AxisHttp {
         }
     }
 
+    fn timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    fn extra_headers(&self) -> &[Header] {
+        &self.headers
+    }
+
+    fn onvif_fallback(&self) -> bool {
+        self.onvif_fallback
+    }
+
     // EXTERNAL API
     async fn switch_spotlight(&self, enabled: bool) -> Result<(), IpCamerasError> {
         let port = Port {
-            port: "2",
+            port: self.spotlight_port.clone(),
             normal_state: enabled.into(),
             state: enabled.into(),
         };
@@ -85,28 +130,17 @@ impl ApiHandler for AxisHttp {
     }
 
     async fn get_spotlight_state(&self) -> Result<bool, IpCamerasError> {
-        let response: ProjectorsData = self
-            .axis_request::<_, [Port; 0]>(RequestParams::GetPorts)
-            .await?;
-
-        let port = response
-            .items
-            .into_iter()
-            .find(|item| item.port == "2")
-            .ok_or(ErrorKind::NotFound)?;
+        let port = self.io_port(&self.spotlight_port).await?;
 
         Ok(port.state == PortState::Closed && port.normal_state == PortState::Closed)
     }
 
     async fn get_fps(&self) -> Result<FpsValue, IpCamerasError> {
-        let fps_val = Self::parse_int(
-            &self
-                .get(
-                    "param",
-                    &[("action", "list"), ("group", "Image.I0.Stream.FPS")],
-                )
-                .await?,
-        );
+        let params = self.axis_get_param("Image.I0.Stream.FPS").await?;
+        let fps_val = params
+            .get("Image.I0.Stream.FPS")
+            .and_then(|v| Self::parse_int::<u32>(v));
+
         if fps_val.is_some() {
             Ok(fps_val.unwrap_or_default().into())
         } else {
@@ -115,90 +149,394 @@ impl ApiHandler for AxisHttp {
     }
 
     async fn set_fps(&self, fps: FpsValue) -> Result<(), IpCamerasError> {
-        if self
-            .get(
-                "param",
-                &[
-                    ("action", "update"),
-                    ("Image.I0.Stream.FPS", &fps.to_string()),
-                ],
-            )
-            .await?
-            .starts_with("OK")
-        {
-            Ok(())
+        self.axis_set_params(&[("Image.I0.Stream.FPS", &fps.to_string())])
+            .await
+    }
+
+    async fn get_supported_fps(&self) -> Result<Vec<FpsValue>, IpCamerasError> {
+        let fps = Self::parse_fps_options(&self.axis_get_param("Properties.Image").await?);
+
+        if fps.is_empty() {
+            Err(IpCamerasError::NotAvialiableApi)
         } else {
-            Err(ErrorKind::InvalidData.into())
+            Ok(fps)
         }
     }
 
-    async fn set_date_time(&self, date_time: chrono::NaiveDateTime) -> Result<(), IpCamerasError> {
-        self.generic_request(
-            "time",
-            SetTimeZoneRequest {
-                time_zone: "UTC".into(),
-            },
-        )
-        .await?;
+    async fn set_date_time(
+        &self,
+        date_time: chrono::DateTime<chrono::FixedOffset>,
+    ) -> Result<(), IpCamerasError> {
+        let _: serde_json::Value = self
+            .generic_request(
+                "time",
+                SetPosixTimeZoneRequest {
+                    posix_time_zone: posix_time_zone(date_time.offset()),
+                },
+            )
+            .await?;
 
-        self.generic_request("time", SetDateTimeRequest { date_time })
+        let _: serde_json::Value = self
+            .generic_request(
+                "time",
+                SetDateTimeRequest {
+                    date_time: date_time.naive_utc(),
+                },
+            )
             .await?;
 
         Ok(())
     }
 
-    async fn get_focus_capabilities(&self) -> Result<FocusCapabilities, IpCamerasError> {
-        Ok(FocusCapabilities::new().absolute(0.0, 1.0, 0.001))
+    async fn get_time_zone(&self) -> Result<String, IpCamerasError> {
+        let info: DateTimeInfo = self
+            .generic_request("time", GetDateTimeInfoRequest {})
+            .await?;
+
+        Ok(info.posix_time_zone)
     }
 
-    async fn get_focus_absolute(&self) -> Result<FocusValue, IpCamerasError> {
-        use std::str::FromStr;
-        use xml::{
-            attribute::OwnedAttribute,
-            reader::{EventReader, XmlEvent},
-        };
+    async fn set_time_zone(&self, tz: String) -> Result<(), IpCamerasError> {
+        validate_time_zone(&tz)?;
 
-        let req = self
-            .get("opticssetup", &[("monitor", "poll"), ("source", "1")])
+        let _: serde_json::Value = self
+            .generic_request(
+                "time",
+                SetPosixTimeZoneRequest {
+                    posix_time_zone: tz,
+                },
+            )
             .await?;
-        let parser = EventReader::new(req.as_bytes());
 
-        for elem in parser.into_iter().flatten() {
-            if let XmlEvent::StartElement {
-                name, attributes, ..
-            } = elem
-            {
-                if name.local_name == "opticsSetupState" {
-                    for OwnedAttribute { name, value } in &attributes {
-                        if name.local_name == "focusPosition" {
-                            if let Ok(focus) = f32::from_str(value) {
-                                return Ok(focus);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        Err(ErrorKind::InvalidData.into())
+        Ok(())
+    }
+
+    async fn verify_credentials(&self) -> Result<bool, IpCamerasError> {
+        self.check_credentials(format!(
+            "http://{}/axis-cgi/param.cgi?action=list&group=Brand",
+            self.host()
+        ))
+        .await
+    }
+
+    async fn get_snapshot(&self) -> Result<Vec<u8>, IpCamerasError> {
+        self.request_bytes(
+            format!("http://{}/axis-cgi/jpg/image.cgi", self.host()),
+            None,
+            Some(LONG_REQUEST_TIMEOUT),
+        )
+        .await
+    }
+
+    async fn get_system_status(&self) -> Result<SystemStatus, IpCamerasError> {
+        let report = self.get("serverreport", &[("mode", "text")]).await?;
+
+        Ok(SystemStatus {
+            uptime: Self::parse_uptime(&report),
+            ..Default::default()
+        })
+    }
+
+    async fn get_settings_json(&self) -> Result<serde_json::Value, IpCamerasError> {
+        let image = self.axis_get_param("Image").await?;
+
+        Ok(serde_json::json!({ "image": image }))
+    }
+
+    async fn is_ptz(&self) -> Result<bool, IpCamerasError> {
+        self.check_is_ptz().await
+    }
+
+    async fn ptz_absolute_move(&self, position: PtzVector) -> Result<(), IpCamerasError> {
+        self.ptz_request(Self::ptz_absolute_params(position)).await
+    }
+
+    async fn ptz_relative_move(&self, translation: PtzVector) -> Result<(), IpCamerasError> {
+        self.ptz_request(Self::ptz_relative_params(translation))
+            .await
+    }
+
+    async fn ptz_continuous_move(&self, speed: PtzVector) -> Result<(), IpCamerasError> {
+        self.ptz_request(Self::ptz_continuous_params(speed)).await
+    }
+
+    async fn ptz_stop(&self) -> Result<(), IpCamerasError> {
+        self.ptz_request(Self::ptz_continuous_params(PtzVector::default()))
+            .await
+    }
+
+    async fn get_focus_capabilities(&self) -> Result<FocusCapabilities, IpCamerasError> {
+        Ok(FocusCapabilities::new().absolute(0.0, 1.0, 0.001))
+    }
+
+    async fn get_focus_absolute(&self) -> Result<FocusValue, IpCamerasError> {
+        self.get_optics_setup_state()
+            .await?
+            .focus_position
+            .ok_or_else(|| ErrorKind::InvalidData.into())
     }
 
     async fn set_focus_absolute(&self, focus: FocusValue) -> Result<(), IpCamerasError> {
-        if self
+        let response = self
             .get(
                 "opticssetup",
                 &[("afocus", &focus.to_string()), ("source", "1")],
             )
-            .await?
-            .starts_with("ok")
-        {
+            .await?;
+
+        if response.starts_with("ok") {
             Ok(())
         } else {
-            Err(ErrorKind::InvalidData.into())
+            Err(Self::parse_error(&response))
         }
     }
 }
 
 impl AxisHttp {
+    // Timeout in seconds
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_credentials(mut self, username: Option<String>, password: Option<String>) -> Self {
+        self.username = username;
+        self.password = password;
+        self
+    }
+
+    // Replaces the default reqwest/curl agent
+    pub fn with_user_agent(mut self, agent: impl Into<String>) -> Self {
+        self.headers.retain(|h| !matches!(h, Header::UserAgent(_)));
+        self.headers.push(Header::UserAgent(agent.into()));
+        self
+    }
+
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers.extend(
+            headers
+                .into_iter()
+                .map(|(name, value)| Header::Custom(name, value)),
+        );
+        self
+    }
+
+    // For firmwares that break compressed answers
+    pub fn without_compression(mut self) -> Self {
+        if !self.headers.contains(&Header::NoCompression) {
+            self.headers.push(Header::NoCompression);
+        }
+        self
+    }
+
+    // Live focus position for tuning UIs, sampled every `interval`
+    pub fn focus_position_stream(
+        &self,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<FocusValue, IpCamerasError>> + '_ {
+        poll_stream(move || self.get_focus_absolute(), interval)
+    }
+
+    // Every I/O port the camera reports, e.g. to find where the spotlight is wired
+    pub async fn list_io_ports(&self) -> Result<Vec<IoPort>, IpCamerasError> {
+        let response: ProjectorsData = self
+            .axis_request::<_, [Port; 0]>(RequestParams::GetPorts)
+            .await?;
+
+        Ok(response.items.into_iter().map(IoPort::from).collect())
+    }
+
+    // Drives a single output, keeping its configured normal state, and reads it back
+    pub async fn set_io_port(&self, port: &str, closed: bool) -> Result<IoPort, IpCamerasError> {
+        let current = self.io_port(port).await?;
+        let request = Port {
+            port: current.port,
+            normal_state: current.normal_state,
+            state: closed.into(),
+        };
+
+        self.axis_request::<SwitchData, _>(RequestParams::SetPorts { ports: [request] })
+            .await?;
+
+        self.io_port(port).await
+    }
+
+    async fn io_port(&self, port: &str) -> Result<IoPort, IpCamerasError> {
+        self.list_io_ports()
+            .await?
+            .into_iter()
+            .find(|item| item.port == port)
+            .ok_or_else(|| {
+                warn!("Axis camera {} has no I/O port {}", self.host(), port);
+                IpCamerasError::NotAvialiableApi
+            })
+    }
+
+    // Reads a param.cgi group into a map of `Group.Param` -> value (without the `root.` prefix)
+    pub async fn axis_get_param(
+        &self,
+        group: &str,
+    ) -> Result<HashMap<String, String>, IpCamerasError> {
+        let response = self
+            .get("param", &[("action", "list"), ("group", group)])
+            .await?;
+
+        if Self::is_error(&response) {
+            return Err(Self::parse_error(&response));
+        }
+
+        Ok(Self::parse_params(&response))
+    }
+
+    pub async fn axis_set_params(&self, pairs: &[(&str, &str)]) -> Result<(), IpCamerasError> {
+        let mut params = vec![("action", "update")];
+        params.extend_from_slice(pairs);
+
+        Self::check_update(&self.get("param", &params).await?)
+    }
+
+    // `update` answers `OK` on success, an error line otherwise
+    fn check_update(response: &str) -> Result<(), IpCamerasError> {
+        if response.starts_with("OK") {
+            Ok(())
+        } else {
+            Err(Self::parse_error(response))
+        }
+    }
+
+    fn is_error(input: &str) -> bool {
+        input.lines().any(|line| {
+            line.trim_start_matches('#')
+                .trim_start()
+                .to_ascii_lowercase()
+                .starts_with("error")
+        })
+    }
+
+    // param.cgi answers `# Error: <message>`, opticssetup answers `error: <message>`
+    fn parse_error(input: &str) -> IpCamerasError {
+        let message = input
+            .lines()
+            .map(|line| line.trim_start_matches('#').trim())
+            .find(|line| line.to_ascii_lowercase().starts_with("error"))
+            .map(|line| {
+                line.split_once(':')
+                    .map_or(line, |(_, message)| message)
+                    .trim()
+                    .to_string()
+            })
+            .unwrap_or_else(|| input.trim().to_string());
+
+        warn!("Axis camera error: {}", message);
+        IpCamerasError::Axis { message }
+    }
+
+    fn parse_params(input: &str) -> HashMap<String, String> {
+        input
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| {
+                (
+                    key.trim().trim_start_matches("root.").to_string(),
+                    value.trim().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    async fn ptz_request(&self, params: Vec<(&'static str, String)>) -> Result<(), IpCamerasError> {
+        if !self.check_is_ptz().await? {
+            return Err(IpCamerasError::NotAvialiableApi);
+        }
+
+        let params: Vec<(&str, &str)> = params.iter().map(|(p, v)| (*p, v.as_str())).collect();
+        let response = self.get("com/ptz", &params).await?;
+
+        // ptz.cgi answers with an empty body on success
+        if response.trim().is_empty() {
+            Ok(())
+        } else {
+            Err(Self::parse_error(&response))
+        }
+    }
+
+    async fn check_is_ptz(&self) -> Result<bool, IpCamerasError> {
+        if let Some(is_ptz) = self.is_ptz.get() {
+            return Ok(*is_ptz);
+        }
+
+        let is_ptz = self
+            .get("com/ptz", &[("info", "1")])
+            .await?
+            .contains("continuouspantiltmove");
+
+        Ok(*self.is_ptz.get_or_init(|| is_ptz))
+    }
+
+    // pan [-180, 180] degrees, tilt [-90, 90] degrees, zoom [1, 9999]
+    pub fn ptz_absolute_params(position: PtzVector) -> Vec<(&'static str, String)> {
+        let p = position.clamped(0.);
+
+        vec![
+            ("pan", format!("{:.2}", p.pan * 180.)),
+            ("tilt", format!("{:.2}", p.tilt * 90.)),
+            ("zoom", format!("{:.0}", 1. + p.zoom * 9998.)),
+        ]
+    }
+
+    pub fn ptz_relative_params(translation: PtzVector) -> Vec<(&'static str, String)> {
+        let t = translation.clamped(-1.);
+
+        vec![
+            ("rpan", format!("{:.2}", t.pan * 180.)),
+            ("rtilt", format!("{:.2}", t.tilt * 90.)),
+            ("rzoom", format!("{:.0}", t.zoom * 9998.)),
+        ]
+    }
+
+    // speeds are in [-100, 100]
+    pub fn ptz_continuous_params(speed: PtzVector) -> Vec<(&'static str, String)> {
+        let s = speed.clamped(-1.);
+
+        vec![
+            (
+                "continuouspantiltmove",
+                format!("{:.0},{:.0}", s.pan * 100., s.tilt * 100.),
+            ),
+            ("continuouszoommove", format!("{:.0}", s.zoom * 100.)),
+        ]
+    }
+
+    // Updates a text overlay already placed on the image, to stamp live data (speed, plate)
+    pub async fn set_dynamic_overlay(&self, id: u32, text: &str) -> Result<(), IpCamerasError> {
+        if !(DYNAMIC_OVERLAY_ID_MIN..=DYNAMIC_OVERLAY_ID_MAX).contains(&id) {
+            return Err(IpCamerasError::InvalidParam(format!(
+                "dynamic overlay id must be in \
+                 {DYNAMIC_OVERLAY_ID_MIN}..={DYNAMIC_OVERLAY_ID_MAX}, got {id}"
+            )));
+        }
+
+        let _: serde_json::Value = self
+            .generic_request(
+                "dynamicoverlay/dynamicoverlay",
+                SetDynamicOverlayTextRequest {
+                    identity: id,
+                    text: text.to_string(),
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_optics_setup_state(&self) -> Result<OpticsSetupState, IpCamerasError> {
+        let body = self
+            .get("opticssetup", &[("monitor", "poll"), ("source", "1")])
+            .await?;
+
+        OpticsSetupState::parse(&body).ok_or_else(|| ErrorKind::InvalidData.into())
+    }
+
     async fn axis_request<
         D: DeserializeOwned + std::fmt::Debug,
         P: AsRef<[Port]> + Serialize + Send + Sync + 'static,
@@ -206,16 +544,16 @@ impl AxisHttp {
         &self,
         params: RequestParams<P>,
     ) -> Result<D, IpCamerasError> {
-        let result: Response<D> = serde_json::from_str(
-            self.request(
+        let body = self
+            .request(
                 format!("http://{}/axis-cgi/io/portmanagement.cgi", self.host()),
                 Some(serde_json::to_string(&ApiRequest::from(params))?),
                 Method::POST,
                 Some(vec![Header::JSON]),
             )
-            .await?
-            .as_ref(),
-        )?;
+            .await?;
+        let result: Response<D> = serde_json::from_str(&body)
+            .map_err(|source| IpCamerasError::json_parse(source, &body))?;
 
         Ok(result.data)
     }
@@ -229,16 +567,16 @@ impl AxisHttp {
         cgi: S,
         payload: impl Into<GenericApiRequest<P>> + Send + 'static,
     ) -> Result<R, IpCamerasError> {
-        let result: Response<R> = serde_json::from_str(
-            self.request(
+        let body = self
+            .request(
                 format!("http://{}/axis-cgi/{}.cgi", self.host(), cgi),
                 Some(serde_json::to_string(&payload.into())?),
                 Method::GET,
                 Some(vec![Header::JSON]),
             )
-            .await?
-            .as_ref(),
-        )?;
+            .await?;
+        let result: Response<R> = serde_json::from_str(&body)
+            .map_err(|source| IpCamerasError::json_parse(source, &body))?;
 
         Ok(result.data)
     }
@@ -267,14 +605,581 @@ impl AxisHttp {
             .await?)
     }
 
-    fn parse_int(input: &str) -> Option<u32> {
-        input
-            .chars()
-            .skip_while(|ch| ch != &'=')
-            .skip_while(|ch| !ch.is_digit(10))
-            .take_while(|ch| ch.is_digit(10))
-            .fold(None, |acc, ch| {
-                ch.to_digit(10).map(|b| acc.unwrap_or(0) * 10 + b)
+    // Values like `Properties.Image.MaxFPS=30` or `...FrameRate=1,2,5,10,15,25`.
+    // Only the listed rates are returned, a lone maximum doesn't mean every lower rate works.
+    fn parse_fps_options(params: &HashMap<String, String>) -> Vec<FpsValue> {
+        let mut fps: Vec<FpsValue> = Vec::new();
+
+        for (key, value) in params {
+            if !(key.ends_with("FPS") || key.ends_with("FrameRate")) {
+                continue;
+            }
+
+            fps.extend(
+                value
+                    .split(',')
+                    .filter_map(|v| v.trim().parse::<u32>().ok())
+                    .map(FpsValue::from),
+            );
+        }
+
+        fps.sort_unstable();
+        fps.dedup();
+        fps
+    }
+
+    // The report embeds `uptime` output: " 10:00:01 up 3 days,  2:14,  load average: ..."
+    // (the last part may also be "2 min" or "2:14" alone)
+    fn parse_uptime(report: &str) -> Option<u64> {
+        let line = report
+            .lines()
+            .find(|l| l.contains(" up ") && l.contains("load average"))?;
+        let up = line.split(" up ").nth(1)?;
+        let up = up.split("load average").next()?;
+
+        let mut seconds = 0u64;
+        for part in up.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            if let Some(days) = part
+                .strip_suffix("days")
+                .or_else(|| part.strip_suffix("day"))
+            {
+                seconds += days.trim().parse::<u64>().ok()? * 86400;
+            } else if let Some(min) = part.strip_suffix("min") {
+                seconds += min.trim().parse::<u64>().ok()? * 60;
+            } else if let Some((h, m)) = part.split_once(':') {
+                seconds +=
+                    h.trim().parse::<u64>().ok()? * 3600 + m.trim().parse::<u64>().ok()? * 60;
+            }
+        }
+
+        Some(seconds)
+    }
+
+    // Parses the first number in `input`, keeping a leading `-` so signed targets work.
+    // Overflowing values (and negative ones for unsigned targets) yield `None`.
+    fn parse_int<T: std::str::FromStr>(input: &str) -> Option<T> {
+        let start = input.find(|ch: char| ch.is_ascii_digit())?;
+        let start = match input[..start].ends_with('-') {
+            true => start - 1,
+            false => start,
+        };
+
+        let number = &input[start..];
+        let end = number
+            .char_indices()
+            .skip(1)
+            .find(|(_, ch)| !ch.is_ascii_digit())
+            .map_or(number.len(), |(i, _)| i);
+
+        number[..end].parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::mock::MockCamera;
+
+    #[test]
+    fn configured_headers_are_merged() {
+        let camera = AxisHttp::default()
+            .with_user_agent("first")
+            .with_user_agent("pulsar/1.0")
+            .with_headers(vec![("X-Api-Key".to_string(), "secret".to_string())]);
+
+        assert_eq!(
+            camera.merge_headers(Some(vec![Header::JSON])),
+            Some(vec![
+                Header::JSON,
+                Header::UserAgent("pulsar/1.0".to_string()),
+                Header::Custom("X-Api-Key".to_string(), "secret".to_string()),
+            ])
+        );
+        assert_eq!(AxisHttp::default().merge_headers(None), None);
+        assert!(!format!("{camera:?}").contains("secret"));
+    }
+
+    #[test]
+    fn param_group_into_map() {
+        let params = AxisHttp::parse_params(
+            "root.Image.I0.Appearance.Resolution=1920x1080\n\
+             root.Image.I0.Appearance.Compression=30\n\
+             root.Image.I0.Text.String = Gate 1 \n\
+             \n",
+        );
+
+        assert_eq!(params.len(), 3);
+        assert_eq!(params["Image.I0.Appearance.Resolution"], "1920x1080");
+        assert_eq!(params["Image.I0.Appearance.Compression"], "30");
+        assert_eq!(params["Image.I0.Text.String"], "Gate 1");
+    }
+
+    #[test]
+    fn param_update_answer() {
+        assert!(AxisHttp::check_update("OK\n").is_ok());
+
+        let error = AxisHttp::check_update(
+            "# Error: Error setting 'root.Image.I0.Appearance.Compression' to 'abc'!",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            IpCamerasError::Axis { message }
+                if message == "Error setting 'root.Image.I0.Appearance.Compression' to 'abc'!"
+        ));
+    }
+
+    fn axis_message(error: IpCamerasError) -> String {
+        match error {
+            IpCamerasError::Axis { message } => message,
+            error => panic!("not an Axis error: {error:?}"),
+        }
+    }
+
+    #[test]
+    fn axis_error_bodies() {
+        let param = "# Error: Error -1 getting param in group 'Image.I9'\n";
+        assert!(AxisHttp::is_error(param));
+        assert_eq!(
+            axis_message(AxisHttp::parse_error(param)),
+            "Error -1 getting param in group 'Image.I9'"
+        );
+
+        let optics = "error: Optics setup is not supported\n";
+        assert!(AxisHttp::is_error(optics));
+        assert_eq!(
+            axis_message(AxisHttp::parse_error(optics)),
+            "Optics setup is not supported"
+        );
+
+        // Without a recognizable error line the whole answer is the message
+        assert_eq!(
+            axis_message(AxisHttp::parse_error("  Request failed  ")),
+            "Request failed"
+        );
+    }
+
+    #[test]
+    fn regular_answers_are_not_errors() {
+        assert!(!AxisHttp::is_error(
+            "root.Image.I0.Appearance.Resolution=1920x1080\n"
+        ));
+        assert!(!AxisHttp::is_error("OK\n"));
+        assert!(!AxisHttp::is_error(""));
+    }
+
+    fn fps_params(params: &[(&str, &str)]) -> HashMap<String, String> {
+        params
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn fps(values: &[u32]) -> Vec<FpsValue> {
+        values.iter().copied().map(FpsValue::from).collect()
+    }
+
+    #[test]
+    fn uptime_from_server_report() {
+        let report = |uptime: &str| {
+            format!("----- Uptime -----\n 10:00:01 up {uptime},  load average: 0.10, 0.20, 0.30\n")
+        };
+
+        assert_eq!(
+            AxisHttp::parse_uptime(&report("3 days,  2:14")),
+            Some(3 * 86400 + 2 * 3600 + 14 * 60)
+        );
+        assert_eq!(
+            AxisHttp::parse_uptime(&report("1 day, 5 min")),
+            Some(86400 + 5 * 60)
+        );
+        assert_eq!(AxisHttp::parse_uptime(&report("2:14")), Some(8040));
+        assert_eq!(AxisHttp::parse_uptime("no uptime here"), None);
+    }
+
+    #[test]
+    fn parse_int_values() {
+        assert_eq!(AxisHttp::parse_int::<u32>("25"), Some(25));
+        assert_eq!(AxisHttp::parse_int::<u32>("fps=30 "), Some(30));
+        assert_eq!(AxisHttp::parse_int::<u32>("4294967295"), Some(u32::MAX));
+        assert_eq!(AxisHttp::parse_int::<i32>("offset=-15"), Some(-15));
+        assert_eq!(AxisHttp::parse_int::<u32>("none"), None);
+    }
+
+    #[test]
+    fn parse_int_rejects_out_of_range() {
+        assert_eq!(AxisHttp::parse_int::<u32>("4294967296"), None);
+        assert_eq!(AxisHttp::parse_int::<u32>("99999999999999999999"), None);
+        assert_eq!(AxisHttp::parse_int::<u32>("-5"), None);
+    }
+
+    #[test]
+    fn single_max_fps_is_not_expanded() {
+        let params = fps_params(&[("Properties.Image.MaxFPS", "30")]);
+
+        assert_eq!(AxisHttp::parse_fps_options(&params), fps(&[30]));
+    }
+
+    #[test]
+    fn fps_lists_are_merged() {
+        let params = fps_params(&[
+            ("Properties.Image.MaxFPS", "25"),
+            ("Properties.Image.FrameRate", "1, 2,5,10,15,25"),
+            ("Properties.Image.Resolution", "1920x1080,1280x720"),
+            ("Properties.Image.Rotation", "0,180"),
+        ]);
+
+        assert_eq!(
+            AxisHttp::parse_fps_options(&params),
+            fps(&[1, 2, 5, 10, 15, 25])
+        );
+        assert!(AxisHttp::parse_fps_options(&HashMap::new()).is_empty());
+    }
+
+    fn query(params: Vec<(&'static str, String)>) -> String {
+        params
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    #[test]
+    fn ptz_absolute_query() {
+        assert_eq!(
+            query(AxisHttp::ptz_absolute_params(PtzVector::new(0.5, -0.5, 1.))),
+            "pan=90.00&tilt=-45.00&zoom=9999"
+        );
+        assert_eq!(
+            query(AxisHttp::ptz_absolute_params(PtzVector::new(2., -2., -0.5))),
+            "pan=180.00&tilt=-90.00&zoom=1"
+        );
+    }
+
+    #[test]
+    fn ptz_continuous_query() {
+        assert_eq!(
+            query(AxisHttp::ptz_continuous_params(PtzVector::new(
+                0.5, -1., 0.25
+            ))),
+            "continuouspantiltmove=50,-100&continuouszoommove=25"
+        );
+        assert_eq!(
+            query(AxisHttp::ptz_continuous_params(PtzVector::new(0., 0., 0.))),
+            "continuouspantiltmove=0,0&continuouszoommove=0"
+        );
+    }
+
+    #[tokio::test]
+    async fn ptz_probe_is_cached() {
+        let camera = AxisHttp::default();
+        camera.is_ptz.set(false).unwrap();
+
+        // Answered from the cache, no camera is listening on the default host
+        assert!(!camera.is_ptz().await.unwrap());
+        assert!(matches!(
+            camera.ptz_stop().await,
+            Err(IpCamerasError::NotAvialiableApi)
+        ));
+    }
+
+    const PORTS: &str = r#"{"apiVersion": "1.0", "method": "getPorts", "data": {
+        "numberOfPorts": 2,
+        "items": [
+            {"port": "0", "state": "open", "normalState": "open"},
+            {"port": "4", "state": "closed", "normalState": "closed"}
+        ]
+    }}"#;
+
+    async fn spotlight_camera(spotlight_port: &str) -> (AxisHttp, MockCamera) {
+        let mock = MockCamera::start(vec![(
+            "POST /axis-cgi/io/portmanagement.cgi",
+            200,
+            PORTS.to_string(),
+        )])
+        .await;
+        let camera = AxisHttp {
+            host: Some(mock.host.clone()),
+            spotlight_port: spotlight_port.to_string(),
+            ..Default::default()
+        };
+
+        (camera, mock)
+    }
+
+    #[tokio::test]
+    async fn every_io_port_is_listed() {
+        let (camera, _mock) = spotlight_camera(DEFAULT_SPOTLIGHT_PORT).await;
+
+        assert_eq!(
+            camera.list_io_ports().await.unwrap(),
+            [
+                IoPort {
+                    port: "0".to_string(),
+                    state: PortState::Open,
+                    normal_state: PortState::Open,
+                },
+                IoPort {
+                    port: "4".to_string(),
+                    state: PortState::Closed,
+                    normal_state: PortState::Closed,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn set_ports_request() {
+        let request = ApiRequest::from(RequestParams::SetPorts {
+            ports: [Port {
+                port: "4".to_string(),
+                normal_state: PortState::Closed,
+                state: true.into(),
+            }],
+        });
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "apiVersion": "1.0",
+                "method": "setPorts",
+                "params": {
+                    "ports": [{ "port": "4", "normalState": "closed", "state": "closed" }],
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn io_port_is_driven_and_read_back() {
+        // Answers both `getPorts` and `setPorts`, each reads only its own fields
+        let mock = MockCamera::start(vec![(
+            "POST /axis-cgi/io/portmanagement.cgi",
+            200,
+            r#"{"apiVersion": "1.0", "method": "getPorts", "data": {
+                "numberOfPorts": 1,
+                "items": [{"port": "4", "state": "open", "normalState": "closed"}],
+                "ports": ["4"]
+            }}"#
+            .to_string(),
+        )])
+        .await;
+        let camera = AxisHttp {
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        };
+
+        let port = camera.set_io_port("4", false).await.unwrap();
+        assert_eq!(port.state, PortState::Open);
+
+        let bodies: Vec<serde_json::Value> = mock
+            .bodies("POST")
+            .iter()
+            .map(|body| serde_json::from_str(body).unwrap())
+            .collect();
+        assert_eq!(bodies.len(), 3);
+        // The configured normal state is kept
+        assert_eq!(
+            bodies[1]["params"]["ports"],
+            serde_json::json!([{ "port": "4", "normalState": "closed", "state": "open" }])
+        );
+
+        assert!(matches!(
+            camera.set_io_port("9", true).await,
+            Err(IpCamerasError::NotAvialiableApi)
+        ));
+    }
+
+    #[tokio::test]
+    async fn spotlight_on_custom_port() {
+        let (camera, _mock) = spotlight_camera("4").await;
+        assert!(camera.get_spotlight_state().await.unwrap());
+
+        let (camera, _mock) = spotlight_camera("0").await;
+        assert!(!camera.get_spotlight_state().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn absent_spotlight_port_is_not_available() {
+        let (camera, _mock) = spotlight_camera(DEFAULT_SPOTLIGHT_PORT).await;
+
+        assert!(matches!(
+            camera.get_spotlight_state().await,
+            Err(IpCamerasError::NotAvialiableApi)
+        ));
+    }
+
+    #[tokio::test]
+    async fn focus_stream_samples_the_lens() {
+        use futures::StreamExt;
+
+        let mock = MockCamera::start(vec![(
+            "GET /axis-cgi/opticssetup.cgi?monitor=poll",
+            200,
+            r#"<opticsSetupState focusPosition="0.5" zoomPosition="0.1"/>"#.to_string(),
+        )])
+        .await;
+        let camera = AxisHttp {
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        };
+
+        let samples: Vec<_> = camera
+            .focus_position_stream(Duration::from_millis(1))
+            .take(3)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert_eq!(samples, [0.5; 3]);
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn focus_is_read_from_the_optics_state() {
+        let mock = MockCamera::start(vec![(
+            "GET /axis-cgi/opticssetup.cgi?monitor=poll",
+            200,
+            r#"<opticsSetupResponse><opticsSetupState focusPosition="0.25" zoomPosition="0.5"
+               irisPosition="1"/></opticsSetupResponse>"#
+                .to_string(),
+        )])
+        .await;
+        let camera = AxisHttp {
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        };
+
+        assert_eq!(camera.get_focus_absolute().await.unwrap(), 0.25);
+        assert_eq!(
+            camera.get_optics_setup_state().await.unwrap().iris_position,
+            Some(1.)
+        );
+    }
+
+    #[tokio::test]
+    async fn ptz_is_probed_once() {
+        let mock = MockCamera::start(vec![(
+            "GET /axis-cgi/com/ptz.cgi?info=1",
+            200,
+            "Available commands:\ncontinuouspantiltmove=<int>,<int>\n".to_string(),
+        )])
+        .await;
+        let camera = AxisHttp {
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        };
+
+        assert!(camera.is_ptz().await.unwrap());
+        assert!(camera.is_ptz().await.unwrap());
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dynamic_overlay_request() {
+        let mock = MockCamera::start(vec![(
+            "GET /axis-cgi/dynamicoverlay/dynamicoverlay.cgi",
+            200,
+            r#"{"apiVersion":"1.0","method":"setText","data":{}}"#.to_string(),
+        )])
+        .await;
+        let camera = AxisHttp {
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        };
+
+        camera.set_dynamic_overlay(2, "88 km/h").await.unwrap();
+
+        let bodies = mock.bodies("GET");
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&bodies[0]).unwrap(),
+            serde_json::json!({
+                "apiVersion": "1.0",
+                "method": "setText",
+                "params": { "identity": 2, "text": "88 km/h" },
             })
+        );
+    }
+
+    #[tokio::test]
+    async fn dynamic_overlay_id_is_checked() {
+        let camera = AxisHttp::default();
+
+        for id in [DYNAMIC_OVERLAY_ID_MIN - 1, DYNAMIC_OVERLAY_ID_MAX + 1] {
+            let result = camera.set_dynamic_overlay(id, "text").await;
+            assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        }
+    }
+
+    async fn brand_camera(status: u16) -> (AxisHttp, MockCamera) {
+        let mock = MockCamera::start(vec![(
+            "GET /axis-cgi/param.cgi?action=list&group=Brand",
+            status,
+            String::new(),
+        )])
+        .await;
+        let camera = AxisHttp {
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        };
+
+        (camera, mock)
+    }
+
+    #[tokio::test]
+    async fn accepted_credentials_are_verified() {
+        let (camera, mock) = brand_camera(200).await;
+
+        assert!(camera.verify_credentials().await.unwrap());
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejected_credentials_are_false() {
+        for status in [401, 403] {
+            let (camera, _mock) = brand_camera(status).await;
+
+            assert!(!camera.verify_credentials().await.unwrap(), "{status}");
+        }
+    }
+
+    #[tokio::test]
+    async fn unreachable_camera_is_an_error() {
+        // Nothing listens on a port released right away
+        let host = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let camera = AxisHttp {
+            host: Some(host),
+            ..Default::default()
+        };
+
+        assert!(camera.verify_credentials().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn latency_includes_the_camera_delay() {
+        let delay = Duration::from_millis(50);
+        let mock = MockCamera::start_delayed(
+            vec![(
+                "GET /axis-cgi/param.cgi?action=list&group=Brand",
+                200,
+                String::new(),
+            )],
+            delay,
+        )
+        .await;
+        let camera = AxisHttp {
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        };
+
+        let latency = camera.measure_latency().await.unwrap();
+
+        assert!(latency >= delay, "{latency:?}");
     }
 }
@@ -1,25 +1,62 @@
-use std::io::ErrorKind;
+use std::{collections::HashMap, io::ErrorKind};
 
 use async_trait::*;
 use domain::{stream::Resource, CameraId};
+use futures::Stream;
 use onvif::FpsValue;
 use pulsar_core::prelude::*;
+use tokio::time::Duration;
 
 use crate::{
-    utils::{focus::*, handler::*, request::*, serde::dahua::*},
-    IpCamerasError, DEFAULT_TIMEOUT,
+    utils::{
+        focus::*,
+        handler::*,
+        poll::{poll_stream, poll_until},
+        request::*,
+        serde::dahua::*,
+        status::SystemStatus,
+        time_zone::*,
+        transport::validate_rtsp_port,
+    },
+    validate_image_level, DayNightMode, ImageAdjustment, IpCamerasError, SpotlightMode,
+    WhiteBalanceMode, DEFAULT_TIMEOUT,
 };
 
-const RETRIES: usize = 5;
-const INTERVAL: u64 = 400;
+pub const DEFAULT_CHANNEL: u32 = 1;
+pub const DEFAULT_FOCUS_RETRIES: usize = 5;
+pub const DEFAULT_FOCUS_INTERVAL: Duration = Duration::from_millis(400);
 
-#[derive(Debug)]
 pub struct DahuaHttp {
     pub id: CameraId,
     pub host: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
     pub timeout: u64,
+    pub onvif_fallback: bool,
+    // Sent with every request, e.g. the User-Agent some firmwares check
+    pub headers: Vec<Header>,
+    // Video input, numbered from 1 as in `snapshot.cgi`; config arrays index it from 0
+    pub channel: u32,
+    // Focus reads are repeated until the lens reports `Normal`; slow lenses need more tries
+    pub focus_retries: usize,
+    pub focus_interval: Duration,
+}
+
+impl std::fmt::Debug for DahuaHttp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DahuaHttp")
+            .field("id", &self.id)
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("password", &redact(&self.password))
+            .field("timeout", &self.timeout)
+            .field("onvif_fallback", &self.onvif_fallback)
+            .field("headers", &redact_headers(&self.headers))
+            .field("channel", &self.channel)
+            .field("focus_retries", &self.focus_retries)
+            .field("focus_interval", &self.focus_interval)
+            .finish()
+    }
 }
 
 impl From<Resource> for DahuaHttp {
@@ -44,6 +81,11 @@ impl Default for DahuaHttp {
             username: None,
             password: None,
             timeout: DEFAULT_TIMEOUT,
+            onvif_fallback: false,
+            headers: Vec::new(),
+            channel: DEFAULT_CHANNEL,
+            focus_retries: DEFAULT_FOCUS_RETRIES,
+            focus_interval: DEFAULT_FOCUS_INTERVAL,
         }
     }
 }
@@ -69,23 +111,38 @@ impl ApiHandler for DahuaHttp {
         }
     }
 
+    fn timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    fn extra_headers(&self) -> &[Header] {
+        &self.headers
+    }
+
+    fn onvif_fallback(&self) -> bool {
+        self.onvif_fallback
+    }
+
     // EXTERNAL API
     async fn set_fps(&self, fps: FpsValue) -> Result<(), IpCamerasError> {
-        let fps = Some(fps as f64);
-        self.set_config(Config {
-            fps,
-            ..Default::default()
-        })
-        .await
+        self.set_fps_on_stream(StreamKind::Main, fps).await
     }
 
     async fn get_fps(&self) -> Result<FpsValue, IpCamerasError> {
-        let result = Self::parse_output(&self.get_config("Encode").await?);
-        if result.is_some() {
-            Ok(result.unwrap_or_default().into())
-        } else {
-            Err(IpCamerasError::Fps)
-        }
+        self.get_fps_on_stream(StreamKind::Main).await
+    }
+
+    async fn get_supported_fps(&self) -> Result<Vec<FpsValue>, IpCamerasError> {
+        let output = self.get("encode", &[("action", "getConfigCaps")]).await?;
+
+        let max = output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(key, _)| key.ends_with(&keys::stream_video(StreamKind::Main, keys::FPS_MAX)))
+            .and_then(|(_, value)| value.trim().parse::<u32>().ok())
+            .ok_or(IpCamerasError::Fps)?;
+
+        Ok((1..=max).map(FpsValue::from).collect())
     }
 
     async fn switch_spotlight(&self, enabled: bool) -> Result<(), IpCamerasError> {
@@ -96,20 +153,180 @@ impl ApiHandler for DahuaHttp {
             ..Default::default()
         })
         .await
+        .map_err(Self::spotlight_error)
+    }
+
+    async fn set_spotlight_mode(&self, mode: SpotlightMode) -> Result<(), IpCamerasError> {
+        let alarm_mode =
+            AlarmMode::from_spotlight_mode(&mode).ok_or(IpCamerasError::NotAvialiableApi)?;
+
+        self.set_config(Config {
+            external_spotlight: Some(ExternalSpotlight::with_mode(alarm_mode)),
+            ..Default::default()
+        })
+        .await
+        .map_err(Self::spotlight_error)
     }
 
     async fn get_spotlight_state(&self) -> Result<bool, IpCamerasError> {
-        let output = self.get_config("AlarmOut").await?;
-        if output.contains("table.AlarmOut[0].Mode=0") {
-            Ok(false)
-        } else if output.contains("table.AlarmOut[0].Mode=1") {
-            Ok(true)
-        } else {
-            Err(ErrorKind::InvalidInput.into())
+        let config = parse_dahua_config(&self.get_config(keys::ALARM_OUT).await?);
+        let mode = Self::config_number(&config, &keys::alarm_out_mode(0));
+
+        match mode {
+            Some(mode)
+                if mode == AlarmMode::Automatic as u32 || mode == AlarmMode::ForceOff as u32 =>
+            {
+                Ok(false)
+            }
+            Some(mode) if mode == AlarmMode::ForceOn as u32 => Ok(true),
+            Some(mode) => Err(IpCamerasError::spotlight(
+                "Dahua",
+                format!("unexpected alarm output mode {mode}"),
+            )),
+            None => Err(IpCamerasError::spotlight(
+                "Dahua",
+                format!("no {} in the config", keys::alarm_out_mode(0)),
+            )),
+        }
+    }
+
+    async fn get_rtsp_port(&self) -> Result<u32, IpCamerasError> {
+        let config = parse_dahua_config(&self.get_config(keys::RTSP).await?);
+
+        Self::config_number(&config, keys::RTSP_PORT).ok_or_else(|| ErrorKind::InvalidData.into())
+    }
+
+    async fn set_rtsp_port(&self, port: u32) -> Result<(), IpCamerasError> {
+        validate_rtsp_port(port)?;
+
+        self.set_config_raw(&[(keys::RTSP_PORT, &port.to_string())])
+            .await
+    }
+
+    async fn set_device_name(&self, name: &str) -> Result<(), IpCamerasError> {
+        validate_machine_name(name)?;
+
+        self.set_config_raw(&[(keys::MACHINE_NAME, name)]).await
+    }
+
+    async fn verify_credentials(&self) -> Result<bool, IpCamerasError> {
+        self.check_credentials(format!(
+            "http://{}/cgi-bin/magicBox.cgi?action=getDeviceType",
+            self.host()
+        ))
+        .await
+    }
+
+    async fn get_snapshot(&self) -> Result<Vec<u8>, IpCamerasError> {
+        self.request_bytes(
+            format!(
+                "http://{}/cgi-bin/snapshot.cgi?channel={}",
+                self.host(),
+                self.channel
+            ),
+            None,
+            Some(LONG_REQUEST_TIMEOUT),
+        )
+        .await
+    }
+
+    async fn set_white_balance(
+        &self,
+        mode: WhiteBalanceMode,
+        red: Option<i32>,
+        blue: Option<i32>,
+    ) -> Result<(), IpCamerasError> {
+        if mode != WhiteBalanceMode::Manual && (red.is_some() || blue.is_some()) {
+            return Err(IpCamerasError::InvalidParam(
+                "white balance gains are only used in manual mode".to_string(),
+            ));
+        }
+
+        let channel = self.channel_index();
+        let mut pairs = vec![];
+        for profile in 0..keys::VIDEO_IN_PROFILES {
+            let key = |field| keys::video_in(keys::VIDEO_IN_WHITE_BALANCE, channel, profile, field);
+
+            pairs.push((key("Mode"), white_balance_mode(mode).to_string()));
+            for (field, gain) in [("GainRed", red), ("GainBlue", blue)] {
+                if let Some(gain) = gain {
+                    validate_image_level(field, gain)?;
+                    pairs.push((key(field), gain.to_string()));
+                }
+            }
+        }
+
+        self.set_config_owned(&pairs).await
+    }
+
+    async fn set_day_night_mode(&self, mode: DayNightMode) -> Result<(), IpCamerasError> {
+        let channel = self.channel_index();
+        let pairs: Vec<(String, String)> = (0..keys::VIDEO_IN_PROFILES)
+            .map(|profile| {
+                (
+                    keys::video_in(keys::VIDEO_IN_DAY_NIGHT, channel, profile, "Mode"),
+                    day_night_mode(mode).to_string(),
+                )
+            })
+            .collect();
+
+        self.set_config_owned(&pairs).await
+    }
+
+    async fn set_image_adjustment(
+        &self,
+        adjustment: ImageAdjustment,
+    ) -> Result<(), IpCamerasError> {
+        let levels = [
+            ("Brightness", adjustment.brightness),
+            ("Contrast", adjustment.contrast),
+            ("Saturation", adjustment.saturation),
+            ("Hue", adjustment.hue),
+        ];
+
+        let mut pairs = vec![];
+        for (field, level) in levels {
+            let level = unwrap_some!(level, continue);
+            validate_image_level(field, level)?;
+
+            for profile in 0..keys::VIDEO_IN_PROFILES {
+                pairs.push((
+                    keys::video_in(keys::VIDEO_COLOR, self.channel_index(), profile, field),
+                    level.to_string(),
+                ));
+            }
         }
+
+        if pairs.is_empty() {
+            return Ok(());
+        }
+
+        self.set_config_owned(&pairs).await
     }
 
-    async fn set_date_time(&self, date_time: chrono::NaiveDateTime) -> Result<(), IpCamerasError> {
+    async fn get_system_status(&self) -> Result<SystemStatus, IpCamerasError> {
+        // up=123456 (seconds)
+        let uptime = self
+            .get("magicBox", &[("action", "getDeviceUpTime")])
+            .await?;
+        // result=2011-7-3 21:02:32
+        let time = self.get("global", &[("action", "getCurrentTime")]).await?;
+
+        Ok(SystemStatus {
+            uptime: Self::parse_value(&uptime).and_then(|v| v.parse().ok()),
+            current_time: Self::parse_value(&time).map(str::to_string),
+            ..Default::default()
+        })
+    }
+
+    // Dahua expects the wall-clock time of the zone configured on the device
+    async fn set_date_time(
+        &self,
+        date_time: chrono::DateTime<chrono::FixedOffset>,
+    ) -> Result<(), IpCamerasError> {
+        let device_offset = self.device_time_zone().await?;
+        let date_time = date_time.with_timezone(&device_offset).naive_local();
+
         self.get(
             "global",
             &[
@@ -122,63 +339,198 @@ impl ApiHandler for DahuaHttp {
         Ok(())
     }
 
+    async fn get_time_zone(&self) -> Result<String, IpCamerasError> {
+        Ok(posix_time_zone(&self.device_time_zone().await?))
+    }
+
+    // Dahua only knows a fixed table of offsets, DST rules are dropped
+    async fn set_time_zone(&self, tz: String) -> Result<(), IpCamerasError> {
+        let offset = validate_time_zone(&tz)?;
+        let index = time_zone_index(&offset).ok_or_else(|| {
+            IpCamerasError::InvalidParam(format!("Dahua has no time zone with offset {offset}"))
+        })?;
+
+        self.set_config_raw(&[(keys::NTP_TIME_ZONE, &index.to_string())])
+            .await
+    }
+
+    async fn is_ptz(&self) -> Result<bool, IpCamerasError> {
+        Ok(self
+            .get("ptz", &[("action", "getCurrentProtocolCaps")])
+            .await?
+            .contains("caps."))
+    }
+
     async fn get_focus_capabilities(&self) -> Result<FocusCapabilities, IpCamerasError> {
         Ok(FocusCapabilities::new().absolute(0.0, 1.0, 0.001))
     }
 
     async fn get_focus_absolute(&self) -> Result<FocusValue, IpCamerasError> {
         use std::str::FromStr;
-        use tokio::time::{sleep, Duration};
-
-        for _ in 0..RETRIES {
-            let output = self
-                .get("devVideoInput", &[("action", "getFocusStatus")])
-                .await?;
-            let mut focus = None;
-            let mut status = None;
-
-            for line in output.lines() {
-                let pv: Vec<&str> = line.split('=').collect();
-                if pv.len() == 2 {
-                    if pv[0] == "status.Focus" {
-                        if let Ok(value) = FocusValue::from_str(pv[1]) {
-                            focus = Some(value);
-                        } else {
-                            return Err(ErrorKind::InvalidData.into());
+
+        let (focus, _) = poll_until(
+            || async move {
+                let output = self
+                    .get("devVideoInput", &[("action", "getFocusStatus")])
+                    .await?;
+                let mut focus = None;
+                let mut normal = false;
+
+                for line in output.lines() {
+                    let pv: Vec<&str> = line.split('=').collect();
+                    if pv.len() == 2 {
+                        if pv[0] == "status.Focus" {
+                            if let Ok(value) = FocusValue::from_str(pv[1]) {
+                                focus = Some(value);
+                            } else {
+                                return Err(IpCamerasError::from(ErrorKind::InvalidData));
+                            }
+                        } else if pv[0] == "status.Status" && pv[1] == "Normal" {
+                            normal = true;
                         }
-                    } else if pv[0] == "status.Status" && pv[1] == "Normal" {
-                        status = Some(());
                     }
                 }
-            }
 
-            if let (Some(focus), Some(())) = (focus, status) {
-                return Ok(focus);
-            }
+                Ok::<_, IpCamerasError>((focus, normal))
+            },
+            |(focus, normal)| focus.is_some() && *normal,
+            self.focus_retries,
+            self.focus_interval,
+        )
+        .await?;
+
+        focus.ok_or_else(|| ErrorKind::InvalidData.into())
+    }
+
+    async fn set_focus_absolute(&self, focus: FocusValue) -> Result<(), IpCamerasError> {
+        self.set_focus_zoom(focus, 0.).await
+    }
+}
+
+impl DahuaHttp {
+    // Timeout in seconds
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
 
-            sleep(Duration::from_millis(INTERVAL)).await;
+    pub fn with_credentials(mut self, username: Option<String>, password: Option<String>) -> Self {
+        self.username = username;
+        self.password = password;
+        self
+    }
+
+    // Video inputs are numbered from 1
+    pub fn with_channel(mut self, channel: u32) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    // Replaces the default reqwest/curl agent
+    pub fn with_user_agent(mut self, agent: impl Into<String>) -> Self {
+        self.headers.retain(|h| !matches!(h, Header::UserAgent(_)));
+        self.headers.push(Header::UserAgent(agent.into()));
+        self
+    }
+
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers.extend(
+            headers
+                .into_iter()
+                .map(|(name, value)| Header::Custom(name, value)),
+        );
+        self
+    }
+
+    // For firmwares that break compressed answers
+    pub fn without_compression(mut self) -> Self {
+        if !self.headers.contains(&Header::NoCompression) {
+            self.headers.push(Header::NoCompression);
         }
+        self
+    }
 
-        warn!("unable to get Normal focus status after {} tries", RETRIES);
-        Err(ErrorKind::InvalidData.into())
+    pub fn with_focus_polling(mut self, retries: usize, interval: Duration) -> Self {
+        self.focus_retries = retries;
+        self.focus_interval = interval;
+        self
     }
 
-    async fn set_focus_absolute(&self, focus: FocusValue) -> Result<(), IpCamerasError> {
+    // Live focus position for tuning UIs, sampled every `interval`
+    pub fn focus_position_stream(
+        &self,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<FocusValue, IpCamerasError>> + '_ {
+        poll_stream(move || self.get_focus_absolute(), interval)
+    }
+
+    // `adjustFocus` always moves both lenses, values are clamped into `[0, 1]`
+    pub async fn set_focus_zoom(
+        &self,
+        focus: FocusValue,
+        zoom: FocusValue,
+    ) -> Result<(), IpCamerasError> {
+        if focus.is_nan() || zoom.is_nan() {
+            return Err(IpCamerasError::InvalidParam(
+                "focus and zoom must be numbers".to_string(),
+            ));
+        }
+
         self.get(
             "devVideoInput",
             &[
                 ("action", "adjustFocus"),
-                ("focus", &focus.to_string()),
-                ("zoom", "0.0"),
+                ("focus", &focus.clamp(0., 1.).to_string()),
+                ("zoom", &zoom.clamp(0., 1.).to_string()),
             ],
         )
         .await?;
 
         Ok(())
     }
-}
 
-impl DahuaHttp {
+    pub async fn set_fps_on_stream(
+        &self,
+        stream: StreamKind,
+        fps: FpsValue,
+    ) -> Result<(), IpCamerasError> {
+        let fps = Some(fps as f64);
+        let config = match stream {
+            StreamKind::Main => Config {
+                channel: self.channel_index(),
+                fps,
+                ..Default::default()
+            },
+            StreamKind::Extra => Config {
+                channel: self.channel_index(),
+                extra_fps: fps,
+                ..Default::default()
+            },
+        };
+
+        self.set_config(config).await
+    }
+
+    async fn device_time_zone(&self) -> Result<chrono::FixedOffset, IpCamerasError> {
+        let config = parse_dahua_config(&self.get_config(keys::NTP).await?);
+        let index =
+            Self::config_number(&config, keys::NTP_TIME_ZONE).ok_or(ErrorKind::InvalidData)?;
+
+        Ok(time_zone_offset(index as usize).ok_or(ErrorKind::InvalidData)?)
+    }
+
+    pub async fn get_fps_on_stream(&self, stream: StreamKind) -> Result<FpsValue, IpCamerasError> {
+        let config = parse_dahua_config(&self.get_config(keys::ENCODE).await?);
+
+        Self::config_number(&config, &stream.fps_key(self.channel_index()))
+            .map(FpsValue::from)
+            .ok_or(IpCamerasError::Fps)
+    }
+
+    fn channel_index(&self) -> usize {
+        self.channel.saturating_sub(1) as usize
+    }
+
     async fn get<S: AsRef<str>>(
         &self,
         cgi: S,
@@ -220,13 +572,26 @@ impl DahuaHttp {
     }
 
     async fn set_config(&self, config: Config) -> Result<(), IpCamerasError> {
+        self.set_config_owned(&Self::config_pairs(&config)?).await
+    }
+
+    async fn set_config_owned(&self, pairs: &[(String, String)]) -> Result<(), IpCamerasError> {
+        let pairs: Vec<(&str, &str)> = pairs
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+
+        self.set_config_raw(&pairs).await
+    }
+
+    pub async fn set_config_raw(&self, pairs: &[(&str, &str)]) -> Result<(), IpCamerasError> {
         // http://<ip>/cgi-bin/configManager.cgi?action=setConfig&<paramName>=<paramValue>[&<paramName>=<paramValue>...]
         if self
             .request(
                 format!(
                     "http://{}/cgi-bin/configManager.cgi?action=setConfig&{}",
                     self.host(),
-                    serde_url_params::to_string(&config)?
+                    Self::encode_pairs(pairs)
                 ),
                 None,
                 Method::GET,
@@ -241,23 +606,659 @@ impl DahuaHttp {
         }
     }
 
-    fn parse_output(input: &str) -> Option<u32> {
-        let inner = input.find("Encode[0].MainFormat[0].Video.FPS");
-        if let Some(i) = inner {
-            let output = match input.get(i..) {
-                Some(s) => s,
-                None => return None,
-            };
-            output
-                .chars()
-                .skip_while(|ch| ch != &'=')
-                .skip_while(|ch| !ch.is_digit(10))
-                .take_while(|ch| ch.is_digit(10))
-                .fold(None, |acc, ch| {
-                    ch.to_digit(10).map(|b| acc.unwrap_or(0) * 10 + b)
+    fn config_pairs(config: &Config) -> Result<Vec<(String, String)>, IpCamerasError> {
+        use serde_json::Value;
+
+        let map = match serde_json::to_value(config)? {
+            Value::Object(map) => map,
+            _ => return Err(ErrorKind::InvalidInput.into()),
+        };
+
+        Ok(map
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let value = match value {
+                    Value::Bool(b) => b.to_string(),
+                    Value::String(s) => s,
+                    Value::Number(n) => match n.as_f64() {
+                        Some(f) if n.is_f64() && f.fract() == 0.0 => (f as i64).to_string(),
+                        _ => n.to_string(),
+                    },
+                    _ => return None,
+                };
+                Some((key, value))
+            })
+            .collect())
+    }
+
+    // Dahua array syntax (`Encode[0].MainFormat[0]`) needs the brackets percent-encoded
+    fn encode_pairs(pairs: &[(&str, &str)]) -> String {
+        fn encode(input: &str) -> String {
+            input
+                .bytes()
+                .map(|b| match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                        (b as char).to_string()
+                    }
+                    _ => format!("%{:02X}", b),
                 })
-        } else {
-            None
+                .collect()
+        }
+
+        pairs
+            .iter()
+            .map(|(k, v)| format!("{}={}", encode(k), encode(v)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    // Value of a single `key=value` answer
+    fn parse_value(input: &str) -> Option<&str> {
+        input
+            .lines()
+            .find_map(|line| line.split_once('='))
+            .map(|(_, value)| value.trim())
+    }
+
+    // `set_config_raw` rejects anything but `OK` as invalid input
+    fn spotlight_error(e: IpCamerasError) -> IpCamerasError {
+        match e {
+            IpCamerasError::Std { source } if source.kind() == ErrorKind::InvalidInput => {
+                IpCamerasError::spotlight("Dahua", "alarm output config was not accepted")
+            }
+            e => e,
+        }
+    }
+
+    // Integer part, some firmwares report FPS as `25.000000`
+    fn config_number(config: &HashMap<String, String>, key: &str) -> Option<u32> {
+        config.get(key)?.split('.').next()?.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::mock::MockCamera;
+
+    async fn mock_camera(routes: Vec<(&'static str, u16, String)>) -> (DahuaHttp, MockCamera) {
+        let mock = MockCamera::start(routes).await;
+        let camera = DahuaHttp {
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        };
+
+        (camera, mock)
+    }
+
+    async fn adjust_focus_camera() -> (DahuaHttp, MockCamera) {
+        mock_camera(vec![(
+            "GET /cgi-bin/devVideoInput.cgi?action=adjustFocus",
+            200,
+            "OK".to_string(),
+        )])
+        .await
+    }
+
+    #[tokio::test]
+    async fn focus_and_zoom_are_sent_together() {
+        let (camera, mock) = adjust_focus_camera().await;
+
+        camera.set_focus_zoom(0.5, 0.25).await.unwrap();
+        camera.set_focus_zoom(1.5, -1.).await.unwrap();
+        camera.set_focus_absolute(0.75).await.unwrap();
+
+        assert_eq!(
+            set_config_paths(&mock),
+            [
+                "/cgi-bin/devVideoInput.cgi?action=adjustFocus&focus=0.5&zoom=0.25",
+                "/cgi-bin/devVideoInput.cgi?action=adjustFocus&focus=1&zoom=0",
+                "/cgi-bin/devVideoInput.cgi?action=adjustFocus&focus=0.75&zoom=0",
+            ]
+        );
+    }
+
+    async fn focus_status_camera(status: &str, retries: usize) -> (DahuaHttp, MockCamera) {
+        let (camera, mock) = mock_camera(vec![(
+            "GET /cgi-bin/devVideoInput.cgi?action=getFocusStatus",
+            200,
+            format!("status.Focus=0.4\r\nstatus.Status={status}\r\n"),
+        )])
+        .await;
+
+        (
+            camera.with_focus_polling(retries, Duration::from_millis(1)),
+            mock,
+        )
+    }
+
+    #[tokio::test]
+    async fn moving_focus_is_polled_the_configured_times() {
+        let (camera, mock) = focus_status_camera("Autofocus", 3).await;
+
+        let result = camera.get_focus_absolute().await;
+
+        assert!(matches!(result, Err(IpCamerasError::Timeout)));
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn settled_focus_is_read_at_once() {
+        let (camera, mock) = focus_status_camera("Normal", 3).await;
+
+        assert_eq!(camera.get_focus_absolute().await.unwrap(), 0.4);
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn nan_focus_is_rejected_before_sending() {
+        let (camera, mock) = adjust_focus_camera().await;
+
+        let result = camera.set_focus_zoom(0.5, FocusValue::NAN).await;
+
+        assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rtsp_port_is_read_from_config() {
+        let (camera, _mock) = mock_camera(vec![(
+            "GET /cgi-bin/configManager.cgi?action=getConfig&name=RTSP",
+            200,
+            "table.RTSP.Enable=true\r\ntable.RTSP.Port=8554\r\n".to_string(),
+        )])
+        .await;
+
+        assert_eq!(camera.get_rtsp_port().await.unwrap(), 8554);
+    }
+
+    #[tokio::test]
+    async fn rtsp_port_is_set_by_key() {
+        let (camera, mock) = mock_camera(vec![(
+            "GET /cgi-bin/configManager.cgi?action=setConfig",
+            200,
+            "OK".to_string(),
+        )])
+        .await;
+
+        camera.set_rtsp_port(1554).await.unwrap();
+        assert!(matches!(
+            camera.set_rtsp_port(0).await,
+            Err(IpCamerasError::InvalidParam(_))
+        ));
+
+        let paths: Vec<_> = mock.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(
+            paths,
+            ["/cgi-bin/configManager.cgi?action=setConfig&RTSP.Port=1554"]
+        );
+    }
+
+    #[test]
+    fn array_keys_are_percent_encoded() {
+        assert_eq!(
+            DahuaHttp::encode_pairs(&[
+                ("Encode[0].MainFormat[0].Video.FPS", "25"),
+                ("VideoInDayNight[0][0].Mode", "Color"),
+            ]),
+            "Encode%5B0%5D.MainFormat%5B0%5D.Video.FPS=25&VideoInDayNight%5B0%5D%5B0%5D.Mode=Color"
+        );
+    }
+
+    #[test]
+    fn reserved_value_characters_are_encoded() {
+        assert_eq!(
+            DahuaHttp::encode_pairs(&[("ChannelTitle[0].Name", "Gate 1&2=in")]),
+            "ChannelTitle%5B0%5D.Name=Gate%201%262%3Din"
+        );
+        assert_eq!(DahuaHttp::encode_pairs(&[]), "");
+    }
+
+    #[test]
+    fn typed_config_becomes_raw_pairs() {
+        let config = Config {
+            spotlight: Some(true),
+            brightness: Some(70),
+            fps: Some(25.),
+            ..Default::default()
+        };
+
+        let mut pairs = DahuaHttp::config_pairs(&config).unwrap();
+        pairs.sort();
+
+        let mut expected = vec![
+            (keys::FLASHLIGHT_ENABLE.to_string(), "true".to_string()),
+            (keys::FLASHLIGHT_BRIGHTNESS.to_string(), "70".to_string()),
+            (StreamKind::Main.fps_key(0), "25".to_string()),
+        ];
+        expected.sort();
+        assert_eq!(pairs, expected);
+    }
+
+    async fn set_config_camera() -> (DahuaHttp, MockCamera) {
+        mock_camera(vec![(
+            "GET /cgi-bin/configManager.cgi?action=setConfig",
+            200,
+            "OK".to_string(),
+        )])
+        .await
+    }
+
+    fn set_config_paths(mock: &MockCamera) -> Vec<String> {
+        mock.requests().into_iter().map(|r| r.path).collect()
+    }
+
+    // `fields` of every day/night/normal profile of `name`, percent-encoded
+    fn video_in_query(name: &str, fields: &[(&str, &str)]) -> String {
+        let pairs: Vec<String> = (0..keys::VIDEO_IN_PROFILES)
+            .flat_map(|profile| {
+                fields.iter().map(move |(field, value)| {
+                    format!("{name}%5B0%5D%5B{profile}%5D.{field}={value}")
+                })
+            })
+            .collect();
+
+        format!(
+            "/cgi-bin/configManager.cgi?action=setConfig&{}",
+            pairs.join("&")
+        )
+    }
+
+    #[tokio::test]
+    async fn white_balance_query() {
+        let (camera, mock) = set_config_camera().await;
+
+        camera
+            .set_white_balance(WhiteBalanceMode::Manual, Some(60), Some(40))
+            .await
+            .unwrap();
+        camera
+            .set_white_balance(WhiteBalanceMode::Outdoor, None, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            camera
+                .set_white_balance(WhiteBalanceMode::Auto, Some(60), None)
+                .await,
+            Err(IpCamerasError::InvalidParam(_))
+        ));
+
+        assert_eq!(
+            set_config_paths(&mock),
+            [
+                video_in_query(
+                    "VideoInWhiteBalance",
+                    &[("Mode", "Manual"), ("GainRed", "60"), ("GainBlue", "40")]
+                ),
+                video_in_query("VideoInWhiteBalance", &[("Mode", "Outdoor")]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn day_night_query() {
+        let (camera, mock) = set_config_camera().await;
+
+        camera.set_day_night_mode(DayNightMode::Auto).await.unwrap();
+        camera
+            .set_day_night_mode(DayNightMode::BlackWhite)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            set_config_paths(&mock),
+            [
+                video_in_query("VideoInDayNight", &[("Mode", "Brightness")]),
+                video_in_query("VideoInDayNight", &[("Mode", "BlackWhite")]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn channel_picks_the_config_index() {
+        let (camera, mock) = set_config_camera().await;
+        let camera = camera.with_channel(2);
+
+        camera.set_day_night_mode(DayNightMode::Auto).await.unwrap();
+        camera.set_fps(FpsValue::from(25u32)).await.unwrap();
+
+        let paths = set_config_paths(&mock);
+        assert_eq!(paths.len(), 2);
+        assert!(
+            paths[0].contains("VideoInDayNight%5B1%5D%5B0%5D.Mode=Brightness"),
+            "{}",
+            paths[0]
+        );
+        assert!(
+            paths[1].contains("Encode%5B1%5D.MainFormat%5B0%5D.Video.FPS="),
+            "{}",
+            paths[1]
+        );
+    }
+
+    #[tokio::test]
+    async fn image_adjustment_query() {
+        let (camera, mock) = set_config_camera().await;
+
+        camera
+            .set_image_adjustment(ImageAdjustment {
+                brightness: Some(55),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        // Nothing to change, nothing is sent
+        camera
+            .set_image_adjustment(ImageAdjustment::default())
+            .await
+            .unwrap();
+        assert!(matches!(
+            camera
+                .set_image_adjustment(ImageAdjustment {
+                    hue: Some(101),
+                    ..Default::default()
+                })
+                .await,
+            Err(IpCamerasError::InvalidParam(_))
+        ));
+
+        assert_eq!(
+            set_config_paths(&mock),
+            [video_in_query("VideoColor", &[("Brightness", "55")])]
+        );
+    }
+
+    #[tokio::test]
+    async fn system_status_from_uptime_and_time() {
+        let (camera, _mock) = mock_camera(vec![
+            (
+                "GET /cgi-bin/magicBox.cgi?action=getDeviceUpTime",
+                200,
+                "up=123456\r\n".to_string(),
+            ),
+            (
+                "GET /cgi-bin/global.cgi?action=getCurrentTime",
+                200,
+                "result=2011-7-3 21:02:32\r\n".to_string(),
+            ),
+        ])
+        .await;
+
+        assert_eq!(
+            camera.get_system_status().await.unwrap(),
+            SystemStatus {
+                uptime: Some(123456),
+                current_time: Some("2011-7-3 21:02:32".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn machine_name_is_set_by_key() {
+        let (camera, mock) = mock_camera(vec![(
+            "GET /cgi-bin/configManager.cgi?action=setConfig",
+            200,
+            "OK".to_string(),
+        )])
+        .await;
+
+        camera.set_device_name("gate_1").await.unwrap();
+        assert!(matches!(
+            camera.set_device_name("gate 1").await,
+            Err(IpCamerasError::InvalidParam(_))
+        ));
+
+        let paths: Vec<_> = mock.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(
+            paths,
+            ["/cgi-bin/configManager.cgi?action=setConfig&General.MachineName=gate_1"]
+        );
+    }
+
+    // What `setConfig` writes comes back as `table.<key>=<value>` from `getConfig`
+    fn echo(pairs: Vec<(String, String)>) -> HashMap<String, String> {
+        parse_dahua_config(
+            &pairs
+                .into_iter()
+                .map(|(key, value)| format!("{}{key}={value}\r\n", keys::TABLE_PREFIX))
+                .collect::<String>(),
+        )
+    }
+
+    #[test]
+    fn spotlight_keys_match_between_set_and_get() {
+        let config = Config {
+            external_spotlight: Some(true.into()),
+            ..Default::default()
+        };
+        let written = echo(DahuaHttp::config_pairs(&config).unwrap());
+
+        assert_eq!(
+            DahuaHttp::config_number(&written, &keys::alarm_out_mode(0)),
+            Some(AlarmMode::ForceOn as u32)
+        );
+        assert_eq!(
+            written.get(&keys::alarm_out_name(0)).map(String::as_str),
+            Some("Noname")
+        );
+    }
+
+    #[test]
+    fn spotlight_modes_set_alarm_out_mode() {
+        for (mode, expected) in [
+            (SpotlightMode::Off, 2),
+            (SpotlightMode::AlwaysOn, 1),
+            (SpotlightMode::Strobe, 0),
+        ] {
+            let alarm_mode = AlarmMode::from_spotlight_mode(&mode).unwrap();
+            let config = Config {
+                external_spotlight: Some(ExternalSpotlight::with_mode(alarm_mode)),
+                ..Default::default()
+            };
+            let written = echo(DahuaHttp::config_pairs(&config).unwrap());
+
+            assert_eq!(
+                DahuaHttp::config_number(&written, &keys::alarm_out_mode(0)),
+                Some(expected),
+                "{mode:?}"
+            );
         }
     }
+
+    #[tokio::test]
+    async fn trigger_wait_spotlight_modes_are_not_available() {
+        let (camera, mock) = set_config_camera().await;
+
+        for mode in [
+            SpotlightMode::ExposureActive,
+            SpotlightMode::FrameTriggerWait,
+            SpotlightMode::AcquisitionTriggerWait,
+        ] {
+            let result = camera.set_spotlight_mode(mode).await;
+            assert!(matches!(result, Err(IpCamerasError::NotAvialiableApi)));
+        }
+        assert!(mock.requests().is_empty());
+    }
+
+    #[test]
+    fn fps_keys_match_between_set_and_get() {
+        let config = Config {
+            fps: Some(25.),
+            extra_fps: Some(12.),
+            ..Default::default()
+        };
+        let written = echo(DahuaHttp::config_pairs(&config).unwrap());
+
+        assert_eq!(
+            DahuaHttp::config_number(&written, &StreamKind::Main.fps_key(0)),
+            Some(25)
+        );
+        assert_eq!(
+            DahuaHttp::config_number(&written, &StreamKind::Extra.fps_key(0)),
+            Some(12)
+        );
+    }
+
+    #[test]
+    fn stream_fps_keys() {
+        assert_eq!(
+            StreamKind::Main.fps_key(0),
+            "Encode[0].MainFormat[0].Video.FPS"
+        );
+        assert_eq!(
+            StreamKind::Extra.fps_key(0),
+            "Encode[0].ExtraFormat[0].Video.FPS"
+        );
+    }
+
+    #[test]
+    fn extra_fps_is_read_from_its_own_key() {
+        let config = parse_dahua_config(
+            "table.Encode[0].MainFormat[0].Video.FPS=25.000000\r\n\
+             table.Encode[0].ExtraFormat[0].Video.FPS=15.000000\r\n",
+        );
+
+        assert_eq!(
+            DahuaHttp::config_number(&config, &StreamKind::Main.fps_key(0)),
+            Some(25)
+        );
+        assert_eq!(
+            DahuaHttp::config_number(&config, &StreamKind::Extra.fps_key(0)),
+            Some(15)
+        );
+    }
+
+    #[test]
+    fn extra_fps_is_written_to_its_own_key() {
+        let config = Config {
+            extra_fps: Some(15.),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            DahuaHttp::config_pairs(&config).unwrap(),
+            vec![(StreamKind::Extra.fps_key(0), "15".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn fps_is_read_from_a_multi_key_body() {
+        let (camera, _mock) = mock_camera(vec![(
+            "GET /cgi-bin/configManager.cgi?action=getConfig&name=Encode",
+            200,
+            "table.Encode[0].MainFormat[0].Video.BitRate=4096\r\n\
+             table.Encode[0].MainFormat[0].Video.FPS=25.000000\r\n\
+             table.Encode[0].ExtraFormat[0].Video.FPS=12.000000\r\n"
+                .to_string(),
+        )])
+        .await;
+
+        assert_eq!(camera.get_fps().await.unwrap(), 25);
+        assert_eq!(
+            camera.get_fps_on_stream(StreamKind::Extra).await.unwrap(),
+            12
+        );
+    }
+
+    #[tokio::test]
+    async fn spotlight_state_is_read_from_the_first_alarm_output() {
+        let state = |mode: &str| {
+            let body = format!(
+                "table.AlarmOut[0].Mode={mode}\r\ntable.AlarmOut[0].Name=Light\r\n\
+                 table.AlarmOut[1].Mode=1\r\n"
+            );
+            async move {
+                let (camera, _mock) = mock_camera(vec![(
+                    "GET /cgi-bin/configManager.cgi?action=getConfig&name=AlarmOut",
+                    200,
+                    body,
+                )])
+                .await;
+                camera.get_spotlight_state().await
+            }
+        };
+
+        assert!(state("1").await.unwrap());
+        assert!(!state("2").await.unwrap());
+        assert!(!state("0").await.unwrap());
+        assert!(state("7").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejected_spotlight_config_names_the_vendor() {
+        let (camera, _mock) = mock_camera(vec![(
+            "GET /cgi-bin/configManager.cgi?action=setConfig",
+            200,
+            "Error\r\nBad Request!".to_string(),
+        )])
+        .await;
+
+        match camera.switch_spotlight(true).await {
+            Err(IpCamerasError::Spotlight { vendor, detail }) => {
+                assert_eq!(vendor, "Dahua");
+                assert_eq!(detail, "alarm output config was not accepted");
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn unexpected_alarm_mode_is_described() {
+        let (camera, _mock) = mock_camera(vec![(
+            "GET /cgi-bin/configManager.cgi?action=getConfig&name=AlarmOut",
+            200,
+            "table.AlarmOut[0].Mode=7\r\n".to_string(),
+        )])
+        .await;
+
+        let error = camera.get_spotlight_state().await.unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Dahua spotlight error: unexpected alarm output mode 7"
+        );
+    }
+
+    #[tokio::test]
+    async fn date_time_is_sent_in_the_device_zone() {
+        let (camera, mock) = mock_camera(vec![
+            // UTC+05:45
+            (
+                "GET /cgi-bin/configManager.cgi?action=getConfig&name=NTP",
+                200,
+                "table.NTP.Enable=false\r\ntable.NTP.TimeZone=9\r\n".to_string(),
+            ),
+            (
+                "GET /cgi-bin/global.cgi?action=setCurrentTime",
+                200,
+                "OK".to_string(),
+            ),
+        ])
+        .await;
+        let date_time = chrono::DateTime::parse_from_rfc3339("2024-03-01T13:00:00+03:00").unwrap();
+
+        camera.set_date_time(date_time).await.unwrap();
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1].path,
+            "/cgi-bin/global.cgi?action=setCurrentTime&time=2024-03-01%2015:45:00"
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_device_zone_sends_no_time() {
+        let (camera, mock) = mock_camera(vec![(
+            "GET /cgi-bin/configManager.cgi?action=getConfig&name=NTP",
+            200,
+            "table.NTP.Enable=false\r\n".to_string(),
+        )])
+        .await;
+        let date_time = chrono::DateTime::parse_from_rfc3339("2024-03-01T13:00:00+03:00").unwrap();
+
+        assert!(camera.set_date_time(date_time).await.is_err());
+        assert_eq!(mock.requests().len(), 1);
+    }
 }
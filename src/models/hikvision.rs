@@ -1,13 +1,14 @@
 use async_trait::*;
 use serde::{de::DeserializeOwned, Serialize};
-use serde_xml_rs::{from_str, to_string};
+use serde_xml_rs::from_str;
 
 use std::{
     io::ErrorKind,
     sync::{
-        atomic::{AtomicBool, Ordering::Relaxed},
+        atomic::{AtomicBool, AtomicI32, Ordering::Relaxed},
         Arc, Mutex,
     },
+    time::Duration,
 };
 
 use common::CameraRole;
@@ -17,8 +18,18 @@ use onvif::FpsValue;
 use pulsar_core::prelude::*;
 
 use crate::{
-    utils::{focus::*, handler::*, request::Method, serde::hik::*},
-    AdditionalConfiguration, HikvisionConfiguration, IpCamerasError, DEFAULT_TIMEOUT,
+    utils::{
+        focus::*,
+        handler::*,
+        poll::poll_until,
+        request::{Header, Method, LONG_REQUEST_TIMEOUT},
+        serde::hik::*,
+        status::{CachedState, SystemStatus},
+        time_zone::validate_time_zone,
+        transport::*,
+    },
+    AdditionalConfiguration, HikvisionConfiguration, ImageProfile, ImageSettings, IpCamerasError,
+    DEFAULT_TIMEOUT,
 };
 
 #[derive(Debug, Clone)]
@@ -36,6 +47,14 @@ impl Default for Focus {
     }
 }
 
+pub const DEFAULT_CHANNEL: u32 = 1;
+pub const DEFAULT_EXTERNAL_LINE: u8 = 5;
+pub const DEFAULT_INTERNAL_LINE: u8 = 7;
+
+// Lens position reads until it settles
+const FOCUS_POLL_RETRIES: usize = 5;
+const FOCUS_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
 #[derive(Debug, Clone)]
 pub struct Projectors {
     pub projectors_lines: Vec<u8>,
@@ -44,7 +63,7 @@ pub struct Projectors {
 impl Default for Projectors {
     fn default() -> Self {
         Self {
-            projectors_lines: vec![5, 7],
+            projectors_lines: vec![DEFAULT_EXTERNAL_LINE, DEFAULT_INTERNAL_LINE],
         }
     }
 }
@@ -66,20 +85,54 @@ type ProjectorsSettings = Arc<Mutex<Projectors>>;
 type FocusSettings = Arc<Mutex<Focus>>;
 type CameraSettings = Arc<Mutex<CameraS>>;
 
-#[derive(Debug)]
 pub struct HikvisionHttp {
     pub id: CameraId,
     pub host: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
     pub timeout: u64,
+    pub onvif_fallback: bool,
+    // Sent with every request, e.g. the User-Agent some firmwares check
+    pub headers: Vec<Header>,
     pub camera_role: CameraRole,
+    // Video input the `channels/<id>` endpoints target, streams are `<id>01`
+    pub channel: u32,
+    // `SyncSignalOutput` ids the projectors are wired to, model dependent
+    pub external_line: u8,
+    pub internal_line: u8,
+    // Root `xmlns` of PUT bodies, `None` follows the firmware version
+    pub xml_namespace: Option<String>,
 
     pub focus: FocusSettings,
     pub projectors: ProjectorsSettings,
     pub camera_version: CameraSettings,
 
     pub is_ptz: AtomicBool,
+    pub initialized: AtomicBool,
+}
+
+impl std::fmt::Debug for HikvisionHttp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HikvisionHttp")
+            .field("id", &self.id)
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("password", &redact(&self.password))
+            .field("timeout", &self.timeout)
+            .field("onvif_fallback", &self.onvif_fallback)
+            .field("headers", &redact_headers(&self.headers))
+            .field("camera_role", &self.camera_role)
+            .field("channel", &self.channel)
+            .field("external_line", &self.external_line)
+            .field("internal_line", &self.internal_line)
+            .field("xml_namespace", &self.xml_namespace)
+            .field("focus", &self.focus)
+            .field("projectors", &self.projectors)
+            .field("camera_version", &self.camera_version)
+            .field("is_ptz", &self.is_ptz)
+            .field("initialized", &self.initialized)
+            .finish()
+    }
 }
 
 impl Default for HikvisionHttp {
@@ -90,13 +143,20 @@ impl Default for HikvisionHttp {
             username: None,
             password: None,
             timeout: DEFAULT_TIMEOUT,
+            onvif_fallback: false,
+            headers: Vec::new(),
             camera_role: CameraRole::View,
+            channel: DEFAULT_CHANNEL,
+            external_line: DEFAULT_EXTERNAL_LINE,
+            internal_line: DEFAULT_INTERNAL_LINE,
+            xml_namespace: None,
 
             focus: Arc::new(Mutex::new(Default::default())),
             projectors: Arc::new(Mutex::new(Default::default())),
             camera_version: Arc::new(Mutex::new(Default::default())),
 
             is_ptz: AtomicBool::new(false),
+            initialized: AtomicBool::new(false),
         }
     }
 }
@@ -138,6 +198,45 @@ impl ApiHandler for HikvisionHttp {
         }
     }
 
+    fn timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    fn extra_headers(&self) -> &[Header] {
+        &self.headers
+    }
+
+    fn onvif_fallback(&self) -> bool {
+        self.onvif_fallback
+    }
+
+    fn can_write(&self) -> bool {
+        !matches!(self.camera_role, CameraRole::View)
+    }
+
+    // A poisoned lock leaves its fields empty rather than failing the snapshot
+    fn cached_state(&self) -> CachedState {
+        let focus = self.focus.lock().ok();
+
+        CachedState {
+            initialized: Some(self.initialized.load(Relaxed)),
+            is_ptz: Some(self.is_ptz.load(Relaxed)),
+            firmware_version: self
+                .camera_version
+                .lock()
+                .ok()
+                .map(|cv| cv.firmware_verison.clone().into()),
+            projector_lines: self
+                .projectors
+                .lock()
+                .ok()
+                .map(|p| p.projectors_lines.clone()),
+            focus_interval: focus.as_ref().map(|f| f.current_interval),
+            focus_forward: focus.as_ref().map(|f| f.current_direction),
+            ..Default::default()
+        }
+    }
+
     // EXTERNAL API
     async fn init(&self) -> Result<(), IpCamerasError> {
         let firmware_verison = self
@@ -158,14 +257,52 @@ impl ApiHandler for HikvisionHttp {
             trace!("Hikvsion got projectors");
         }
 
+        self.initialized.store(true, Relaxed);
+        Ok(())
+    }
+
+    async fn ensure_initialized(&self) -> Result<(), IpCamerasError> {
+        if !self.initialized.load(Relaxed) {
+            trace!("Hikvision {} is not initialized, run init", self.host());
+            self.init().await?;
+        }
+
         Ok(())
     }
 
+    async fn is_ptz(&self) -> Result<bool, IpCamerasError> {
+        self.ensure_initialized().await?;
+        Ok(self.is_ptz.load(Relaxed))
+    }
+
+    async fn get_proportional_pan(&self) -> Result<bool, IpCamerasError> {
+        let ic = self.retrieve_ptz_image_channel().await?;
+        let pp = unwrap_some!(
+            ic.proportionalpan,
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+
+        Ok(pp.enabled)
+    }
+
+    async fn set_proportional_pan(&self, enabled: bool) -> Result<(), IpCamerasError> {
+        let mut ic = self.retrieve_ptz_image_channel().await?;
+        match ic.proportionalpan.as_mut() {
+            Some(pp) if pp.enabled == enabled => return Ok(()),
+            Some(pp) => pp.enabled = enabled,
+            None => ic.proportionalpan = Some(Proportionalpan { enabled }),
+        }
+
+        self.send_image_channel_proportionalpan(&ic).await
+    }
+
     async fn get_spotlight_state(&self) -> Result<bool, IpCamerasError> {
+        self.ensure_initialized().await?;
         Ok(self.retrieve_spotlight_settings().await?.into())
     }
 
     async fn switch_spotlight(&self, enabled: bool) -> Result<(), IpCamerasError> {
+        self.ensure_initialized().await?;
         let some_lines = self.projectors.lock()?.projectors_lines.clone();
 
         let sync_signal_output_list = if enabled {
@@ -181,20 +318,97 @@ impl ApiHandler for HikvisionHttp {
             .await?)
     }
 
+    async fn set_supplement_light(
+        &self,
+        mode: SupplementMode,
+        brightness: Option<i32>,
+    ) -> Result<(), IpCamerasError> {
+        match (mode, brightness) {
+            (SupplementMode::Manual, None) => {
+                return Err(IpCamerasError::InvalidParam(
+                    "supplement light brightness is required in manual mode".to_string(),
+                ))
+            }
+            (SupplementMode::Auto, Some(_)) => {
+                return Err(IpCamerasError::InvalidParam(
+                    "supplement light brightness is only used in manual mode".to_string(),
+                ))
+            }
+            (_, Some(level))
+                if !(SUPPLEMENT_LIGHT_BRIGHTNESS_MIN..=SUPPLEMENT_LIGHT_BRIGHTNESS_MAX)
+                    .contains(&level) =>
+            {
+                return Err(IpCamerasError::InvalidParam(format!(
+                    "supplement light brightness must be in \
+                     {SUPPLEMENT_LIGHT_BRIGHTNESS_MIN}..={SUPPLEMENT_LIGHT_BRIGHTNESS_MAX}, got {level}"
+                )))
+            }
+            _ => {}
+        }
+
+        let mut sl = self.retrieve_supplement_light().await?;
+        sl.mixed_light_brightness_regulat_mode = Some(mode);
+
+        if brightness.is_some() {
+            // Only touch the lights the camera actually has
+            if sl.white_light_brightness.is_none() && sl.ir_light_brightness.is_none() {
+                return Err(IpCamerasError::NotAvialiableApi);
+            }
+            if sl.white_light_brightness.is_some() {
+                sl.white_light_brightness = brightness;
+            }
+            if sl.ir_light_brightness.is_some() {
+                sl.ir_light_brightness = brightness;
+            }
+        }
+
+        self.send_supplement_light(sl).await
+    }
+
     async fn get_fps(&self) -> Result<FpsValue, IpCamerasError> {
         let video_settings = self.retrieve_video_settings().await?.video;
-        let fps = video_settings.max_frame_rate / 100;
-        Ok(fps)
+        Ok(fps_from_raw(video_settings.max_frame_rate))
     }
 
     async fn set_fps(&self, fps: FpsValue) -> Result<(), IpCamerasError> {
         let mut sc = self.retrieve_video_settings().await?;
-        sc.video.max_frame_rate = fps * 100;
+
+        // Keep fractional rates (e.g. 2997) untouched if they already round to the requested fps
+        if fps_from_raw(sc.video.max_frame_rate) == fps {
+            return Ok(());
+        }
+        sc.video.max_frame_rate = fps_to_raw(fps);
 
         self.send_video_settings(sc).await?;
         Ok(())
     }
 
+    async fn get_supported_fps(&self) -> Result<Vec<FpsValue>, IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+
+        // <maxFrameRate opt="2500,2200,2000,...">2500</maxFrameRate>
+        let options = self
+            .retrieve_options(
+                format!("http://{host}/ISAPI/Streaming/channels/{channel}01/capabilities"),
+                "maxFrameRate",
+            )
+            .await?
+            .ok_or(IpCamerasError::Fps)?;
+
+        let mut fps: Vec<FpsValue> = options
+            .iter()
+            .filter_map(|v| v.parse::<FpsValue>().ok())
+            .map(fps_from_raw)
+            .filter(|fps| *fps > 0)
+            .collect();
+
+        fps.sort_unstable();
+        fps.dedup();
+
+        Ok(fps)
+    }
+
     async fn set_focus_continuous(&self, fc: FocusContinuous) -> Result<(), IpCamerasError> {
         let (interval, direction) = (fc.interval, fc.direction);
 
@@ -225,7 +439,44 @@ impl ApiHandler for HikvisionHttp {
     }
 
     async fn get_focus_capabilities(&self) -> Result<FocusCapabilities, IpCamerasError> {
-        Ok(FocusCapabilities::new().continuous(1, 1))
+        Ok(FocusCapabilities::new()
+            .continuous(1, 1)
+            .absolute(0.0, 1.0, 0.001))
+    }
+
+    // A moving lens answers busy or a changing position, so the position is read
+    // until two reads in a row agree
+    async fn get_focus_absolute(&self) -> Result<FocusValue, IpCamerasError> {
+        let last = AtomicI32::new(i32::MIN);
+
+        let (position, _) = poll_until(
+            || async {
+                match self.retrieve_focus_position().await {
+                    Ok(fd) => {
+                        let settled = last.swap(fd.focus, Relaxed) == fd.focus;
+                        Ok((Some(fd), settled))
+                    }
+                    Err(IpCamerasError::Camera(ErrorCode::DeviceBusy)) => {
+                        trace!("Hikvision {} lens is busy", self.host());
+                        Ok((None, false))
+                    }
+                    Err(e) => Err(e),
+                }
+            },
+            |(_, settled)| *settled,
+            FOCUS_POLL_RETRIES,
+            FOCUS_POLL_INTERVAL,
+        )
+        .await?;
+
+        position
+            .map(|fd| fd.to_normalized())
+            .ok_or_else(|| ErrorKind::InvalidData.into())
+    }
+
+    async fn set_focus_absolute(&self, focus: FocusValue) -> Result<(), IpCamerasError> {
+        self.send_focus_position(FocusData::from_normalized(focus))
+            .await
     }
 
     async fn get_focus_continuous(&self) -> Result<FocusContinuous, IpCamerasError> {
@@ -241,720 +492,3984 @@ impl ApiHandler for HikvisionHttp {
         })
     }
 
-    async fn get_additional_configuration(
-        &self,
-    ) -> Result<AdditionalConfiguration, IpCamerasError> {
-        let hikvision = Some(self.prepare_hikvision_configuration().await?);
-        let id = self.id;
+    async fn apply_profile(&self, profile: ImageProfile) -> Result<(), IpCamerasError> {
+        let (role, tune) = Self::profile_tuning(profile);
 
-        Ok(AdditionalConfiguration {
-            id,
-            hikvision,
-            default_settings: Some(false),
+        self.send_role_settings(&role, tune).await
+    }
+
+    async fn set_device_name(&self, name: &str) -> Result<(), IpCamerasError> {
+        validate_device_info("device name", name)?;
 
+        self.send_device_info(DeviceInfoUpdate {
+            device_name: Some(name.to_string()),
             ..Default::default()
         })
+        .await
     }
 
-    async fn get_default_configuration(&self) -> Result<AdditionalConfiguration, IpCamerasError> {
-        let (ic, sc) = self.retrieve_common_default_settings().await?;
-
-        Ok(AdditionalConfiguration {
-            id: self.id,
-            default_settings: Some(true),
-            hikvision: Some(HikvisionConfiguration {
-                image_channel: Some(ic),
-                streaming_channel: Some(sc),
+    async fn set_device_location(&self, location: &str) -> Result<(), IpCamerasError> {
+        validate_device_info("device location", location)?;
 
-                ..Default::default()
-            }),
+        self.send_device_info(DeviceInfoUpdate {
+            device_location: Some(location.to_string()),
             ..Default::default()
         })
+        .await
     }
 
-    async fn set_additional_configuration(
+    async fn verify_credentials(&self) -> Result<bool, IpCamerasError> {
+        let host = self.host();
+        self.check_credentials(format!("http://{host}/ISAPI/System/deviceInfo"))
+            .await
+    }
+
+    async fn get_system_status(&self) -> Result<SystemStatus, IpCamerasError> {
+        let host = self.host();
+        let status: DeviceStatus = self
+            .recieve(format!("http://{host}/ISAPI/System/status"))
+            .await?;
+
+        Ok(status.into())
+    }
+
+    async fn get_snapshot(&self) -> Result<Vec<u8>, IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        self.request_bytes(
+            format!("http://{host}/ISAPI/Streaming/channels/{channel}01/picture"),
+            None,
+            Some(LONG_REQUEST_TIMEOUT),
+        )
+        .await
+    }
+
+    async fn set_jpeg_params(
         &self,
-        configuration: AdditionalConfiguration,
+        size: Option<i32>,
+        exif: Option<bool>,
+        merge_size: Option<i32>,
     ) -> Result<(), IpCamerasError> {
-        match configuration.default_settings {
-            Some(ds) => {
-                if ds {
-                    return self.send_common_default_settings().await;
+        for (name, size) in [("JPEG size", size), ("merged JPEG size", merge_size)] {
+            if let Some(size) = size {
+                if size <= 0 {
+                    return Err(IpCamerasError::InvalidParam(format!(
+                        "{name} must be positive, got {size}"
+                    )));
                 }
             }
-            _ => (),
         }
 
-        match configuration.hikvision {
-            Some(configuration) => {
-                if configuration.default_switch {
-                    let mut new_projectors = Vec::new();
-
-                    if configuration.external_projector {
-                        new_projectors.push(5)
-                    }
+        let mut ic = self.retrieve_image_channel().await?;
 
-                    if configuration.internal_projector {
-                        new_projectors.push(7)
-                    }
+        // Firmwares reject the merged size while only one shutter is taken
+        let double_shut = ic
+            .image_multi_shut
+            .as_ref()
+            .map_or(false, |ims| ims.double_shut_enable);
+        if merge_size.is_some() && !double_shut {
+            return Err(IpCamerasError::InvalidParam(
+                "merged JPEG size requires ImageMultishut doubleShutEnable".to_string(),
+            ));
+        }
 
-                    self.projectors.lock()?.projectors_lines = new_projectors;
-                }
+        let jpeg_param = unwrap_some!(
+            ic.jpeg_param.as_mut(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
 
-                if let Some(ic) = configuration.image_channel {
-                    self.send_image_channel(ic.clone()).await?;
-                }
+        if size.is_some() {
+            jpeg_param.jpeg_size = size;
+        }
+        if exif.is_some() {
+            jpeg_param.exif_information_enabled = exif;
+        }
+        if merge_size.is_some() {
+            jpeg_param.merge_jpeg_size = merge_size;
+        }
 
-                if let Some(sc) = configuration.streaming_channel {
-                    self.send_video_settings(sc).await?;
-                }
+        self.send_image_channel_jpeg_param(&ic).await
+    }
 
-                Ok(())
+    async fn set_overexpose_suppress(
+        &self,
+        enabled: bool,
+        distance_level: Option<i32>,
+    ) -> Result<(), IpCamerasError> {
+        if let Some(level) = distance_level {
+            if !(OVEREXPOSE_DISTANCE_LEVEL_MIN..=OVEREXPOSE_DISTANCE_LEVEL_MAX).contains(&level) {
+                return Err(IpCamerasError::InvalidParam(format!(
+                    "overexpose suppress distance level must be in \
+                     {OVEREXPOSE_DISTANCE_LEVEL_MIN}..={OVEREXPOSE_DISTANCE_LEVEL_MAX}, got {level}"
+                )));
             }
-            _ => Ok(()),
         }
-    }
-}
-
-impl HikvisionHttp {
-    async fn send<S>(&self, url: String, settings: S) -> Result<(), IpCamerasError>
-    where
-        S: Serialize + Send + 'static + std::fmt::Debug,
-    {
-        let response: Response = from_str(
-            &self
-                .request(
-                    url,
-                    Some(format!(r#"{}"#, to_string(&settings)?)),
-                    Method::PUT,
-                    None,
-                )
-                .await?,
-        )?;
-
-        if response.is_ok() {
-            Ok(())
-        } else {
-            let error_code = response.status_code;
-            let err_msg = response.status_string;
 
-            error!("Hikvision send reqwest error: {err_msg} with code {error_code}");
+        let mut ic = self.retrieve_image_channel().await?;
+        let exposure = unwrap_some!(
+            ic.exposure.as_mut(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
 
-            Err(IpCamerasError::from(ErrorKind::InvalidData))
+        let suppress = exposure
+            .overexpose_suppress
+            .get_or_insert_with(Default::default);
+        suppress.enabled = enabled;
+        if distance_level.is_some() {
+            suppress.ost = Some(OverexposeSuppressType::MANUAL);
+            suppress.distance_level = distance_level;
         }
-    }
 
-    async fn recieve<D>(&self, url: String) -> Result<D, IpCamerasError>
-    where
-        D: DeserializeOwned,
-    {
-        Ok(from_str(
-            &self.request(url, None, Method::GET, None).await?,
-        )?)
+        self.send_image_channel_exposure(&ic).await
     }
 
-    // FUNCTIONS TO PREPEARE RECIEVE|SEND
-    async fn retrieve_spotlight_settings(&self) -> Result<SPSettings, IpCamerasError> {
-        let host = self.host.clone().unwrap_or_default();
-
-        let ss = match self.camera_role {
-            CameraRole::View => return Ok(SPSettings::default()),
-            _ => {
-                if self.is_ptz.load(Relaxed) {
-                    Ok(self
-                        .recieve::<IrcutFilter>(format!(
-                            "http://{host}/ISAPI/Image/channels/1/ircutFilter"
-                        ))
-                        .await?
-                        .into())
-                } else {
-                    Ok(self
-                        .recieve::<ImageIcrE>(format!("http://{host}/ISAPI/Image/channels/1/icr"))
-                        .await?
-                        .into())
-                }
+    async fn set_face_exposure(
+        &self,
+        enabled: bool,
+        sensitivity: Option<i32>,
+    ) -> Result<(), IpCamerasError> {
+        if let Some(sensitivity) = sensitivity {
+            if !(FACE_EXPOSURE_SENSITIVITY_MIN..=FACE_EXPOSURE_SENSITIVITY_MAX)
+                .contains(&sensitivity)
+            {
+                return Err(IpCamerasError::InvalidParam(format!(
+                    "face exposure sensitivity must be in \
+                     {FACE_EXPOSURE_SENSITIVITY_MIN}..={FACE_EXPOSURE_SENSITIVITY_MAX}, got {sensitivity}"
+                )));
             }
-        };
+        }
 
-        trace!(
-            "Projector hik spotlight settings: addr: {}, return: {:?}",
-            self.host(),
-            ss
+        let mut ic = self.retrieve_image_channel().await?;
+        let exposure = unwrap_some!(
+            ic.exposure.as_mut(),
+            return Err(IpCamerasError::NotAvialiableApi)
         );
 
-        ss
-    }
+        let face_exposure = exposure.face_exposure.get_or_insert(FaceExposure {
+            enabled: None,
+            sensitivity: None,
+        });
+        face_exposure.enabled = Some(enabled);
+        if sensitivity.is_some() {
+            face_exposure.sensitivity = sensitivity;
+        }
 
-    async fn get_raw_projectors_params(&self) -> Result<SyncSignalOutputList, IpCamerasError> {
-        let host = self.host.clone().unwrap_or_default();
+        self.send_image_channel_exposure(&ic).await
+    }
 
-        let ssol = match self.camera_role {
-            CameraRole::View => return Err(IpCamerasError::NotAvialiableApi),
-            _ => {
-                self.recieve::<SyncSignalOutputList>(format!(
-                    "http://{host}/ISAPI/ITC/syncSignalOutput"
-                ))
-                .await?
-            }
-        };
+    async fn get_bright_enhance(&self) -> Result<i32, IpCamerasError> {
+        let ic = self.retrieve_image_channel().await?;
+        let be = unwrap_some!(
+            ic.bright_enhance,
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
 
-        Ok(ssol)
+        Ok(be.bright_enhance_level)
     }
 
-    async fn send_projectors_settings(
-        &self,
-        host: &str,
-        ps: SyncSignalOutputList,
-    ) -> Result<(), IpCamerasError> {
-        let projector = self
-            .send::<SyncSignalOutputList>(
-                format!("http://{host}/ISAPI/ITC/syncSignalOutput"),
-                ps.into(),
-            )
-            .await;
+    async fn set_bright_enhance(&self, level: i32) -> Result<(), IpCamerasError> {
+        if !(BRIGHT_ENHANCE_LEVEL_MIN..=BRIGHT_ENHANCE_LEVEL_MAX).contains(&level) {
+            return Err(IpCamerasError::InvalidParam(format!(
+                "bright enhance level must be in \
+                 {BRIGHT_ENHANCE_LEVEL_MIN}..={BRIGHT_ENHANCE_LEVEL_MAX}, got {level}"
+            )));
+        }
 
-        trace!(
-            "Switch day|night hik status: addr: {}, return: {:?}",
-            self.host(),
-            projector
+        let mut ic = self.retrieve_image_channel().await?;
+        let be = unwrap_some!(
+            ic.bright_enhance.as_mut(),
+            return Err(IpCamerasError::NotAvialiableApi)
         );
+        if be.bright_enhance_level == level {
+            return Ok(());
+        }
+        be.bright_enhance_level = level;
 
-        projector
+        self.send_image_channel_bright_enhance(&ic).await
     }
 
-    async fn send_icr_settings(&self, host: &str, ss: SPSettings) -> Result<(), IpCamerasError> {
-        let day_and_night = self
-            .send::<ImageIcrE>(
-                format!("http://{host}/ISAPI/Image/channels/1/icr"),
-                ss.into(),
-            )
-            .await;
-        trace!(
-            "Projector hik status: addr: {}, return: {:?}",
-            self.host(),
-            day_and_night
+    async fn get_image_settings(&self) -> Result<ImageSettings, IpCamerasError> {
+        Ok((&self.retrieve_image_channel().await?).into())
+    }
+
+    async fn set_gray_scale(&self, mode: GrayScaleMode) -> Result<(), IpCamerasError> {
+        let mut ic = self.retrieve_image_channel().await?;
+        let color = unwrap_some!(
+            ic.color.as_mut(),
+            return Err(IpCamerasError::NotAvialiableApi)
         );
+        let gray_scale = Some(GrayScale {
+            gray_scale_mode: mode,
+        });
+        if color.gray_scale == gray_scale {
+            return Ok(());
+        }
+        color.gray_scale = gray_scale;
 
-        day_and_night
+        self.send_image_channel_color(&ic).await
     }
 
-    async fn send_ptz_icr_settings(
+    async fn set_temp_range(
         &self,
-        host: &str,
-        ss: SPSettings,
+        mode: TempRangeMode,
+        upper: Option<i32>,
+        lower: Option<i32>,
     ) -> Result<(), IpCamerasError> {
-        let day_and_night = self
-            .send::<IrcutFilter>(
-                format!("http://{host}/ISAPI/Image/channels/1/ircutFilter"),
-                ss.into(),
-            )
-            .await;
-        trace!(
-            "Projector hik status: addr: {}, return: {:?}",
-            self.host(),
-            day_and_night
+        match (&mode, upper, lower) {
+            (TempRangeMode::MANUAL, Some(upper), Some(lower)) if upper < lower => {
+                return Err(IpCamerasError::InvalidParam(format!(
+                    "temperature upper limit {upper} is below the lower limit {lower}"
+                )));
+            }
+            (TempRangeMode::MANUAL, Some(_), Some(_)) => {}
+            (TempRangeMode::MANUAL, _, _) => {
+                return Err(IpCamerasError::InvalidParam(
+                    "manual temperature range needs both limits".to_string(),
+                ));
+            }
+            (TempRangeMode::AUTOMATIC, None, None) => {}
+            (TempRangeMode::AUTOMATIC, _, _) => {
+                return Err(IpCamerasError::InvalidParam(
+                    "temperature limits are only used in manual mode".to_string(),
+                ));
+            }
+        }
+
+        let mut ic = self.retrieve_image_channel().await?;
+        let tr = unwrap_some!(
+            ic.temp_range.as_mut(),
+            return Err(IpCamerasError::NotAvialiableApi)
         );
+        let range = TempRange {
+            mode: Some(mode),
+            temperature_upper_limit: upper,
+            temperature_lower_limit: lower,
+        };
+        if *tr == range {
+            return Ok(());
+        }
+        *tr = range;
 
-        day_and_night
+        self.send_image_channel_temp_range(&ic).await
     }
 
-    async fn send_spotlight_settings(
+    async fn set_car_window_enhancement(
         &self,
-        ss: SPSettings,
-        ps: SyncSignalOutputList,
+        enabled: bool,
+        brighten: i32,
+        defog: i32,
     ) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        match self.camera_role {
-            CameraRole::View => return Ok(()),
-            _ => {
-                if self.is_ptz.load(Relaxed) {
-                    trace!("PTZ switch");
-                    self.send_ptz_icr_settings(host, ss).await
-                } else {
-                    self.send_projectors_settings(host, ps)
-                        .await
-                        .and(self.send_icr_settings(host, ss).await)
-                }
+        for (name, level) in [("brighten", brighten), ("defog", defog)] {
+            if !(CAR_WINDOW_LEVEL_MIN..=CAR_WINDOW_LEVEL_MAX).contains(&level) {
+                return Err(IpCamerasError::InvalidParam(format!(
+                    "car window {name} level must be in \
+                     {CAR_WINDOW_LEVEL_MIN}..={CAR_WINDOW_LEVEL_MAX}, got {level}"
+                )));
             }
         }
+
+        let mut ic = self.retrieve_image_channel().await?;
+        let snap = unwrap_some!(
+            ic.itc_image_snap.as_mut(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        let enhancement = Some(CarWindowEnhancement {
+            enabled,
+            brighten_level: brighten,
+            defog_level: defog,
+        });
+        if snap.car_window_enhancement == enhancement {
+            return Ok(());
+        }
+        snap.car_window_enhancement = enhancement;
+
+        self.send_image_channel_itc_image_snap(&ic).await
     }
 
-    async fn retrieve_video_settings(&self) -> Result<StreamingChannel, IpCamerasError> {
-        let host = self.host();
-        self.recieve(format!("http://{host}/ISAPI/Streaming/channels/1"))
-            .await
+    async fn get_iris(&self) -> Result<i32, IpCamerasError> {
+        let ic = self.retrieve_image_channel().await?;
+        let iris = unwrap_some!(ic.iris, return Err(IpCamerasError::NotAvialiableApi));
+
+        iris.iris_level.ok_or(IpCamerasError::NotAvialiableApi)
     }
 
-    async fn send_video_settings(&self, sc: StreamingChannel) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        self.send(format!("http://{host}/ISAPI/Streaming/channels/1"), sc)
-            .await
+    async fn set_iris(&self, level: i32) -> Result<(), IpCamerasError> {
+        let mut ic = self.retrieve_image_channel().await?;
+        let iris = unwrap_some!(
+            ic.iris.as_mut(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+
+        let level = iris.clamp_level(level);
+        if iris.iris_level == Some(level) {
+            return Ok(());
+        }
+        iris.iris_level = Some(level);
+
+        self.send_image_channel_iris(&ic).await
     }
 
-    async fn retrieve_version_of_camera(&self) -> Result<DeviceInfo, IpCamerasError> {
-        let host = self.host();
-        self.recieve(format!("http://{host}/ISAPI/System/deviceInfo"))
-            .await
+    async fn set_image_loss_detection(&self, enabled: bool) -> Result<(), IpCamerasError> {
+        let mut ic = self.retrieve_image_channel().await?;
+        if ic.enable_image_loss_detection == Some(enabled) {
+            return Ok(());
+        }
+        ic.enable_image_loss_detection = Some(enabled);
+
+        self.send_image_channel_whole(&ic).await
     }
 
-    async fn retrieve_image_channel(&self) -> Result<ImageChannel, IpCamerasError> {
-        let host = self.host();
-        self.recieve(format!("http://{host}/ISAPI/Image/channels/1"))
-            .await
+    async fn set_mirror(&self, enabled: bool) -> Result<(), IpCamerasError> {
+        let mut sc = self.retrieve_video_settings().await?;
+        if sc.video.mirror_enabled == Some(enabled) {
+            return Ok(());
+        }
+        sc.video.mirror_enabled = Some(enabled);
+
+        self.send_video_settings(sc).await
     }
 
-    // This spaghetti code needs coz hikvision can't process image_channel request.
-    // Faggot
-    async fn send_image_channel(&self, ic: ImageChannel) -> Result<(), IpCamerasError> {
-        let common_req = Ok(())
-            .and(self.send_image_channel_color(&ic).await)
-            .and(self.send_image_channel_sharpness(&ic).await)
-            .and(self.send_image_channel_gain(&ic).await)
-            .and(self.send_image_channel_shutter(&ic).await);
+    async fn set_rotation(&self, degrees: i32) -> Result<(), IpCamerasError> {
+        if !ROTATION_DEGREES.contains(&degrees) {
+            return Err(IpCamerasError::InvalidParam(format!(
+                "rotation must be one of {ROTATION_DEGREES:?}, got {degrees}"
+            )));
+        }
 
-        match self.camera_role {
-            CameraRole::Register => common_req
-                .and(self.send_image_channel_white_balance(&ic).await)
-                .and(self.send_image_channel_noise_reduce_ext(&ic).await)
-                .and(self.send_image_channel_gamma_correction(&ic).await)
-                .and(self.send_image_channel_noise_reduce_2d(&ic).await)
-                .and(self.send_image_channel_bright_enhance(&ic).await),
-            CameraRole::View => common_req
-                .and(self.send_image_channel_exposure(&ic).await)
-                .and(self.send_image_channel_hlc(&ic).await)
-                .and(self.send_image_channel_noise_reduce(&ic).await),
-            _ => Ok(()),
+        let mut sc = self.retrieve_video_settings().await?;
+        if sc.video.rotation_degree == Some(degrees) {
+            return Ok(());
         }
+        sc.video.rotation_degree = Some(degrees);
+
+        self.send_video_settings(sc).await
     }
 
-    async fn send_image_channel_color(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        let c = unwrap_some!(
-            ic.color.clone(),
-            return Err(IpCamerasError::NotAvialiableApi)
-        );
-        self.send(format!("http://{host}/ISAPI/Image/channels/1/color"), c)
-            .await
+    async fn get_capture_mode(&self) -> Result<String, IpCamerasError> {
+        let ic = self.retrieve_image_channel().await?;
+
+        ic.capture_mode
+            .and_then(|cm| cm.mode)
+            .ok_or(IpCamerasError::NotAvialiableApi)
     }
 
-    async fn send_image_channel_sharpness(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+    async fn set_capture_mode(&self, mode: String) -> Result<(), IpCamerasError> {
         let host = self.host();
-        let s = unwrap_some!(
-            ic.sharpness.clone(),
-            return Err(IpCamerasError::NotAvialiableApi)
-        );
+        let channel = self.channel;
 
-        self.send(format!("http://{host}/ISAPI/Image/channels/1/sharpness"), s)
+        // Older firmwares have no capabilities for it, then the camera validates the mode itself
+        let options = match self
+            .retrieve_options(
+                format!("http://{host}/ISAPI/Image/channels/{channel}/captureMode/capabilities"),
+                "mode",
+            )
             .await
-    }
+        {
+            Err(IpCamerasError::NotAvialiableApi) => None,
+            options => options?,
+        };
+        if let Some(options) = options {
+            if !options.contains(&mode) {
+                return Err(IpCamerasError::InvalidParam(format!(
+                    "capture mode must be one of {options:?}, got {mode}"
+                )));
+            }
+        }
 
-    async fn send_image_channel_white_balance(
-        &self,
-        ic: &ImageChannel,
-    ) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        let wb = unwrap_some!(
-            ic.white_balance.clone(),
+        let mut ic = self.retrieve_image_channel().await?;
+        let capture_mode = unwrap_some!(
+            ic.capture_mode.as_mut(),
             return Err(IpCamerasError::NotAvialiableApi)
         );
-        self.send(
-            format!("http://{host}/ISAPI/Image/channels/1/whiteBalance"),
-            wb,
-        )
-        .await
-    }
+        capture_mode.mode = Some(mode);
 
-    async fn send_image_channel_bright_enhance(
-        &self,
-        ic: &ImageChannel,
-    ) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        let be = unwrap_some!(
-            ic.bright_enhance.clone(),
-            return Err(IpCamerasError::NotAvialiableApi)
-        );
-        self.send(
-            format!("http://{host}/ISAPI/Image/channels/1/brightEnhance"),
-            be,
-        )
-        .await
+        self.send_image_channel_capture_mode(&ic).await
     }
 
-    async fn send_image_channel_shutter(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        let s = unwrap_some!(
-            ic.shutter.clone(),
+    async fn set_dss(&self, enabled: bool, level: Option<String>) -> Result<(), IpCamerasError> {
+        if let Some(level) = level.as_deref() {
+            if !DSS_LEVELS.contains(&level) {
+                return Err(IpCamerasError::InvalidParam(format!(
+                    "DSS level must be one of {DSS_LEVELS:?}, got {level}"
+                )));
+            }
+        }
+
+        let mut ic = self.retrieve_image_channel().await?;
+        let dss = unwrap_some!(
+            ic.dss.as_mut(),
             return Err(IpCamerasError::NotAvialiableApi)
         );
-        self.send(format!("http://{host}/ISAPI/Image/channels/1/shutter"), s)
-            .await
+
+        let level = level.or_else(|| dss.dss_level.clone());
+        if dss.enabled == enabled && dss.dss_level == level {
+            return Ok(());
+        }
+        dss.enabled = enabled;
+        dss.dss_level = level;
+
+        self.send_image_channel_dss(&ic).await
     }
 
-    async fn send_image_channel_noise_reduce_2d(
+    async fn set_plate_bright(
         &self,
-        ic: &ImageChannel,
+        enabled: bool,
+        sensitivity: Option<i32>,
+        correction: Option<i32>,
     ) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        let nrd = unwrap_some!(
-            ic.noise_reduce_2d.clone(),
-            return Err(IpCamerasError::NotAvialiableApi)
-        );
-        self.send(
-            format!("http://{host}/ISAPI/Image/channels/1/NoiseReduce2D"),
-            nrd,
-        )
-        .await
-    }
+        if let Some(sensitivity) = sensitivity {
+            if !(PLATE_BRIGHT_SENSITIVITY_MIN..=PLATE_BRIGHT_SENSITIVITY_MAX).contains(&sensitivity)
+            {
+                return Err(IpCamerasError::InvalidParam(format!(
+                    "plate bright sensitivity must be in \
+                     {PLATE_BRIGHT_SENSITIVITY_MIN}..={PLATE_BRIGHT_SENSITIVITY_MAX}, got {sensitivity}"
+                )));
+            }
+        }
+        if let Some(correction) = correction {
+            if !(PLATE_BRIGHT_CORRECTION_MIN..=PLATE_BRIGHT_CORRECTION_MAX).contains(&correction) {
+                return Err(IpCamerasError::InvalidParam(format!(
+                    "plate bright correction must be in \
+                     {PLATE_BRIGHT_CORRECTION_MIN}..={PLATE_BRIGHT_CORRECTION_MAX}, got {correction}"
+                )));
+            }
+        }
 
-    async fn send_image_channel_gain(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        let g = unwrap_some!(
-            ic.gain.clone(),
+        let mut ic = self.retrieve_image_channel().await?;
+        let plate_bright = unwrap_some!(
+            ic.plate_bright.as_mut(),
             return Err(IpCamerasError::NotAvialiableApi)
         );
-        self.send(format!("http://{host}/ISAPI/Image/channels/1/gain"), g)
-            .await
+
+        plate_bright.plate_bright_enabled = Some(enabled);
+        if sensitivity.is_some() {
+            plate_bright.plate_bright_sensitivity = sensitivity;
+        }
+        if correction.is_some() {
+            plate_bright.correct_factor_enabled = Some(true);
+            plate_bright.correct_factor = correction;
+        }
+
+        self.send_image_channel_plate_bright(&ic).await
     }
 
-    async fn send_image_channel_gamma_correction(
-        &self,
-        ic: &ImageChannel,
-    ) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        let gc = unwrap_some!(
-            ic.gamma_correction.clone(),
-            return Err(IpCamerasError::NotAvialiableApi)
-        );
-        self.send(
-            format!("http://{host}/ISAPI/Image/channels/1/gammaCorrection"),
-            gc,
-        )
-        .await
+    async fn get_transport(&self) -> Result<TransportInfo, IpCamerasError> {
+        Ok(self.retrieve_transport().await?.into())
     }
 
-    async fn send_image_channel_noise_reduce(
+    async fn set_transport(
         &self,
-        ic: &ImageChannel,
+        tcp: bool,
+        multicast: Option<MulticastConfig>,
     ) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        let nre = unwrap_some!(
-            ic.noise_reduce.clone(),
-            return Err(IpCamerasError::NotAvialiableApi)
-        );
-        self.send(
-            format!("http://{host}/ISAPI/Image/channels/1/noiseReduce"),
-            nre,
-        )
-        .await
+        let mut transport = self.retrieve_transport().await?;
+
+        let rtp_transport_type = if tcp {
+            RtpTransportType::TCP
+        } else {
+            RtpTransportType::UDP
+        };
+        match transport.unicast.as_mut() {
+            Some(unicast) => unicast.rtp_transport_type = Some(rtp_transport_type),
+            None => {
+                transport.unicast = Some(Unicast {
+                    enabled: true,
+                    interface_id: None,
+                    rtp_transport_type: Some(rtp_transport_type),
+                })
+            }
+        }
+
+        transport.multicast = match multicast {
+            Some(mc) => Some(Multicast {
+                enabled: true,
+                dest_ip_address: Some(mc.address),
+                video_dest_port_no: Some(mc.port),
+            }),
+            None => transport.multicast.map(|mc| Multicast {
+                enabled: false,
+                ..mc
+            }),
+        };
+
+        self.send_transport(transport).await
     }
 
-    async fn send_image_channel_hlc(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        let nre = unwrap_some!(ic.hlc.clone(), return Err(IpCamerasError::NotAvialiableApi));
-        self.send(format!("http://{host}/ISAPI/Image/channels/1/HLC"), nre)
-            .await
+    async fn get_rtsp_port(&self) -> Result<u32, IpCamerasError> {
+        Ok(self.retrieve_transport().await?.rtsp_port_no)
     }
 
-    async fn send_image_channel_exposure(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        let nre = unwrap_some!(
-            ic.exposure.clone(),
-            return Err(IpCamerasError::NotAvialiableApi)
-        );
-        self.send(
-            format!("http://{host}/ISAPI/Image/channels/1/exposure"),
-            nre,
-        )
-        .await
+    async fn set_rtsp_port(&self, port: u32) -> Result<(), IpCamerasError> {
+        validate_rtsp_port(port)?;
+
+        let mut transport = self.retrieve_transport().await?;
+        if transport.rtsp_port_no == port {
+            return Ok(());
+        }
+        transport.rtsp_port_no = port;
+
+        self.send_transport(transport).await
     }
 
-    async fn send_image_channel_noise_reduce_ext(
+    async fn set_date_time(
         &self,
-        ic: &ImageChannel,
+        date_time: chrono::DateTime<chrono::FixedOffset>,
     ) -> Result<(), IpCamerasError> {
         let host = self.host();
-        let nre = unwrap_some!(
-            ic.noise_reduce_ext.clone(),
-            return Err(IpCamerasError::NotAvialiableApi)
-        );
-        self.send(
-            format!("http://{host}/ISAPI/Image/channels/1/noiseReduceExt"),
-            nre,
-        )
-        .await
-    }
+        let mut time = self.retrieve_time_settings().await?;
 
-    #[allow(dead_code)]
-    async fn retrieve_time_settings(&self) -> Result<Time, IpCamerasError> {
-        let host = self.host();
-        self.recieve(format!("http://{host}/ISAPI/System/time"))
+        time.time_mode = dublicates::TimeMode::MANUAL;
+        time.local_time = date_time.format("%FT%T%:z").to_string();
+        time.time_zone = hik_time_zone(date_time.offset());
+
+        self.send(format!("http://{host}/ISAPI/System/time"), time)
             .await
     }
 
-    #[allow(dead_code)]
-    async fn send_time_settings(&self, ts: (Time, NTPServer)) -> Result<(), IpCamerasError> {
-        let (t, ntp) = ts;
-        let host = self.host();
+    async fn get_settings_json(&self) -> Result<serde_json::Value, IpCamerasError> {
+        let (image_channel, streaming_channel) = self.retrieve_common_settings().await?;
 
-        self.send(format!("http://{host}/ISAPI/System/time"), t)
-            .await
-            .and(
-                self.send(format!("http://{host}/ISAPI/System/time/ntpServers/1"), ntp)
-                    .await,
-            )
+        Ok(serde_json::json!({
+            "image_channel": image_channel,
+            "streaming_channel": streaming_channel,
+        }))
     }
 
-    async fn retrieve_ptz_channel(&self) -> Result<PTZChannel, IpCamerasError> {
+    async fn get_time_zone(&self) -> Result<String, IpCamerasError> {
+        Ok(self.retrieve_time_settings().await?.time_zone)
+    }
+
+    // Hikvision stores the POSIX string as is, DST rules included
+    async fn set_time_zone(&self, tz: String) -> Result<(), IpCamerasError> {
+        validate_time_zone(&tz)?;
+
         let host = self.host();
+        let mut time = self.retrieve_time_settings().await?;
+        if time.time_zone == tz {
+            return Ok(());
+        }
+        time.time_zone = tz;
 
-        self.recieve(format!("http://{host}/ISAPI/PTZCtrl/channels/1"))
+        self.send(format!("http://{host}/ISAPI/System/time"), time)
             .await
     }
 
-    async fn send_focus_settings(&self, fd: FocusData) -> Result<(), IpCamerasError> {
-        let host = self.host();
-        match self.camera_role {
-            CameraRole::View => {
-                self.send(
-                    format!("http://{host}/ISAPI/System/Video/inputs/channels/1/focus"),
-                    fd,
-                )
-                .await
-            }
-            _ => Err(IpCamerasError::NotAvialiableApi),
-        }
+    async fn get_additional_configuration(
+        &self,
+    ) -> Result<AdditionalConfiguration, IpCamerasError> {
+        self.ensure_initialized().await?;
+        let hikvision = Some(self.prepare_hikvision_configuration().await?);
+        let id = self.id;
+
+        Ok(AdditionalConfiguration {
+            id,
+            hikvision,
+            default_settings: Some(false),
+
+            ..Default::default()
+        })
     }
 
-    #[allow(dead_code)]
-    async fn default_time_settings(&self) -> Result<(Time, NTPServer), IpCamerasError> {
-        let mut time = self.retrieve_time_settings().await?;
-        time.time_mode = dublicates::TimeMode::NTP;
+    async fn apply_role_defaults(&self) -> Result<(), IpCamerasError> {
+        self.send_role_settings(&self.camera_role, |_| ()).await
+    }
 
-        let ntp_server = NTPServer {
-            id: 1,
-            addresing_format_type: AddresingFormatType::IPADDRESS,
-            ip_address: Some("172.16.16.10".to_owned()),
-            synchronize_interval: Some(60),
-            port_no: Some(123),
-            host_name: None,
-            ip6_address: None,
-        };
+    async fn get_default_configuration(&self) -> Result<AdditionalConfiguration, IpCamerasError> {
+        let (ic, sc) = self.retrieve_common_default_settings().await?;
 
-        Ok((time, ntp_server))
+        Ok(AdditionalConfiguration {
+            id: self.id,
+            default_settings: Some(true),
+            hikvision: Some(HikvisionConfiguration {
+                image_channel: Some(ic),
+                streaming_channel: Some(sc),
+
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
     }
 
-    async fn default_general_settings(&self) -> Result<ImageChannel, IpCamerasError> {
-        let mut ic = self.retrieve_image_channel().await?;
+    async fn set_additional_configuration(
+        &self,
+        configuration: AdditionalConfiguration,
+    ) -> Result<(), IpCamerasError> {
+        // A later lazy init would overwrite the projector lines set here
+        self.ensure_initialized().await?;
 
-        //Setting default params
-        match self.camera_role {
-            CameraRole::Register => {
-                ic.color.as_mut().map(|color| {
-                    color.saturation_level = 50;
-                    color.night_mode = Some(true);
-                    color.brightness_level = 75;
-                    color.contrast_level = 18;
-                });
-                ic.sharpness.as_mut().map(|sharpness| {
-                    sharpness.sharpness_level = 60;
-                });
-                ic.white_balance.as_mut().map(|w_b| {
-                    w_b.white_balance_style = WhiteBalanceStyle::AUTO1;
-                });
-                ic.bright_enhance.as_mut().map(|b_e| {
-                    b_e.bright_enhance_level = 70;
-                });
-                ic.plate_bright.as_mut().map(|p_b| {
-                    p_b.plate_bright_enabled = Some(true);
-                    p_b.plate_bright_sensitivity = Some(5);
-                });
-                ic.gamma_correction.as_mut().map(|g_c| {
-                    g_c.gamma_correction_enabled = true;
-                    g_c.gamma_correction_level = 30;
-                });
-                ic.shutter.as_mut().map(|shutter| {
-                    shutter.shutter_level = 2000.to_string();
-                });
-                ic.gain.as_mut().map(|gain| {
-                    gain.gain_level = 30;
-                });
-                ic.noise_reduce_2d.as_mut().map(|nr2d| {
-                    nr2d.noise_reduce_2d_enable = true;
-                    nr2d.noise_reduce_2d_level = 55;
-                });
-                ic.noise_reduce_ext.as_mut().map(|nre| {
-                    nre.mode = NoiseReduceMode::GENERAL;
-                    nre.general_mode.general_level = 100;
-                });
-            }
-            CameraRole::View => {
-                ic.color.as_mut().map(|color| {
-                    color.brightness_level = 62;
-                    color.saturation_level = 50;
-                    color.contrast_level = 44;
-                    color.gray_scale = Some(GrayScale {
-                        gray_scale_mode: GrayScaleMode::OUTDOOR,
-                    });
-                });
-                ic.sharpness.as_mut().map(|sharpness| {
-                    sharpness.sharpness_level = 50;
-                });
-                ic.exposure.as_mut().map(|exposure| {
-                    exposure.exposure_type = ExposureType::AUTO;
-                    exposure.auto_iris_level = Some(100);
-                    exposure.overexpose_suppress = Some(OverexposeSuppress {
-                        enabled: false,
-                        ..Default::default()
-                    });
-                });
-                ic.shutter.as_mut().map(|shutter| {
-                    shutter.shutter_level = "1/500".to_string();
-                });
-                ic.gain.as_mut().map(|gain| {
-                    gain.gain_level = 65;
-                });
-                ic.hlc.as_mut().map(|hlc| {
-                    hlc.enabled = true;
-                    hlc.hlc_level = 50;
-                });
-                ic.noise_reduce.as_mut().map(|n_r| {
-                    n_r.mode = NoiseReduceMode::GENERAL;
-                    n_r.general_mode = Some(GeneralMode { general_level: 51 });
-                });
+        match configuration.default_settings {
+            Some(ds) => {
+                if ds {
+                    return self.apply_role_defaults().await;
+                }
             }
             _ => (),
         }
 
-        Ok(ic)
-    }
+        match configuration.hikvision {
+            Some(configuration) => {
+                if configuration.default_switch {
+                    let mut new_projectors = Vec::new();
 
-    async fn default_video_settings(&self) -> Result<StreamingChannel, IpCamerasError> {
-        let mut sc = self.retrieve_video_settings().await?;
+                    if configuration.external_projector {
+                        new_projectors.push(self.external_line)
+                    }
 
-        //Setting default params
-        match self.camera_role {
-            CameraRole::View => {
-                sc.video.max_frame_rate = 1000;
-                sc.video.video_resolution_width = 2592;
-                sc.video.video_resolution_height = 1944;
-                sc.video.video_quality_control_type = Some("cbr".to_string());
-                sc.video.constant_bit_rate = Some(8192);
-                sc.video.gov_length = Some(10);
-                sc.video.h264_profile = Some(H264Profile::Baseline);
-                sc.video.svc = Some(SVC {
-                    enabled: Some(false),
-                    svc_mode: None,
-                });
-                sc.video.smoothing = Some(1);
-                sc.video.smart_codec = Some(SmartCodec { enabled: false });
-            }
-            CameraRole::Register => {
-                sc.video.max_frame_rate = 1000;
-                sc.video.video_resolution_width = 4096;
-                sc.video.video_resolution_height = 2160;
-                sc.video.svc = Some(SVC {
-                    enabled: None,
-                    svc_mode: Some(SVCMode::CLOSE),
-                });
-                sc.video.gov_length = Some(10);
+                    if configuration.internal_projector {
+                        new_projectors.push(self.internal_line)
+                    }
+
+                    self.projectors.lock()?.projectors_lines = new_projectors;
+                }
+
+                if let Some(ic) = configuration.image_channel {
+                    self.send_image_channel(ic.clone(), &self.camera_role)
+                        .await?;
+                }
+
+                if let Some(sc) = configuration.streaming_channel {
+                    self.send_video_settings(sc).await?;
+                }
+
+                Ok(())
             }
-            _ => (),
+            _ => Ok(()),
         }
-        Ok(sc)
     }
+}
 
-    async fn retrieve_common_settings(
-        &self,
-    ) -> Result<(ImageChannel, StreamingChannel), IpCamerasError> {
-        Ok((
-            self.retrieve_image_channel().await?,
-            self.retrieve_video_settings().await?,
-        ))
+impl HikvisionHttp {
+    // Timeout in seconds
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
     }
 
-    async fn retrieve_common_default_settings(
-        &self,
-    ) -> Result<(ImageChannel, StreamingChannel), IpCamerasError> {
-        Ok((
-            self.default_general_settings().await?,
-            self.default_video_settings().await?,
-        ))
+    pub fn with_credentials(mut self, username: Option<String>, password: Option<String>) -> Self {
+        self.username = username;
+        self.password = password;
+        self
     }
 
-    async fn send_common_default_settings(&self) -> Result<(), IpCamerasError> {
-        Ok(self
-            .send_video_settings(self.default_video_settings().await?)
-            .await
-            .and(
-                self.send_image_channel(self.default_general_settings().await?)
-                    .await,
-            )?)
+    // Replaces the default reqwest/curl agent
+    pub fn with_user_agent(mut self, agent: impl Into<String>) -> Self {
+        self.headers.retain(|h| !matches!(h, Header::UserAgent(_)));
+        self.headers.push(Header::UserAgent(agent.into()));
+        self
     }
 
-    async fn prepare_raw_projectors(&self) -> Result<Vec<u8>, IpCamerasError> {
-        let ssol = self.get_raw_projectors_params().await?;
-        let mut projectors = Vec::new();
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers.extend(
+            headers
+                .into_iter()
+                .map(|(name, value)| Header::Custom(name, value)),
+        );
+        self
+    }
 
-        //Get 5 line
-        if let Some(external) = ssol.sync_signal_output_list.get(4) {
-            trace!("External state is {:?}", external);
-            projectors.push(5)
-        };
+    // For firmwares that break compressed answers
+    pub fn without_compression(mut self) -> Self {
+        if !self.headers.contains(&Header::NoCompression) {
+            self.headers.push(Header::NoCompression);
+        }
+        self
+    }
 
-        //Get 7 line
-        if let Some(internal) = ssol.sync_signal_output_list.get(6) {
-            trace!("Internal state is {:?}", internal);
-            projectors.push(7);
-        };
+    pub fn with_role(mut self, role: CameraRole) -> Self {
+        self.camera_role = role;
+        self
+    }
 
-        let current_version = self.camera_version.lock()?.firmware_verison;
+    // Video inputs are numbered from 1
+    pub fn with_channel(mut self, channel: u32) -> Self {
+        self.channel = channel;
+        self
+    }
 
-        trace!("Current version of hikvision: {:?}", current_version);
-        let default_switch = match current_version {
-            FirmwareVerison::V514 => false,
-            _ => true,
+    async fn send<S>(&self, url: String, settings: S) -> Result<(), IpCamerasError>
+    where
+        S: Serialize + Send + 'static + std::fmt::Debug,
+    {
+        let payload = self.payload(&settings)?;
+
+        self.send_payload(url, payload).await
+    }
+
+    fn payload<S: Serialize>(&self, settings: &S) -> Result<String, IpCamerasError> {
+        let namespace = match &self.xml_namespace {
+            Some(namespace) => namespace.clone(),
+            None => self
+                .camera_version
+                .lock()?
+                .firmware_verison
+                .xml_namespace()
+                .to_string(),
         };
 
-        //Get 7 line
-        if !default_switch {
-            projectors.push(1)
-        }
+        to_hik_xml(settings, Some(&namespace))
+    }
 
-        Ok(projectors)
+    async fn send_payload(&self, url: String, payload: String) -> Result<(), IpCamerasError> {
+        let body =
+            self.invalidate_on_error(self.request(url, Some(payload), Method::PUT, None).await)?;
+
+        self.check_response(&body)
     }
 
-    async fn prepare_hikvision_configuration(
-        &self,
-    ) -> Result<HikvisionConfiguration, IpCamerasError> {
-        let projectors = self.projectors.lock()?.projectors_lines.clone();
+    fn check_response(&self, body: &str) -> Result<(), IpCamerasError> {
+        let response: Response =
+            from_str(body).map_err(|source| IpCamerasError::xml_parse(source, body))?;
 
-        //Get 7 line
-        let internal_projector = projectors.contains(&7);
-        //Get 5 line
-        let external_projector = projectors.contains(&5);
-        let default_switch = !projectors.contains(&1);
+        if response.is_ok() {
+            Ok(())
+        } else {
+            if response.status() == StatusCode::RebootRequired {
+                warn!(
+                    "Hikvision {} requires reboot, init will be repeated",
+                    self.host()
+                );
+                self.initialized.store(false, Relaxed);
+            }
 
-        let (image_channel, streaming_channel) =
-            if let Ok((ic, sc)) = self.retrieve_common_settings().await {
-                (Some(ic), Some(sc))
-            } else {
-                error!("Cannot get image channel and streaming channel on hikvision");
-                (None, None)
-            };
+            let error_code = response.status();
+            let err_msg = response.status_string;
 
-        Ok(HikvisionConfiguration {
-            internal_projector,
-            external_projector,
-            default_switch,
+            error!("Hikvision send reqwest error: {err_msg} with code {error_code:?}");
 
-            image_channel,
-            streaming_channel,
+            match response.error_code {
+                Some(code) => Err(IpCamerasError::Camera(code.into())),
+                None => Err(IpCamerasError::from(ErrorKind::InvalidData)),
+            }
+        }
+    }
 
-            ..Default::default()
+    async fn recieve<D>(&self, url: String) -> Result<D, IpCamerasError>
+    where
+        D: DeserializeOwned,
+    {
+        let body = self.invalidate_on_error(self.request(url, None, Method::GET, None).await)?;
+
+        // A camera that can't answer (busy, moving lens) sends a `ResponseStatus` instead
+        from_str(&body).map_err(|source| match from_str::<Response>(&body) {
+            Ok(Response {
+                error_code: Some(code),
+                ..
+            }) => IpCamerasError::Camera(code.into()),
+            _ => IpCamerasError::xml_parse(source, &body),
         })
     }
 
-    async fn check_is_ptz(&self) -> Result<bool, IpCamerasError> {
-        Ok(self.retrieve_ptz_channel().await.is_ok())
+    // A lost connection or rejected credentials may mean the camera rebooted or was swapped,
+    // so cached state is stale. A busy camera is still the same camera.
+    fn invalidate_on_error<T>(
+        &self,
+        result: Result<T, IpCamerasError>,
+    ) -> Result<T, IpCamerasError> {
+        if let Err(e) = &result {
+            if e.is_connection_failure() {
+                trace!("Hikvision {} connection problem: {e}", self.host());
+                self.initialized.store(false, Relaxed);
+            }
+        }
+
+        result
+    }
+
+    // Some ISAPI endpoints answer JSON (wrapped in the root element name) when asked to.
+    // Fall back to the XML representation if the camera answers something else,
+    // transport errors are returned as is.
+    async fn recieve_json<D>(&self, url: String) -> Result<D, IpCamerasError>
+    where
+        D: DeserializeOwned,
+    {
+        let body = self.invalidate_on_error(
+            self.request(
+                format!("{url}?format=json"),
+                None,
+                Method::GET,
+                Some(vec![Header::AcceptJSON]),
+            )
+            .await,
+        )?;
+
+        match Self::parse_json(&body) {
+            Ok(d) => Ok(d),
+            Err(e) => {
+                trace!("Hikvision JSON response is not available ({e}), fall back to XML");
+                self.recieve(url).await
+            }
+        }
+    }
+
+    // `{"DeviceInfo": {...}}`: the root element name wraps the actual object
+    fn parse_json<D>(body: &str) -> Result<D, IpCamerasError>
+    where
+        D: DeserializeOwned,
+    {
+        let value = match serde_json::from_str::<serde_json::Value>(body)
+            .map_err(|source| IpCamerasError::json_parse(source, body))?
+        {
+            serde_json::Value::Object(map) if map.len() == 1 => {
+                map.into_iter().next().map(|(_, v)| v).unwrap_or_default()
+            }
+            value => value,
+        };
+
+        Ok(serde_json::from_value::<D>(value)?)
+    }
+
+    // FUNCTIONS TO PREPEARE RECIEVE|SEND
+    async fn retrieve_spotlight_settings(&self) -> Result<SPSettings, IpCamerasError> {
+        let host = self.host.clone().unwrap_or_default();
+        let channel = self.channel;
+
+        let ss = match self.camera_role {
+            CameraRole::View => return Ok(SPSettings::default()),
+            _ => {
+                if self.is_ptz.load(Relaxed) {
+                    Ok(self
+                        .recieve::<IrcutFilter>(format!(
+                            "http://{host}/ISAPI/Image/channels/{channel}/ircutFilter"
+                        ))
+                        .await?
+                        .into())
+                } else {
+                    Ok(self
+                        .recieve::<ImageIcrE>(format!(
+                            "http://{host}/ISAPI/Image/channels/{channel}/icr"
+                        ))
+                        .await?
+                        .into())
+                }
+            }
+        };
+
+        trace!(
+            "Projector hik spotlight settings: addr: {}, return: {:?}",
+            self.host(),
+            ss
+        );
+
+        ss
+    }
+
+    // Raw ITC outputs, for flash timing beyond the boolean spotlight toggle
+    pub async fn get_sync_signal_outputs(&self) -> Result<Vec<SyncSignalOutput>, IpCamerasError> {
+        Ok(self
+            .get_raw_projectors_params()
+            .await?
+            .sync_signal_output_list)
+    }
+
+    pub async fn set_sync_signal_outputs(
+        &self,
+        outputs: Vec<SyncSignalOutput>,
+    ) -> Result<(), IpCamerasError> {
+        if !self.can_write() {
+            return Err(IpCamerasError::InsufficientRole);
+        }
+
+        self.send_projectors_settings(
+            self.host(),
+            SyncSignalOutputList {
+                sync_signal_output_list: outputs,
+            },
+        )
+        .await
+    }
+
+    async fn get_raw_projectors_params(&self) -> Result<SyncSignalOutputList, IpCamerasError> {
+        let host = self.host.clone().unwrap_or_default();
+
+        let ssol = match self.camera_role {
+            CameraRole::View => return Err(IpCamerasError::NotAvialiableApi),
+            _ => {
+                self.recieve::<SyncSignalOutputList>(format!(
+                    "http://{host}/ISAPI/ITC/syncSignalOutput"
+                ))
+                .await?
+            }
+        };
+
+        Ok(ssol)
+    }
+
+    async fn send_projectors_settings(
+        &self,
+        host: &str,
+        ps: SyncSignalOutputList,
+    ) -> Result<(), IpCamerasError> {
+        let projector = self
+            .send::<SyncSignalOutputList>(
+                format!("http://{host}/ISAPI/ITC/syncSignalOutput"),
+                ps.into(),
+            )
+            .await;
+
+        trace!(
+            "Switch day|night hik status: addr: {}, return: {:?}",
+            self.host(),
+            projector
+        );
+
+        projector
+    }
+
+    async fn send_icr_settings(&self, host: &str, ss: SPSettings) -> Result<(), IpCamerasError> {
+        let channel = self.channel;
+        let day_and_night = self
+            .send::<ImageIcrE>(
+                format!("http://{host}/ISAPI/Image/channels/{channel}/icr"),
+                ss.into(),
+            )
+            .await;
+        trace!(
+            "Projector hik status: addr: {}, return: {:?}",
+            self.host(),
+            day_and_night
+        );
+
+        day_and_night
+    }
+
+    async fn send_ptz_icr_settings(
+        &self,
+        host: &str,
+        ss: SPSettings,
+    ) -> Result<(), IpCamerasError> {
+        let channel = self.channel;
+        let day_and_night = self
+            .send::<IrcutFilter>(
+                format!("http://{host}/ISAPI/Image/channels/{channel}/ircutFilter"),
+                ss.into(),
+            )
+            .await;
+        trace!(
+            "Projector hik status: addr: {}, return: {:?}",
+            self.host(),
+            day_and_night
+        );
+
+        day_and_night
+    }
+
+    async fn send_spotlight_settings(
+        &self,
+        ss: SPSettings,
+        ps: SyncSignalOutputList,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        match self.camera_role {
+            CameraRole::View => Err(IpCamerasError::InsufficientRole),
+            _ => {
+                if self.is_ptz.load(Relaxed) {
+                    trace!("PTZ switch");
+                    self.send_ptz_icr_settings(host, ss).await
+                } else {
+                    self.send_projectors_settings(host, ps)
+                        .await
+                        .and(self.send_icr_settings(host, ss).await)
+                }
+            }
+        }
+    }
+
+    // Codec, profile and GOP in one read and one PUT of the streaming channel
+    pub async fn set_encoding(
+        &self,
+        codec: VideoEncoding,
+        profile: H264Profile,
+        gop: u32,
+    ) -> Result<(), IpCamerasError> {
+        if codec == VideoEncoding::H265 && profile != H264Profile::Main {
+            return Err(IpCamerasError::InvalidParam(format!(
+                "H.265 only supports the Main profile, got {profile:?}"
+            )));
+        }
+        if !(GOV_LENGTH_MIN..=GOV_LENGTH_MAX).contains(&gop) {
+            return Err(IpCamerasError::InvalidParam(format!(
+                "GOP length must be in {GOV_LENGTH_MIN}..={GOV_LENGTH_MAX}, got {gop}"
+            )));
+        }
+
+        let mut sc = self.retrieve_video_settings().await?;
+        let (h264_profile, h265_profile) = match codec {
+            VideoEncoding::H264 => (Some(profile), sc.video.h265_profile.clone()),
+            VideoEncoding::H265 => (sc.video.h264_profile.clone(), Some(profile)),
+        };
+        if sc.video.video_codec_type == codec
+            && sc.video.h264_profile == h264_profile
+            && sc.video.h265_profile == h265_profile
+            && sc.video.gov_length == Some(gop)
+        {
+            return Ok(());
+        }
+
+        sc.video.video_codec_type = codec;
+        sc.video.h264_profile = h264_profile;
+        sc.video.h265_profile = h265_profile;
+        sc.video.gov_length = Some(gop);
+
+        self.send_video_settings(sc).await
+    }
+
+    // For SVAC (GB/T 25724) deployments, the rest of the video settings are kept
+    pub async fn set_svac_profile(&self, profile: SVACProfile) -> Result<(), IpCamerasError> {
+        let mut sc = self.retrieve_video_settings().await?;
+        if sc.video.svac_profile.as_ref() == Some(&profile) {
+            return Ok(());
+        }
+        sc.video.svac_profile = Some(profile);
+
+        self.send_video_settings(sc).await
+    }
+
+    // Channel ids are `<input><stream>`: 101 is the main stream of input 1, 102 its sub stream
+    pub async fn list_streaming_channels(&self) -> Result<Vec<StreamingChannel>, IpCamerasError> {
+        let host = self.host();
+        let list: StreamingChannelList = self
+            .recieve(format!("http://{host}/ISAPI/Streaming/channels"))
+            .await?;
+
+        Ok(list.streaming_channel)
+    }
+
+    async fn retrieve_video_settings(&self) -> Result<StreamingChannel, IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        self.recieve(format!(
+            "http://{host}/ISAPI/Streaming/channels/{channel}01"
+        ))
+        .await
+    }
+
+    async fn send_video_settings(&self, sc: StreamingChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let payload = with_unknown_fields(self.payload(&sc)?, "Video", &sc.video.extra);
+
+        self.send_payload(
+            format!("http://{host}/ISAPI/Streaming/channels/{channel}01"),
+            payload,
+        )
+        .await
+    }
+
+    // Values of the `opt` attribute of the first `element` in a capabilities document
+    async fn retrieve_options(
+        &self,
+        url: String,
+        element: &str,
+    ) -> Result<Option<Vec<String>>, IpCamerasError> {
+        use xml::reader::{EventReader, XmlEvent};
+
+        let caps = self.invalidate_on_error(self.request(url, None, Method::GET, None).await)?;
+
+        for elem in EventReader::new(caps.as_bytes()).into_iter().flatten() {
+            if let XmlEvent::StartElement {
+                name, attributes, ..
+            } = elem
+            {
+                if name.local_name != element {
+                    continue;
+                }
+
+                return Ok(attributes
+                    .iter()
+                    .find(|a| a.name.local_name == "opt")
+                    .map(|a| a.value.split(',').map(|v| v.trim().to_string()).collect()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn retrieve_supplement_light(&self) -> Result<SupplementLight, IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        self.recieve(format!(
+            "http://{host}/ISAPI/Image/channels/{channel}/supplementLight"
+        ))
+        .await
+    }
+
+    async fn send_supplement_light(&self, sl: SupplementLight) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/supplementLight"),
+            sl,
+        )
+        .await
+    }
+
+    async fn retrieve_transport(&self) -> Result<Transport, IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        self.recieve(format!(
+            "http://{host}/ISAPI/Streaming/channels/{channel}01/transport"
+        ))
+        .await
+    }
+
+    async fn send_transport(&self, transport: Transport) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        self.send(
+            format!("http://{host}/ISAPI/Streaming/channels/{channel}01/transport"),
+            transport,
+        )
+        .await
+    }
+
+    async fn retrieve_version_of_camera(&self) -> Result<DeviceInfo, IpCamerasError> {
+        let host = self.host();
+        self.recieve_json(format!("http://{host}/ISAPI/System/deviceInfo"))
+            .await
+    }
+
+    async fn send_device_info(&self, info: DeviceInfoUpdate) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        self.send(format!("http://{host}/ISAPI/System/deviceInfo"), info)
+            .await
+    }
+
+    // Every image field as the camera reports it, to inspect before patching
+    pub async fn get_image_channel(&self) -> Result<ImageChannel, IpCamerasError> {
+        self.retrieve_image_channel().await
+    }
+
+    async fn retrieve_image_channel(&self) -> Result<ImageChannel, IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        self.recieve(format!("http://{host}/ISAPI/Image/channels/{channel}"))
+            .await
+    }
+
+    // This spaghetti code needs coz hikvision can't process image_channel request.
+    // Faggot
+    async fn send_image_channel(
+        &self,
+        ic: ImageChannel,
+        role: &CameraRole,
+    ) -> Result<(), IpCamerasError> {
+        // PTZ cameras take a different set of endpoints
+        self.ensure_initialized().await?;
+
+        let common_req = Ok(())
+            .and(self.send_image_channel_color(&ic).await)
+            .and(self.send_image_channel_sharpness(&ic).await)
+            .and(self.send_image_channel_gain(&ic).await)
+            .and(self.send_image_channel_shutter(&ic).await);
+
+        match role {
+            CameraRole::Register => common_req
+                .and(self.send_image_channel_white_balance(&ic).await)
+                .and(self.send_image_channel_noise_reduce_ext(&ic).await)
+                .and(self.send_image_channel_gamma_correction(&ic).await)
+                .and(self.send_image_channel_noise_reduce_2d(&ic).await)
+                .and(self.send_image_channel_bright_enhance(&ic).await),
+            CameraRole::View => common_req
+                .and(self.send_image_channel_exposure(&ic).await)
+                .and(self.send_image_channel_hlc(&ic).await)
+                .and(self.send_image_channel_noise_reduce(&ic).await),
+            _ => Ok(()),
+        }
+    }
+
+    // Top-level fields have no endpoint of their own, so the whole channel goes back
+    async fn send_image_channel_whole(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let payload = with_unknown_fields(self.payload(ic)?, "ImageChannel", &ic.extra);
+
+        self.send_payload(
+            format!("http://{host}/ISAPI/Image/channels/{channel}"),
+            payload,
+        )
+        .await
+    }
+
+    async fn send_image_channel_color(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let c = unwrap_some!(
+            ic.color.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/color"),
+            c,
+        )
+        .await
+    }
+
+    async fn send_image_channel_sharpness(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let s = unwrap_some!(
+            ic.sharpness.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/sharpness"),
+            s,
+        )
+        .await
+    }
+
+    async fn send_image_channel_white_balance(
+        &self,
+        ic: &ImageChannel,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let wb = unwrap_some!(
+            ic.white_balance.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/whiteBalance"),
+            wb,
+        )
+        .await
+    }
+
+    // Proportional pan lives in the image channel but only means something on PTZ cameras
+    async fn retrieve_ptz_image_channel(&self) -> Result<ImageChannel, IpCamerasError> {
+        self.ensure_initialized().await?;
+        if !self.is_ptz.load(Relaxed) {
+            return Err(IpCamerasError::NotAvialiableApi);
+        }
+
+        self.retrieve_image_channel().await
+    }
+
+    async fn send_image_channel_proportionalpan(
+        &self,
+        ic: &ImageChannel,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let pp = unwrap_some!(
+            ic.proportionalpan.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/proportionalpan"),
+            pp,
+        )
+        .await
+    }
+
+    async fn send_image_channel_iris(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let iris = unwrap_some!(
+            ic.iris.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/iris"),
+            iris,
+        )
+        .await
+    }
+
+    async fn send_image_channel_temp_range(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let tr = unwrap_some!(
+            ic.temp_range.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/tempRange"),
+            tr,
+        )
+        .await
+    }
+
+    async fn send_image_channel_itc_image_snap(
+        &self,
+        ic: &ImageChannel,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let snap = unwrap_some!(
+            ic.itc_image_snap.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/ITCImageSnap"),
+            snap,
+        )
+        .await
+    }
+
+    async fn send_image_channel_bright_enhance(
+        &self,
+        ic: &ImageChannel,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let be = unwrap_some!(
+            ic.bright_enhance.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/brightEnhance"),
+            be,
+        )
+        .await
+    }
+
+    async fn send_image_channel_shutter(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let s = unwrap_some!(
+            ic.shutter.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/shutter"),
+            s,
+        )
+        .await
+    }
+
+    async fn send_image_channel_noise_reduce_2d(
+        &self,
+        ic: &ImageChannel,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let nrd = unwrap_some!(
+            ic.noise_reduce_2d.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/NoiseReduce2D"),
+            nrd,
+        )
+        .await
+    }
+
+    async fn send_image_channel_gain(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let g = unwrap_some!(
+            ic.gain.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/gain"),
+            g,
+        )
+        .await
+    }
+
+    async fn send_image_channel_gamma_correction(
+        &self,
+        ic: &ImageChannel,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let gc = unwrap_some!(
+            ic.gamma_correction.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/gammaCorrection"),
+            gc,
+        )
+        .await
+    }
+
+    async fn send_image_channel_noise_reduce(
+        &self,
+        ic: &ImageChannel,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let nre = unwrap_some!(
+            ic.noise_reduce.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/noiseReduce"),
+            nre,
+        )
+        .await
+    }
+
+    async fn send_image_channel_hlc(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let nre = unwrap_some!(ic.hlc.clone(), return Err(IpCamerasError::NotAvialiableApi));
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/HLC"),
+            nre,
+        )
+        .await
+    }
+
+    async fn send_image_channel_exposure(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let nre = unwrap_some!(
+            ic.exposure.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/exposure"),
+            nre,
+        )
+        .await
+    }
+
+    async fn send_image_channel_noise_reduce_ext(
+        &self,
+        ic: &ImageChannel,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let nre = unwrap_some!(
+            ic.noise_reduce_ext.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/noiseReduceExt"),
+            nre,
+        )
+        .await
+    }
+
+    async fn send_image_channel_capture_mode(
+        &self,
+        ic: &ImageChannel,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let cm = unwrap_some!(
+            ic.capture_mode.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/captureMode"),
+            cm,
+        )
+        .await
+    }
+
+    async fn send_image_channel_dss(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let dss = unwrap_some!(ic.dss.clone(), return Err(IpCamerasError::NotAvialiableApi));
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/DSS"),
+            dss,
+        )
+        .await
+    }
+
+    async fn send_image_channel_plate_bright(
+        &self,
+        ic: &ImageChannel,
+    ) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let pb = unwrap_some!(
+            ic.plate_bright.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/plateBright"),
+            pb,
+        )
+        .await
+    }
+
+    async fn send_image_channel_jpeg_param(&self, ic: &ImageChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        let jp = unwrap_some!(
+            ic.jpeg_param.clone(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        self.send(
+            format!("http://{host}/ISAPI/Image/channels/{channel}/JPEGParam"),
+            jp,
+        )
+        .await
+    }
+
+    async fn retrieve_time_settings(&self) -> Result<Time, IpCamerasError> {
+        let host = self.host();
+        self.recieve(format!("http://{host}/ISAPI/System/time"))
+            .await
+    }
+
+    #[allow(dead_code)]
+    async fn send_time_settings(&self, ts: (Time, NTPServer)) -> Result<(), IpCamerasError> {
+        let (t, ntp) = ts;
+        let host = self.host();
+
+        self.send(format!("http://{host}/ISAPI/System/time"), t)
+            .await
+            .and(
+                self.send(format!("http://{host}/ISAPI/System/time/ntpServers/1"), ntp)
+                    .await,
+            )
+    }
+
+    async fn retrieve_ptz_channel(&self) -> Result<PTZChannel, IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+
+        self.recieve(format!("http://{host}/ISAPI/PTZCtrl/channels/{channel}"))
+            .await
+    }
+
+    async fn send_ptz_channel(&self, pc: PTZChannel) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+
+        self.send(
+            format!("http://{host}/ISAPI/PTZCtrl/channels/{channel}"),
+            pc,
+        )
+        .await
+    }
+
+    // RS-485 line to an analog PTZ head
+    pub async fn get_ptz_serial_config(&self) -> Result<PTZRs485Para, IpCamerasError> {
+        let pc = self.retrieve_ptz_channel().await?;
+
+        pc.ptz_rs_485_para.ok_or(IpCamerasError::NotAvialiableApi)
+    }
+
+    // The rest of the PTZ channel is sent back as the camera reported it
+    pub async fn set_ptz_serial_config(&self, para: PTZRs485Para) -> Result<(), IpCamerasError> {
+        if !PTZ_BAUD_RATES.contains(&para.baud_rate) {
+            return Err(IpCamerasError::InvalidParam(format!(
+                "unsupported PTZ baud rate {}, expected one of {PTZ_BAUD_RATES:?}",
+                para.baud_rate
+            )));
+        }
+
+        let mut pc = self.retrieve_ptz_channel().await?;
+        let current = unwrap_some!(
+            pc.ptz_rs_485_para.as_mut(),
+            return Err(IpCamerasError::NotAvialiableApi)
+        );
+        if *current == para {
+            return Ok(());
+        }
+        *current = para;
+
+        self.send_ptz_channel(pc).await
+    }
+
+    async fn send_focus_settings(&self, fd: FocusData) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        match self.camera_role {
+            CameraRole::View => {
+                self.send(
+                    format!("http://{host}/ISAPI/System/Video/inputs/channels/{channel}/focus"),
+                    fd,
+                )
+                .await
+            }
+            _ => Err(IpCamerasError::NotAvialiableApi),
+        }
+    }
+
+    async fn retrieve_focus_position(&self) -> Result<FocusData, IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        self.recieve(format!(
+            "http://{host}/ISAPI/System/Video/inputs/channels/{channel}/focus/position"
+        ))
+        .await
+    }
+
+    async fn send_focus_position(&self, fd: FocusData) -> Result<(), IpCamerasError> {
+        let host = self.host();
+        let channel = self.channel;
+        self.send(
+            format!("http://{host}/ISAPI/System/Video/inputs/channels/{channel}/focus/position"),
+            fd,
+        )
+        .await
+    }
+
+    #[allow(dead_code)]
+    async fn default_time_settings(&self) -> Result<(Time, NTPServer), IpCamerasError> {
+        let mut time = self.retrieve_time_settings().await?;
+        time.time_mode = dublicates::TimeMode::NTP;
+
+        let ntp_server = NTPServer {
+            id: 1,
+            addresing_format_type: AddresingFormatType::IPADDRESS,
+            ip_address: Some("172.16.16.10".to_owned()),
+            synchronize_interval: Some(60),
+            port_no: Some(123),
+            host_name: None,
+            ip6_address: None,
+        };
+
+        Ok((time, ntp_server))
+    }
+
+    async fn default_general_settings(
+        &self,
+        role: &CameraRole,
+    ) -> Result<ImageChannel, IpCamerasError> {
+        let mut ic = self.retrieve_image_channel().await?;
+        Self::tune_image_channel(&mut ic, role);
+
+        Ok(ic)
+    }
+
+    // Role defaults a profile starts from, plus its own tweaks on top
+    fn profile_tuning(profile: ImageProfile) -> (CameraRole, fn(&mut ImageChannel)) {
+        match profile {
+            ImageProfile::LicensePlate => (CameraRole::Register, |_| ()),
+            ImageProfile::Default => (CameraRole::View, |_| ()),
+            ImageProfile::LowLight => (CameraRole::View, |ic| {
+                ic.shutter.as_mut().map(|shutter| {
+                    shutter.shutter_level = "1/50".to_string();
+                });
+                ic.gain.as_mut().map(|gain| {
+                    gain.gain_level = 85;
+                });
+                ic.noise_reduce.as_mut().map(|n_r| {
+                    n_r.mode = NoiseReduceMode::GENERAL;
+                    n_r.general_mode = Some(GeneralMode { general_level: 70 });
+                });
+            }),
+        }
+    }
+
+    fn tune_image_channel(ic: &mut ImageChannel, role: &CameraRole) {
+        //Setting default params
+        match role {
+            CameraRole::Register => {
+                ic.color.as_mut().map(|color| {
+                    color.saturation_level = 50;
+                    color.night_mode = Some(true);
+                    color.brightness_level = 75;
+                    color.contrast_level = 18;
+                });
+                ic.sharpness.as_mut().map(|sharpness| {
+                    sharpness.sharpness_level = 60;
+                });
+                ic.white_balance.as_mut().map(|w_b| {
+                    w_b.white_balance_style = WhiteBalanceStyle::AUTO1;
+                });
+                ic.bright_enhance.as_mut().map(|b_e| {
+                    b_e.bright_enhance_level = 70;
+                });
+                ic.plate_bright.as_mut().map(|p_b| {
+                    p_b.plate_bright_enabled = Some(true);
+                    p_b.plate_bright_sensitivity = Some(5);
+                });
+                ic.gamma_correction.as_mut().map(|g_c| {
+                    g_c.gamma_correction_enabled = true;
+                    g_c.gamma_correction_level = 30;
+                });
+                ic.shutter.as_mut().map(|shutter| {
+                    shutter.shutter_level = 2000.to_string();
+                });
+                ic.gain.as_mut().map(|gain| {
+                    gain.gain_level = 30;
+                });
+                ic.noise_reduce_2d.as_mut().map(|nr2d| {
+                    nr2d.noise_reduce_2d_enable = true;
+                    nr2d.noise_reduce_2d_level = 55;
+                });
+                ic.noise_reduce_ext.as_mut().map(|nre| {
+                    nre.mode = NoiseReduceMode::GENERAL;
+                    nre.general_mode.general_level = 100;
+                });
+            }
+            CameraRole::View => {
+                ic.color.as_mut().map(|color| {
+                    color.brightness_level = 62;
+                    color.saturation_level = 50;
+                    color.contrast_level = 44;
+                    color.gray_scale = Some(GrayScale {
+                        gray_scale_mode: GrayScaleMode::OUTDOOR,
+                    });
+                });
+                ic.sharpness.as_mut().map(|sharpness| {
+                    sharpness.sharpness_level = 50;
+                });
+                ic.exposure.as_mut().map(|exposure| {
+                    exposure.exposure_type = ExposureType::AUTO;
+                    exposure.auto_iris_level = Some(100);
+                    exposure.overexpose_suppress = Some(OverexposeSuppress {
+                        enabled: false,
+                        ..Default::default()
+                    });
+                });
+                ic.shutter.as_mut().map(|shutter| {
+                    shutter.shutter_level = "1/500".to_string();
+                });
+                ic.gain.as_mut().map(|gain| {
+                    gain.gain_level = 65;
+                });
+                ic.hlc.as_mut().map(|hlc| {
+                    hlc.enabled = true;
+                    hlc.hlc_level = 50;
+                });
+                ic.noise_reduce.as_mut().map(|n_r| {
+                    n_r.mode = NoiseReduceMode::GENERAL;
+                    n_r.general_mode = Some(GeneralMode { general_level: 51 });
+                });
+            }
+            _ => (),
+        }
+    }
+
+    async fn default_video_settings(
+        &self,
+        role: &CameraRole,
+    ) -> Result<StreamingChannel, IpCamerasError> {
+        let mut sc = self.retrieve_video_settings().await?;
+
+        //Setting default params
+        match role {
+            CameraRole::View => {
+                sc.video.max_frame_rate = 1000;
+                sc.video.video_resolution_width = 2592;
+                sc.video.video_resolution_height = 1944;
+                sc.video.video_quality_control_type = Some("cbr".to_string());
+                sc.video.constant_bit_rate = Some(8192);
+                sc.video.gov_length = Some(10);
+                sc.video.h264_profile = Some(H264Profile::Baseline);
+                sc.video.svc = Some(SVC {
+                    enabled: Some(false),
+                    svc_mode: None,
+                });
+                sc.video.smoothing = Some(1);
+                sc.video.smart_codec = Some(SmartCodec { enabled: false });
+            }
+            CameraRole::Register => {
+                sc.video.max_frame_rate = 1000;
+                sc.video.video_resolution_width = 4096;
+                sc.video.video_resolution_height = 2160;
+                sc.video.svc = Some(SVC {
+                    enabled: None,
+                    svc_mode: Some(SVCMode::CLOSE),
+                });
+                sc.video.gov_length = Some(10);
+            }
+            _ => (),
+        }
+        Ok(sc)
+    }
+
+    async fn retrieve_common_settings(
+        &self,
+    ) -> Result<(ImageChannel, StreamingChannel), IpCamerasError> {
+        Ok((
+            self.retrieve_image_channel().await?,
+            self.retrieve_video_settings().await?,
+        ))
+    }
+
+    async fn retrieve_common_default_settings(
+        &self,
+    ) -> Result<(ImageChannel, StreamingChannel), IpCamerasError> {
+        Ok((
+            self.default_general_settings(&self.camera_role).await?,
+            self.default_video_settings(&self.camera_role).await?,
+        ))
+    }
+
+    async fn send_role_settings<F>(&self, role: &CameraRole, tune: F) -> Result<(), IpCamerasError>
+    where
+        F: FnOnce(&mut ImageChannel) + Send,
+    {
+        let mut ic = self.default_general_settings(role).await?;
+        tune(&mut ic);
+
+        Ok(self
+            .send_video_settings(self.default_video_settings(role).await?)
+            .await
+            .and(self.send_image_channel(ic, role).await)?)
+    }
+
+    async fn prepare_raw_projectors(&self) -> Result<Vec<u8>, IpCamerasError> {
+        let ssol = self.get_raw_projectors_params().await?;
+        let mut projectors = Vec::new();
+
+        // Line ids start at 1
+        let line = |id: u8| {
+            usize::from(id)
+                .checked_sub(1)
+                .and_then(|i| ssol.sync_signal_output_list.get(i))
+        };
+
+        //Get external line
+        if let Some(external) = line(self.external_line) {
+            trace!("External state is {:?}", external);
+            projectors.push(self.external_line)
+        };
+
+        //Get internal line
+        if let Some(internal) = line(self.internal_line) {
+            trace!("Internal state is {:?}", internal);
+            projectors.push(self.internal_line);
+        };
+
+        let current_version = self.camera_version.lock()?.firmware_verison.clone();
+
+        trace!("Current version of hikvision: {:?}", current_version);
+
+        //Get 7 line
+        if !Self::default_switch(&current_version) {
+            projectors.push(1)
+        }
+
+        Ok(projectors)
+    }
+
+    // Unknown firmware keeps the switch like every listed version except V5.1.4,
+    // rather than guessing the legacy line layout
+    fn default_switch(version: &FirmwareVerison) -> bool {
+        !matches!(version, FirmwareVerison::V514)
+    }
+
+    async fn prepare_hikvision_configuration(
+        &self,
+    ) -> Result<HikvisionConfiguration, IpCamerasError> {
+        let projectors = self.projectors.lock()?.projectors_lines.clone();
+
+        let internal_projector = projectors.contains(&self.internal_line);
+        let external_projector = projectors.contains(&self.external_line);
+        let default_switch = !projectors.contains(&1);
+
+        // Each channel is kept on its own, a failure in one doesn't discard the other
+        let image_channel = self
+            .retrieve_image_channel()
+            .await
+            .map_err(|e| error!("Cannot get image channel on hikvision {}: {e}", self.host()))
+            .ok();
+        let streaming_channel = self
+            .retrieve_video_settings()
+            .await
+            .map_err(|e| {
+                error!(
+                    "Cannot get streaming channel on hikvision {}: {e}",
+                    self.host()
+                )
+            })
+            .ok();
+
+        Ok(HikvisionConfiguration {
+            internal_projector,
+            external_projector,
+            default_switch,
+
+            image_channel,
+            streaming_channel,
+
+            ..Default::default()
+        })
+    }
+
+    async fn check_is_ptz(&self) -> Result<bool, IpCamerasError> {
+        Ok(self.retrieve_ptz_channel().await.is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::mock::MockCamera;
+
+    const OK_STATUS: &str =
+        "<ResponseStatus><statusCode>1</statusCode><statusString>OK</statusString></ResponseStatus>";
+
+    fn initialized() -> HikvisionHttp {
+        let camera = HikvisionHttp::default();
+        camera.initialized.store(true, Relaxed);
+        camera
+    }
+
+    // Initialized camera talking to a mock answering `routes`
+    async fn mock_camera(routes: Vec<(&'static str, u16, String)>) -> (HikvisionHttp, MockCamera) {
+        let mock = MockCamera::start(routes).await;
+        let camera = HikvisionHttp {
+            host: Some(mock.host.clone()),
+            ..initialized()
+        };
+
+        (camera, mock)
+    }
+
+    fn image_channel_xml(fields: &str) -> String {
+        format!("<ImageChannel><id>1</id><enabled>true</enabled>{fields}</ImageChannel>")
+    }
+
+    fn put(path: &'static str) -> (&'static str, u16, String) {
+        (path, 200, OK_STATUS.to_string())
+    }
+
+    #[tokio::test]
+    async fn jpeg_params_body() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(
+                    "<JPEGParam><JPEGSize>100</JPEGSize>\
+                     <EXIFInformationEnabled>false</EXIFInformationEnabled></JPEGParam>",
+                ),
+            ),
+            put("PUT /ISAPI/Image/channels/1/JPEGParam"),
+        ])
+        .await;
+
+        camera
+            .set_jpeg_params(Some(300), Some(true), None)
+            .await
+            .unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains("<JPEGSize>300</JPEGSize>"),
+            "{}",
+            bodies[0]
+        );
+        assert!(
+            bodies[0].contains("<EXIFInformationEnabled>true</EXIFInformationEnabled>"),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn image_loss_detection_body() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(
+                    "<enableImageLossDetection>false</enableImageLossDetection>\
+                     <Gain><GainLevel>20</GainLevel></Gain>",
+                ),
+            ),
+            put("PUT /ISAPI/Image/channels/1"),
+        ])
+        .await;
+
+        camera.set_image_loss_detection(true).await.unwrap();
+        // The mock still reports it disabled
+        camera.set_image_loss_detection(false).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        for expected in [
+            "<ImageChannel",
+            "<enableImageLossDetection>true</enableImageLossDetection>",
+            "<GainLevel>20</GainLevel>",
+        ] {
+            assert!(bodies[0].contains(expected), "{}", bodies[0]);
+        }
+    }
+
+    #[tokio::test]
+    async fn car_window_enhancement_body() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(
+                    "<ITCImageSnap><CarWindowEnhancement><enabled>false</enabled>\
+                     <brightenLevel>50</brightenLevel><defogLevel>50</defogLevel>\
+                     </CarWindowEnhancement></ITCImageSnap>",
+                ),
+            ),
+            put("PUT /ISAPI/Image/channels/1/ITCImageSnap"),
+        ])
+        .await;
+
+        camera
+            .set_car_window_enhancement(true, 70, 30)
+            .await
+            .unwrap();
+        // Matches what the mock reports
+        camera
+            .set_car_window_enhancement(false, 50, 50)
+            .await
+            .unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        for expected in [
+            "<enabled>true</enabled>",
+            "<brightenLevel>70</brightenLevel>",
+            "<defogLevel>30</defogLevel>",
+        ] {
+            assert!(bodies[0].contains(expected), "{}", bodies[0]);
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_car_window_level_is_rejected_before_reading() {
+        let (camera, mock) = mock_camera(vec![]).await;
+
+        let result = camera
+            .set_car_window_enhancement(true, 50, CAR_WINDOW_LEVEL_MAX + 1)
+            .await;
+
+        assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    async fn merge_jpeg_camera(double_shut: bool) -> (HikvisionHttp, MockCamera) {
+        mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(&format!(
+                    "<ImageMultishut><doubleShutEnable>{double_shut}</doubleShutEnable>\
+                     <codecType>JPEG</codecType></ImageMultishut>\
+                     <JPEGParam><JPEGSize>100</JPEGSize></JPEGParam>"
+                )),
+            ),
+            put("PUT /ISAPI/Image/channels/1/JPEGParam"),
+        ])
+        .await
+    }
+
+    #[tokio::test]
+    async fn merged_jpeg_size_with_double_shutter() {
+        let (camera, mock) = merge_jpeg_camera(true).await;
+
+        camera.set_jpeg_params(None, None, Some(400)).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains("<MergeJPEGSize>400</MergeJPEGSize>"),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn merged_jpeg_size_needs_double_shutter() {
+        let (camera, mock) = merge_jpeg_camera(false).await;
+
+        let result = camera.set_jpeg_params(None, None, Some(400)).await;
+
+        assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        assert!(mock.bodies("PUT").is_empty());
+
+        // Without a merged size the single shutter is fine
+        camera.set_jpeg_params(Some(200), None, None).await.unwrap();
+        assert_eq!(mock.bodies("PUT").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_jpeg_size_is_rejected_before_reading() {
+        let (camera, mock) = mock_camera(vec![]).await;
+
+        let result = camera.set_jpeg_params(Some(0), None, None).await;
+
+        assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn overexpose_suppress_body() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml("<Exposure><ExposureType>auto</ExposureType></Exposure>"),
+            ),
+            put("PUT /ISAPI/Image/channels/1/exposure"),
+        ])
+        .await;
+
+        camera
+            .set_overexpose_suppress(true, Some(40))
+            .await
+            .unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        for expected in [
+            "<OverexposeSuppress><enabled>true</enabled>",
+            "MANUAL",
+            "<DistanceLevel>40</DistanceLevel>",
+        ] {
+            assert!(bodies[0].contains(expected), "{}", bodies[0]);
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_distance_level_is_rejected_before_reading() {
+        let (camera, mock) = mock_camera(vec![]).await;
+
+        let result = camera.set_overexpose_suppress(true, Some(101)).await;
+
+        assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    // Exposure with face exposure enabled at sensitivity 30
+    async fn face_exposure_camera() -> (HikvisionHttp, MockCamera) {
+        mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(
+                    "<Exposure><ExposureType>auto</ExposureType><faceExposure>\
+                     <enabled>true</enabled><sensitivity>30</sensitivity>\
+                     </faceExposure></Exposure>",
+                ),
+            ),
+            put("PUT /ISAPI/Image/channels/1/exposure"),
+        ])
+        .await
+    }
+
+    #[tokio::test]
+    async fn face_exposure_body_with_sensitivity() {
+        let (camera, mock) = face_exposure_camera().await;
+
+        camera.set_face_exposure(true, Some(60)).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains(
+                "<faceExposure><enabled>true</enabled><sensitivity>60</sensitivity></faceExposure>"
+            ),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn face_exposure_body_keeps_sensitivity() {
+        let (camera, mock) = face_exposure_camera().await;
+
+        camera.set_face_exposure(false, None).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains(
+                "<faceExposure><enabled>false</enabled><sensitivity>30</sensitivity></faceExposure>"
+            ),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_face_sensitivity_is_rejected_before_reading() {
+        let (camera, mock) = mock_camera(vec![]).await;
+
+        let result = camera.set_face_exposure(true, Some(-1)).await;
+
+        assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn capture_mode_body() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1/captureMode/capabilities",
+                200,
+                r#"<CaptureMode><mode opt="auto,1920*1080@25fps">auto</mode></CaptureMode>"#
+                    .to_string(),
+            ),
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml("<CaptureMode><mode>auto</mode></CaptureMode>"),
+            ),
+            put("PUT /ISAPI/Image/channels/1/captureMode"),
+        ])
+        .await;
+
+        assert_eq!(camera.get_capture_mode().await.unwrap(), "auto");
+
+        camera
+            .set_capture_mode("1920*1080@25fps".to_string())
+            .await
+            .unwrap();
+        assert!(matches!(
+            camera.set_capture_mode("640*480@60fps".to_string()).await,
+            Err(IpCamerasError::InvalidParam(_))
+        ));
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains("<mode>1920*1080@25fps</mode>"),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn capture_mode_without_capabilities_is_sent_as_is() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1/captureMode/capabilities",
+                404,
+                String::new(),
+            ),
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml("<CaptureMode><mode>auto</mode></CaptureMode>"),
+            ),
+            put("PUT /ISAPI/Image/channels/1/captureMode"),
+        ])
+        .await;
+
+        camera.set_capture_mode("custom".to_string()).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].contains("<mode>custom</mode>"), "{}", bodies[0]);
+    }
+
+    #[tokio::test]
+    async fn dss_body() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml("<DSS><enabled>false</enabled><DSSLevel>2</DSSLevel></DSS>"),
+            ),
+            put("PUT /ISAPI/Image/channels/1/DSS"),
+        ])
+        .await;
+
+        camera.set_dss(true, Some("8".to_string())).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains("<enabled>true</enabled><DSSLevel>8</DSSLevel>"),
+            "{}",
+            bodies[0]
+        );
+
+        assert!(matches!(
+            camera.set_dss(true, Some("5".to_string())).await,
+            Err(IpCamerasError::InvalidParam(_))
+        ));
+        assert_eq!(mock.bodies("PUT").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unchanged_dss_is_not_written() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml("<DSS><enabled>true</enabled><DSSLevel>2</DSSLevel></DSS>"),
+            ),
+            put("PUT /ISAPI/Image/channels/1/DSS"),
+        ])
+        .await;
+
+        camera.set_dss(true, Some("2".to_string())).await.unwrap();
+        camera.set_dss(true, None).await.unwrap();
+
+        assert!(mock.bodies("PUT").is_empty());
+    }
+
+    #[test]
+    fn fluent_builders_set_fields() {
+        let camera = HikvisionHttp::default()
+            .with_timeout(3)
+            .with_credentials(Some("operator".to_string()), Some("secret".to_string()))
+            .with_role(CameraRole::Register)
+            .with_channel(2);
+
+        assert_eq!(camera.timeout, 3);
+        assert_eq!(camera.username.as_deref(), Some("operator"));
+        assert_eq!(camera.password.as_deref(), Some("secret"));
+        assert!(matches!(camera.camera_role, CameraRole::Register));
+        assert_eq!(camera.channel, 2);
+        assert_eq!(HikvisionHttp::default().channel, DEFAULT_CHANNEL);
+    }
+
+    #[tokio::test]
+    async fn channel_picks_the_endpoints() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/2",
+                200,
+                image_channel_xml("<DSS><enabled>false</enabled><DSSLevel>2</DSSLevel></DSS>"),
+            ),
+            put("PUT /ISAPI/Image/channels/2/DSS"),
+        ])
+        .await;
+        let camera = camera.with_channel(2);
+
+        camera.set_dss(true, None).await.unwrap();
+
+        let paths: Vec<String> = mock.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(
+            paths,
+            ["/ISAPI/Image/channels/2", "/ISAPI/Image/channels/2/DSS"],
+        );
+    }
+
+    #[tokio::test]
+    async fn plate_bright_body() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(
+                    "<PlateBright><plateBrightEnabled>false</plateBrightEnabled>\
+                     <plateBrightSensitivity>50</plateBrightSensitivity>\
+                     <correctFactorEnabled>false</correctFactorEnabled>\
+                     <correctFactor>50</correctFactor></PlateBright>",
+                ),
+            ),
+            put("PUT /ISAPI/Image/channels/1/plateBright"),
+        ])
+        .await;
+
+        camera
+            .set_plate_bright(true, Some(70), Some(30))
+            .await
+            .unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        for expected in [
+            "<plateBrightEnabled>true</plateBrightEnabled>",
+            "<plateBrightSensitivity>70</plateBrightSensitivity>",
+            "<correctFactorEnabled>true</correctFactorEnabled>",
+            "<correctFactor>30</correctFactor>",
+        ] {
+            assert!(bodies[0].contains(expected), "{}", bodies[0]);
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_plate_bright_correction_is_rejected_before_reading() {
+        let (camera, mock) = mock_camera(vec![]).await;
+
+        let result = camera.set_plate_bright(true, None, Some(150)).await;
+
+        assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn whole_image_channel_is_returned() {
+        let (camera, _mock) = mock_camera(vec![(
+            "GET /ISAPI/Image/channels/1",
+            200,
+            image_channel_xml(
+                "<videoInputID>1</videoInputID>\
+                 <ImageFlip><enabled>true</enabled></ImageFlip>\
+                 <Color><brightnessLevel>40</brightnessLevel><contrastLevel>55</contrastLevel>\
+                 <saturationLevel>60</saturationLevel></Color>\
+                 <Sharpness><SharpnessLevel>70</SharpnessLevel></Sharpness>\
+                 <Gain><GainLevel>20</GainLevel></Gain>\
+                 <Shutter><ShutterLevel>1/100</ShutterLevel></Shutter>\
+                 <BrightEnhance><brightEnhanceLevel>30</brightEnhanceLevel></BrightEnhance>",
+            ),
+        )])
+        .await;
+
+        let ic = camera.get_image_channel().await.unwrap();
+
+        assert_eq!(ic.id, 1);
+        assert_eq!(ic.video_input_id, Some(1));
+        assert_eq!(ic.image_flip.map(|f| f.enabled), Some(true));
+        let color = ic.color.unwrap();
+        assert_eq!(
+            (
+                color.brightness_level,
+                color.contrast_level,
+                color.saturation_level
+            ),
+            (40, 55, 60)
+        );
+        assert_eq!(ic.sharpness.map(|s| s.sharpness_level), Some(70));
+        assert_eq!(ic.gain.map(|g| g.gain_level), Some(20));
+        assert_eq!(
+            ic.shutter.map(|s| s.shutter_level).as_deref(),
+            Some("1/100")
+        );
+        assert_eq!(ic.bright_enhance.map(|b| b.bright_enhance_level), Some(30));
+        assert_eq!(ic.dss, None);
+    }
+
+    async fn ptz_camera(fields: &str) -> (HikvisionHttp, MockCamera) {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(fields),
+            ),
+            put("PUT /ISAPI/Image/channels/1/proportionalpan"),
+        ])
+        .await;
+        camera.is_ptz.store(true, Relaxed);
+
+        (camera, mock)
+    }
+
+    #[tokio::test]
+    async fn proportional_pan_body() {
+        let (camera, mock) =
+            ptz_camera("<proportionalpan><enabled>false</enabled></proportionalpan>").await;
+
+        assert!(!camera.get_proportional_pan().await.unwrap());
+        camera.set_proportional_pan(true).await.unwrap();
+        // The mock still reports it disabled, nothing to write
+        camera.set_proportional_pan(false).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains("<enabled>true</enabled>"),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_proportional_pan_is_added() {
+        let (camera, mock) = ptz_camera("").await;
+
+        camera.set_proportional_pan(false).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains("<enabled>false</enabled>"),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn proportional_pan_needs_ptz() {
+        let (camera, mock) = mock_camera(vec![]).await;
+
+        let result = camera.set_proportional_pan(true).await;
+
+        assert!(matches!(result, Err(IpCamerasError::NotAvialiableApi)));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn configured_namespace_is_sent() {
+        let (camera, mock) = mock_camera(bright_enhance_camera_routes()).await;
+        let camera = HikvisionHttp {
+            xml_namespace: Some("urn:custom".to_string()),
+            ..camera
+        };
+
+        camera.set_bright_enhance(70).await.unwrap();
+
+        let body = &mock.bodies("PUT")[0];
+        assert!(body.starts_with(XML_DECLARATION), "{body}");
+        assert!(
+            body.contains(r#"<BrightEnhance xmlns="urn:custom">"#),
+            "{body}"
+        );
+    }
+
+    #[tokio::test]
+    async fn gray_scale_body() {
+        for (mode, value) in [
+            (GrayScaleMode::INDOOR, "indoor"),
+            (GrayScaleMode::OUTDOOR, "outdoor"),
+        ] {
+            let (camera, mock) = mock_camera(vec![
+                (
+                    "GET /ISAPI/Image/channels/1",
+                    200,
+                    image_channel_xml(
+                        "<Color><brightnessLevel>50</brightnessLevel>\
+                         <contrastLevel>50</contrastLevel>\
+                         <saturationLevel>50</saturationLevel></Color>",
+                    ),
+                ),
+                put("PUT /ISAPI/Image/channels/1/color"),
+            ])
+            .await;
+
+            camera.set_gray_scale(mode).await.unwrap();
+
+            let bodies = mock.bodies("PUT");
+            assert_eq!(bodies.len(), 1);
+            let expected = format!("<grayScaleMode>{value}</grayScaleMode>");
+            assert!(bodies[0].contains(&expected), "{}", bodies[0]);
+            assert!(
+                bodies[0].contains("<brightnessLevel>50</brightnessLevel>"),
+                "{}",
+                bodies[0]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unchanged_gray_scale_is_not_written() {
+        let (camera, mock) = mock_camera(vec![(
+            "GET /ISAPI/Image/channels/1",
+            200,
+            image_channel_xml(
+                "<Color><brightnessLevel>50</brightnessLevel><contrastLevel>50</contrastLevel>\
+                 <saturationLevel>50</saturationLevel>\
+                 <grayScale><grayScaleMode>outdoor</grayScaleMode></grayScale></Color>",
+            ),
+        )])
+        .await;
+
+        camera.set_gray_scale(GrayScaleMode::OUTDOOR).await.unwrap();
+
+        assert!(mock.bodies("PUT").is_empty());
+    }
+
+    #[tokio::test]
+    async fn temp_range_bodies() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(
+                    "<TempRange><mode>manual</mode><temperatureUpperLimit>50</temperatureUpperLimit>\
+                     <temperatureLowerLimit>0</temperatureLowerLimit></TempRange>",
+                ),
+            ),
+            put("PUT /ISAPI/Image/channels/1/tempRange"),
+        ])
+        .await;
+
+        camera
+            .set_temp_range(TempRangeMode::AUTOMATIC, None, None)
+            .await
+            .unwrap();
+        camera
+            .set_temp_range(TempRangeMode::MANUAL, Some(80), Some(-20))
+            .await
+            .unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 2);
+        assert!(
+            bodies[0].contains("<mode>automatic</mode>"),
+            "{}",
+            bodies[0]
+        );
+        assert!(!bodies[0].contains("Limit>"), "{}", bodies[0]);
+        for expected in [
+            "<mode>manual</mode>",
+            "<temperatureUpperLimit>80</temperatureUpperLimit>",
+            "<temperatureLowerLimit>-20</temperatureLowerLimit>",
+        ] {
+            assert!(bodies[1].contains(expected), "{}", bodies[1]);
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_temp_range_is_rejected_before_reading() {
+        let (camera, mock) = mock_camera(vec![]).await;
+
+        for (mode, upper, lower) in [
+            (TempRangeMode::MANUAL, Some(10), Some(20)),
+            (TempRangeMode::MANUAL, Some(10), None),
+            (TempRangeMode::AUTOMATIC, None, Some(0)),
+        ] {
+            let result = camera.set_temp_range(mode, upper, lower).await;
+            assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        }
+        assert!(mock.requests().is_empty());
+    }
+
+    fn bright_enhance_camera_routes() -> Vec<(&'static str, u16, String)> {
+        vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(
+                    "<BrightEnhance><brightEnhanceLevel>50</brightEnhanceLevel></BrightEnhance>",
+                ),
+            ),
+            put("PUT /ISAPI/Image/channels/1/brightEnhance"),
+        ]
+    }
+
+    #[tokio::test]
+    async fn bright_enhance_body() {
+        let (camera, mock) = mock_camera(bright_enhance_camera_routes()).await;
+
+        assert_eq!(camera.get_bright_enhance().await.unwrap(), 50);
+        camera.set_bright_enhance(70).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains("<brightEnhanceLevel>70</brightEnhanceLevel>"),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn unchanged_bright_enhance_is_not_written() {
+        let (camera, mock) = mock_camera(bright_enhance_camera_routes()).await;
+
+        camera.set_bright_enhance(50).await.unwrap();
+
+        assert!(mock.bodies("PUT").is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalid_bright_enhance_is_rejected_before_reading() {
+        let (camera, mock) = mock_camera(vec![]).await;
+
+        let result = camera
+            .set_bright_enhance(BRIGHT_ENHANCE_LEVEL_MAX + 1)
+            .await;
+
+        assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    #[test]
+    fn device_status_is_parsed() {
+        let status: DeviceStatus = from_str(
+            "<DeviceStatus><currentDeviceTime>2024-03-01T10:00:00+03:00</currentDeviceTime>\
+             <deviceUpTime>86400</deviceUpTime>\
+             <CPUList><CPU><cpuDescription>ARM</cpuDescription><cpuUtilization>20</cpuUtilization></CPU>\
+             <CPU><cpuDescription>ARM</cpuDescription><cpuUtilization>40</cpuUtilization></CPU></CPUList>\
+             <MemoryList><Memory><memoryDescription>DDR</memoryDescription>\
+             <memoryUsage>256</memoryUsage><memoryAvailable>768</memoryAvailable></Memory>\
+             </MemoryList></DeviceStatus>",
+        )
+        .unwrap();
+
+        assert_eq!(
+            SystemStatus::from(status),
+            SystemStatus {
+                uptime: Some(86400),
+                cpu_usage: Some(30.),
+                memory_usage: Some(25.),
+                current_time: Some("2024-03-01T10:00:00+03:00".to_string()),
+            }
+        );
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Firmware {
+        #[serde(rename = "firmwareVersion")]
+        firmware_version: FirmwareVerison,
+    }
+
+    #[test]
+    fn unlisted_firmware_is_kept() {
+        let firmware: Firmware =
+            from_str("<DeviceInfo><firmwareVersion>V5.6.2</firmwareVersion></DeviceInfo>").unwrap();
+        assert_eq!(
+            firmware.firmware_version,
+            FirmwareVerison::Other("V5.6.2".to_string())
+        );
+
+        let firmware: Firmware =
+            from_str("<DeviceInfo><firmwareVersion>V5.1.4</firmwareVersion></DeviceInfo>").unwrap();
+        assert_eq!(firmware.firmware_version, FirmwareVerison::V514);
+    }
+
+    #[test]
+    fn unlisted_firmware_keeps_default_switch() {
+        assert!(HikvisionHttp::default_switch(&FirmwareVerison::Other(
+            "V5.6.2".to_string()
+        )));
+        assert!(HikvisionHttp::default_switch(&FirmwareVerison::V55820));
+        assert!(!HikvisionHttp::default_switch(&FirmwareVerison::V514));
+    }
+
+    #[test]
+    fn busy_camera_keeps_init_state() {
+        let camera = initialized();
+        let result: Result<(), _> =
+            camera.invalidate_on_error(Err(IpCamerasError::Camera(ErrorCode::DeviceBusy)));
+
+        assert!(result.is_err());
+        assert!(camera.initialized.load(Relaxed));
+    }
+
+    #[test]
+    fn lost_connection_invalidates_init_state() {
+        let camera = initialized();
+        let _ = camera.invalidate_on_error::<()>(Err(ErrorKind::ConnectionReset.into()));
+        assert!(!camera.initialized.load(Relaxed));
+
+        let camera = initialized();
+        let _ = camera.invalidate_on_error::<()>(Err(IpCamerasError::Timeout));
+        assert!(!camera.initialized.load(Relaxed));
+    }
+
+    fn image_channel() -> ImageChannel {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "enabled": true,
+            "Color": {
+                "brightnessLevel": 50,
+                "contrastLevel": 50,
+                "saturationLevel": 50,
+            },
+            "Sharpness": { "SharpnessLevel": 50 },
+            "Gain": { "GainLevel": 50 },
+            "Shutter": { "ShutterLevel": "1/25" },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn license_plate_profile_matches_register_defaults() {
+        let (role, tune) = HikvisionHttp::profile_tuning(ImageProfile::LicensePlate);
+        assert!(matches!(role, CameraRole::Register));
+
+        let mut profiled = image_channel();
+        HikvisionHttp::tune_image_channel(&mut profiled, &role);
+        tune(&mut profiled);
+
+        let mut register = image_channel();
+        HikvisionHttp::tune_image_channel(&mut register, &CameraRole::Register);
+
+        assert_eq!(profiled, register);
+        assert_eq!(profiled.color.as_ref().unwrap().brightness_level, 75);
+        assert_eq!(profiled.shutter.as_ref().unwrap().shutter_level, "2000");
+    }
+
+    #[test]
+    fn low_light_profile_tunes_view_defaults() {
+        let (role, tune) = HikvisionHttp::profile_tuning(ImageProfile::LowLight);
+        assert!(matches!(role, CameraRole::View));
+
+        let mut ic = image_channel();
+        HikvisionHttp::tune_image_channel(&mut ic, &role);
+        tune(&mut ic);
+
+        assert_eq!(ic.shutter.as_ref().unwrap().shutter_level, "1/50");
+        assert_eq!(ic.gain.as_ref().unwrap().gain_level, 85);
+        assert_eq!(ic.color.as_ref().unwrap().brightness_level, 62);
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Time {
+        time_mode: String,
+        time_zone: String,
+    }
+
+    #[test]
+    fn json_root_element_is_unwrapped() {
+        let expected = Time {
+            time_mode: "NTP".to_string(),
+            time_zone: "CST-3:00:00".to_string(),
+        };
+
+        let wrapped = r#"{"Time": {"timeMode": "NTP", "timeZone": "CST-3:00:00"}}"#;
+        assert_eq!(
+            HikvisionHttp::parse_json::<Time>(wrapped).unwrap(),
+            expected
+        );
+
+        let bare = r#"{"timeMode": "NTP", "timeZone": "CST-3:00:00"}"#;
+        assert_eq!(HikvisionHttp::parse_json::<Time>(bare).unwrap(), expected);
+    }
+
+    #[test]
+    fn xml_answer_is_not_json() {
+        let result = HikvisionHttp::parse_json::<Time>("<Time><timeMode>NTP</timeMode></Time>");
+
+        assert!(matches!(result, Err(IpCamerasError::JsonParse { .. })));
+    }
+
+    #[test]
+    fn success_keeps_init_state() {
+        let camera = initialized();
+        assert_eq!(camera.invalidate_on_error(Ok(5)).unwrap(), 5);
+        assert!(camera.initialized.load(Relaxed));
+    }
+
+    const TRANSPORT: &str =
+        "<Transport><rtspPortNo>8554</rtspPortNo><maxPacketSize>1000</maxPacketSize>\
+        <Unicast><enabled>true</enabled><rtpTransportType>RTP/UDP</rtpTransportType></Unicast>\
+        <Multicast><enabled>true</enabled><destIPAddress>239.1.1.1</destIPAddress>\
+        <videoDestPortNo>8860</videoDestPortNo></Multicast></Transport>";
+
+    #[test]
+    fn transport_document_is_parsed() {
+        let transport: Transport = from_str(TRANSPORT).unwrap();
+
+        assert_eq!(
+            TransportInfo::from(transport),
+            TransportInfo {
+                rtsp_port: 8554,
+                protocol: StreamProtocol::Multicast,
+                multicast: Some(MulticastConfig {
+                    address: "239.1.1.1".to_string(),
+                    port: 8860,
+                }),
+            }
+        );
+
+        let unicast: Transport = from_str(
+            "<Transport><rtspPortNo>554</rtspPortNo><maxPacketSize>1000</maxPacketSize>\
+             <Unicast><enabled>true</enabled><rtpTransportType>RTP/UDP</rtpTransportType></Unicast>\
+             </Transport>",
+        )
+        .unwrap();
+        assert_eq!(TransportInfo::from(unicast).protocol, StreamProtocol::Udp);
+    }
+
+    #[tokio::test]
+    async fn forcing_tcp_disables_multicast() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Streaming/channels/101/transport",
+                200,
+                TRANSPORT.to_string(),
+            ),
+            put("PUT /ISAPI/Streaming/channels/101/transport"),
+        ])
+        .await;
+
+        camera.set_transport(true, None).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].contains("RTP/TCP"), "{}", bodies[0]);
+        assert!(!bodies[0].contains("RTP/UDP"), "{}", bodies[0]);
+        assert!(
+            bodies[0].contains("<Multicast><enabled>false</enabled>"),
+            "{}",
+            bodies[0]
+        );
+        assert!(
+            bodies[0].contains("<rtspPortNo>8554</rtspPortNo>"),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn rtsp_port_is_read_from_transport() {
+        let (camera, _mock) = mock_camera(vec![(
+            "GET /ISAPI/Streaming/channels/101/transport",
+            200,
+            TRANSPORT.to_string(),
+        )])
+        .await;
+
+        assert_eq!(camera.get_rtsp_port().await.unwrap(), 8554);
+    }
+
+    #[tokio::test]
+    async fn rtsp_port_body() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Streaming/channels/101/transport",
+                200,
+                TRANSPORT.to_string(),
+            ),
+            put("PUT /ISAPI/Streaming/channels/101/transport"),
+        ])
+        .await;
+
+        // Already set, nothing to send
+        camera.set_rtsp_port(8554).await.unwrap();
+        assert!(mock.bodies("PUT").is_empty());
+
+        camera.set_rtsp_port(1554).await.unwrap();
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains("<rtspPortNo>1554</rtspPortNo>"),
+            "{}",
+            bodies[0]
+        );
+
+        assert!(matches!(
+            camera.set_rtsp_port(70000).await,
+            Err(IpCamerasError::InvalidParam(_))
+        ));
+        assert_eq!(mock.bodies("PUT").len(), 1);
+    }
+
+    async fn supplement_light_camera() -> (HikvisionHttp, MockCamera) {
+        mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1/supplementLight",
+                200,
+                "<SupplementLight><supplementLightMode>irLight</supplementLightMode>\
+                 <mixedLightBrightnessRegulatMode>manual</mixedLightBrightnessRegulatMode>\
+                 <irLightBrightness>50</irLightBrightness></SupplementLight>"
+                    .to_string(),
+            ),
+            put("PUT /ISAPI/Image/channels/1/supplementLight"),
+        ])
+        .await
+    }
+
+    #[tokio::test]
+    async fn auto_supplement_light_body() {
+        let (camera, mock) = supplement_light_camera().await;
+
+        camera
+            .set_supplement_light(SupplementMode::Auto, None)
+            .await
+            .unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].contains("auto"), "{}", bodies[0]);
+        assert!(!bodies[0].contains("manual"), "{}", bodies[0]);
+        assert!(
+            bodies[0].contains("<irLightBrightness>50</irLightBrightness>"),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn manual_supplement_light_body() {
+        let (camera, mock) = supplement_light_camera().await;
+
+        camera
+            .set_supplement_light(SupplementMode::Manual, Some(80))
+            .await
+            .unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].contains("manual"), "{}", bodies[0]);
+        assert!(
+            bodies[0].contains("<irLightBrightness>80</irLightBrightness>"),
+            "{}",
+            bodies[0]
+        );
+        // The camera has no white light, it is not added
+        assert!(
+            !bodies[0].contains("80</whiteLightBrightness>"),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn brightness_without_lights_is_not_available() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1/supplementLight",
+                200,
+                "<SupplementLight><supplementLightMode>close</supplementLightMode>\
+                 </SupplementLight>"
+                    .to_string(),
+            ),
+            put("PUT /ISAPI/Image/channels/1/supplementLight"),
+        ])
+        .await;
+
+        assert!(matches!(
+            camera
+                .set_supplement_light(SupplementMode::Manual, Some(80))
+                .await,
+            Err(IpCamerasError::NotAvialiableApi)
+        ));
+        assert!(mock.bodies("PUT").is_empty());
+    }
+
+    #[tokio::test]
+    async fn supplement_light_brightness_follows_the_mode() {
+        let (camera, mock) = mock_camera(vec![]).await;
+
+        for (mode, brightness) in [
+            (SupplementMode::Manual, None),
+            (SupplementMode::Auto, Some(50)),
+            (SupplementMode::Manual, Some(101)),
+        ] {
+            assert!(matches!(
+                camera.set_supplement_light(mode, brightness).await,
+                Err(IpCamerasError::InvalidParam(_))
+            ));
+        }
+        assert!(mock.requests().is_empty());
+    }
+
+    fn streaming_channel_xml(fields: &str) -> String {
+        format!(
+            "<StreamingChannel><id>101</id><channelName>Camera 01</channelName>\
+             <enabled>true</enabled><Video><enabled>true</enabled>\
+             <videoInputChannelID>1</videoInputChannelID><videoCodecType>H.264</videoCodecType>\
+             <videoResolutionWidth>1920</videoResolutionWidth>\
+             <videoResolutionHeight>1080</videoResolutionHeight><fixedQuality>60</fixedQuality>\
+             <maxFrameRate>2500</maxFrameRate>{fields}</Video></StreamingChannel>"
+        )
+    }
+
+    #[tokio::test]
+    async fn image_channel_survives_a_streaming_failure() {
+        let (camera, _mock) = mock_camera(vec![
+            ("GET /ISAPI/Image/channels/1", 200, image_channel_xml("")),
+            ("GET /ISAPI/Streaming/channels/101", 500, String::new()),
+        ])
+        .await;
+
+        let hikvision = camera
+            .get_additional_configuration()
+            .await
+            .unwrap()
+            .hikvision
+            .unwrap();
+
+        assert_eq!(hikvision.image_channel.map(|ic| ic.id), Some(1));
+        assert!(hikvision.streaming_channel.is_none());
+    }
+
+    #[tokio::test]
+    async fn streaming_channel_survives_an_image_failure() {
+        let (camera, _mock) = mock_camera(vec![
+            ("GET /ISAPI/Image/channels/1", 500, String::new()),
+            (
+                "GET /ISAPI/Streaming/channels/101",
+                200,
+                streaming_channel_xml(""),
+            ),
+        ])
+        .await;
+
+        let hikvision = camera
+            .get_additional_configuration()
+            .await
+            .unwrap()
+            .hikvision
+            .unwrap();
+
+        assert!(hikvision.image_channel.is_none());
+        assert_eq!(hikvision.streaming_channel.map(|sc| sc.id), Some(101));
+    }
+
+    #[tokio::test]
+    async fn register_role_defaults_are_applied() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(
+                    "<Color><brightnessLevel>50</brightnessLevel><contrastLevel>50</contrastLevel>\
+                     <saturationLevel>50</saturationLevel></Color>\
+                     <Sharpness><SharpnessLevel>50</SharpnessLevel></Sharpness>\
+                     <Gain><GainLevel>50</GainLevel></Gain>\
+                     <Shutter><ShutterLevel>1/25</ShutterLevel></Shutter>\
+                     <WhiteBalance><WhiteBalanceStyle>auto</WhiteBalanceStyle></WhiteBalance>\
+                     <NoiseReduceExt><mode>close</mode>\
+                     <GeneralMode><generalLevel>50</generalLevel></GeneralMode>\
+                     <AdvancedMode><spatialLevel>50</spatialLevel>\
+                     <temporalLevel>50</temporalLevel></AdvancedMode></NoiseReduceExt>\
+                     <gammaCorrection><gammaCorrectionEnabled>false</gammaCorrectionEnabled>\
+                     <gammaCorrectionLevel>50</gammaCorrectionLevel></gammaCorrection>\
+                     <NoiseReduce2D><noiseReduce2DEnable>false</noiseReduce2DEnable>\
+                     <noiseReduce2DLevel>50</noiseReduce2DLevel></NoiseReduce2D>\
+                     <BrightEnhance><brightEnhanceLevel>50</brightEnhanceLevel></BrightEnhance>",
+                ),
+            ),
+            (
+                "GET /ISAPI/Streaming/channels/101",
+                200,
+                streaming_channel_xml(""),
+            ),
+            put("PUT /ISAPI/Image/channels/1/"),
+            put("PUT /ISAPI/Streaming/channels/101"),
+        ])
+        .await;
+        let camera = HikvisionHttp {
+            camera_role: CameraRole::Register,
+            ..camera
+        };
+
+        camera.apply_role_defaults().await.unwrap();
+
+        let put_body = |path: &str| {
+            mock.requests()
+                .into_iter()
+                .find(|r| r.method == "PUT" && r.path == path)
+                .map(|r| r.body)
+                .unwrap_or_else(|| panic!("no PUT to {path}"))
+        };
+        let color = put_body("/ISAPI/Image/channels/1/color");
+        assert!(
+            color.contains("<brightnessLevel>75</brightnessLevel>"),
+            "{color}"
+        );
+        let bright_enhance = put_body("/ISAPI/Image/channels/1/brightEnhance");
+        assert!(
+            bright_enhance.contains("<brightEnhanceLevel>70</brightEnhanceLevel>"),
+            "{bright_enhance}"
+        );
+        let video = put_body("/ISAPI/Streaming/channels/101");
+        assert!(
+            video.contains("<videoResolutionWidth>4096</videoResolutionWidth>"),
+            "{video}"
+        );
+        assert!(
+            video.contains("<videoResolutionHeight>2160</videoResolutionHeight>"),
+            "{video}"
+        );
+    }
+
+    fn rs485(baud_rate: i32) -> PTZRs485Para {
+        PTZRs485Para {
+            baud_rate,
+            data_bits: 8,
+            parity_type: "none".to_string(),
+            stop_bits: 1,
+            flow_ctrl: "none".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn ptz_serial_config_keeps_the_channel() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/PTZCtrl/channels/1",
+                200,
+                "<PTZChannel><id>1</id><enabled>true</enabled><videoInputID>1</videoInputID>\
+                 <controlProtocol>pelco-d</controlProtocol>\
+                 <PTZRs485Para><baudRate>2400</baudRate><dataBits>8</dataBits>\
+                 <parityType>none</parityType><stopBits>1</stopBits><flowCtrl>none</flowCtrl>\
+                 </PTZRs485Para></PTZChannel>"
+                    .to_string(),
+            ),
+            put("PUT /ISAPI/PTZCtrl/channels/1"),
+        ])
+        .await;
+
+        assert_eq!(camera.get_ptz_serial_config().await.unwrap(), rs485(2400));
+        camera.set_ptz_serial_config(rs485(9600)).await.unwrap();
+        // Same as the camera reports
+        camera.set_ptz_serial_config(rs485(2400)).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        for expected in [
+            "<baudRate>9600</baudRate>",
+            "<dataBits>8</dataBits>",
+            "<controlProtocol>pelco-d</controlProtocol>",
+            "<videoInputID>1</videoInputID>",
+        ] {
+            assert!(bodies[0].contains(expected), "{}", bodies[0]);
+        }
+    }
+
+    #[tokio::test]
+    async fn unusual_baud_rate_is_rejected_before_reading() {
+        let (camera, mock) = mock_camera(vec![]).await;
+
+        let result = camera.set_ptz_serial_config(rs485(1000)).await;
+
+        assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        assert!(mock.requests().is_empty());
+    }
+
+    async fn streaming_camera() -> (HikvisionHttp, MockCamera) {
+        mock_camera(vec![
+            (
+                "GET /ISAPI/Streaming/channels/101",
+                200,
+                streaming_channel_xml(
+                    "<mirrorEnabled>false</mirrorEnabled><rotationDegree>0</rotationDegree>",
+                ),
+            ),
+            put("PUT /ISAPI/Streaming/channels/101"),
+        ])
+        .await
+    }
+
+    fn assert_resolution_kept(body: &str) {
+        assert!(
+            body.contains("<videoResolutionWidth>1920</videoResolutionWidth>"),
+            "{body}"
+        );
+        assert!(
+            body.contains("<videoResolutionHeight>1080</videoResolutionHeight>"),
+            "{body}"
+        );
+    }
+
+    #[tokio::test]
+    async fn encoding_is_sent_in_one_put() {
+        let (camera, mock) = streaming_camera().await;
+
+        camera
+            .set_encoding(VideoEncoding::H265, H264Profile::Main, 50)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            mock.requests().iter().filter(|r| r.method == "GET").count(),
+            1
+        );
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        for expected in [
+            "<videoCodecType>H.265</videoCodecType>",
+            "<H265Profile>Main</H265Profile>",
+            "<GovLength>50</GovLength>",
+        ] {
+            assert!(bodies[0].contains(expected), "{}", bodies[0]);
+        }
+        assert_resolution_kept(&bodies[0]);
+    }
+
+    #[tokio::test]
+    async fn incompatible_encoding_is_rejected_before_reading() {
+        let (camera, mock) = streaming_camera().await;
+
+        for (codec, profile, gop) in [
+            (VideoEncoding::H265, H264Profile::High, 50),
+            (VideoEncoding::H264, H264Profile::High, GOV_LENGTH_MAX + 1),
+        ] {
+            let result = camera.set_encoding(codec, profile, gop).await;
+            assert!(matches!(result, Err(IpCamerasError::InvalidParam(_))));
+        }
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn mirror_body() {
+        let (camera, mock) = streaming_camera().await;
+
+        // Already off, nothing to send
+        camera.set_mirror(false).await.unwrap();
+        camera.set_mirror(true).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains("<mirrorEnabled>true</mirrorEnabled>"),
+            "{}",
+            bodies[0]
+        );
+        assert_resolution_kept(&bodies[0]);
+    }
+
+    #[tokio::test]
+    async fn rotation_body() {
+        let (camera, mock) = streaming_camera().await;
+
+        camera.set_rotation(180).await.unwrap();
+        assert!(matches!(
+            camera.set_rotation(45).await,
+            Err(IpCamerasError::InvalidParam(_))
+        ));
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].contains("<rotationDegree>180</rotationDegree>"),
+            "{}",
+            bodies[0]
+        );
+        assert_resolution_kept(&bodies[0]);
+    }
+
+    #[tokio::test]
+    async fn device_info_update_body() {
+        let (camera, mock) = mock_camera(vec![put("PUT /ISAPI/System/deviceInfo")]).await;
+
+        camera.set_device_name("Gate 1").await.unwrap();
+        camera.set_device_location("North lane").await.unwrap();
+        assert!(matches!(
+            camera.set_device_name("<Gate>").await,
+            Err(IpCamerasError::InvalidParam(_))
+        ));
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 2);
+        assert!(
+            bodies[0].contains("<deviceName>Gate 1</deviceName>"),
+            "{}",
+            bodies[0]
+        );
+        assert!(!bodies[0].contains("deviceLocation"), "{}", bodies[0]);
+        assert!(
+            bodies[1].contains("<deviceLocation>North lane</deviceLocation>"),
+            "{}",
+            bodies[1]
+        );
+        assert!(!bodies[1].contains("deviceName"), "{}", bodies[1]);
+    }
+
+    #[tokio::test]
+    async fn view_role_refuses_spotlight_writes() {
+        let (camera, mock) = mock_camera(vec![]).await;
+        assert!(matches!(camera.camera_role, CameraRole::View));
+        assert!(!camera.can_write());
+
+        assert!(matches!(
+            camera.switch_spotlight(true).await,
+            Err(IpCamerasError::InsufficientRole)
+        ));
+        assert!(mock.requests().is_empty());
+
+        let register = HikvisionHttp {
+            camera_role: CameraRole::Register,
+            ..Default::default()
+        };
+        assert!(register.can_write());
+    }
+
+    // ITC camera with four sync outputs, spotlights wired to lines 2 and 3
+    async fn itc_camera() -> (HikvisionHttp, MockCamera) {
+        itc_camera_with(vec![]).await
+    }
+
+    async fn itc_camera_with(
+        mut routes: Vec<(&'static str, u16, String)>,
+    ) -> (HikvisionHttp, MockCamera) {
+        let outputs: String = (1..=4)
+            .map(|id| {
+                format!(
+                    "<SyncSignalOutput><id>{id}</id><outputStatus>high</outputStatus>\
+                     <videoFlashEnable>true</videoFlashEnable>\
+                     <detectBrightnessEnable>true</detectBrightnessEnable></SyncSignalOutput>"
+                )
+            })
+            .collect();
+        routes.extend([
+            (
+                "GET /ISAPI/ITC/syncSignalOutput",
+                200,
+                format!("<SyncSignalOutputList>{outputs}</SyncSignalOutputList>"),
+            ),
+            put("PUT /ISAPI/ITC/syncSignalOutput"),
+        ]);
+        let mock = MockCamera::start(routes).await;
+        let camera = HikvisionHttp {
+            host: Some(mock.host.clone()),
+            camera_role: CameraRole::Register,
+            external_line: 2,
+            internal_line: 3,
+            ..Default::default()
+        };
+
+        (camera, mock)
+    }
+
+    #[tokio::test]
+    async fn custom_projector_lines_are_switched() {
+        let (camera, mock) = itc_camera().await;
+
+        camera.switch_spotlight(true).await.unwrap();
+        camera.switch_spotlight(false).await.unwrap();
+
+        assert_eq!(camera.cached_state().projector_lines, Some(vec![2, 3]));
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 2);
+        for body in &bodies {
+            assert!(body.contains("<id>2</id>"), "{body}");
+            assert!(body.contains("<id>3</id>"), "{body}");
+            assert!(!body.contains("<id>1</id>"), "{body}");
+            assert!(!body.contains("<id>4</id>"), "{body}");
+        }
+        // Pulsing outputs are on, the flash is only left to the camera when off
+        assert!(
+            bodies[0].contains("<videoFlashEnable>false</videoFlashEnable>"),
+            "{}",
+            bodies[0]
+        );
+        assert!(
+            bodies[1].contains("<videoFlashEnable>true</videoFlashEnable>"),
+            "{}",
+            bodies[1]
+        );
+    }
+
+    #[tokio::test]
+    async fn init_is_reflected_in_cached_state() {
+        let device_info = DeviceInfo {
+            firmware_verison: FirmwareVerison::V514,
+            ..Default::default()
+        };
+        let (camera, _mock) = itc_camera_with(vec![(
+            "GET /ISAPI/System/deviceInfo",
+            200,
+            serde_json::json!({ "DeviceInfo": device_info }).to_string(),
+        )])
+        .await;
+        assert_eq!(camera.cached_state().initialized, Some(false));
+
+        camera.init().await.unwrap();
+
+        let state = camera.cached_state();
+        assert_eq!(state.initialized, Some(true));
+        assert_eq!(state.is_ptz, Some(false));
+        assert_eq!(state.firmware_version.as_deref(), Some("V5.1.4"));
+        // V5.1.4 switches the spotlights through line 1 as well
+        assert_eq!(state.projector_lines, Some(vec![2, 3, 1]));
+    }
+
+    #[tokio::test]
+    async fn ptz_is_answered_from_init_state() {
+        let (camera, mock) = mock_camera(vec![]).await;
+        camera.is_ptz.store(true, Relaxed);
+
+        assert!(camera.is_ptz().await.unwrap());
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn absolute_focus_body() {
+        let (camera, mock) = mock_camera(vec![put(
+            "PUT /ISAPI/System/Video/inputs/channels/1/focus/position",
+        )])
+        .await;
+
+        camera.set_focus_absolute(0.5).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].contains("<focus>500</focus>"), "{}", bodies[0]);
+    }
+
+    #[tokio::test]
+    async fn absolute_focus_is_read_once_settled() {
+        let (camera, mock) = mock_camera(vec![(
+            "GET /ISAPI/System/Video/inputs/channels/1/focus/position",
+            200,
+            "<FocusData><focus>250</focus></FocusData>".to_string(),
+        )])
+        .await;
+
+        assert_eq!(camera.get_focus_absolute().await.unwrap(), 0.25);
+        // The first read has nothing to agree with
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests
+            .iter()
+            .all(|r| r.path == "/ISAPI/System/Video/inputs/channels/1/focus/position"));
+    }
+
+    #[test]
+    fn image_channel_maps_to_image_settings() {
+        use crate::{DayNightMode, WhiteBalanceMode};
+
+        let mut ic = image_channel();
+        ic.color.as_mut().unwrap().contrast_level = 60;
+        ic.white_balance = serde_json::from_value(serde_json::json!({
+            "WhiteBalanceStyle": "outdoor",
+        }))
+        .unwrap();
+        ic.exposure = serde_json::from_value(serde_json::json!({
+            "ExposureType": "IrisFirst",
+        }))
+        .unwrap();
+        ic.ircut_filter = serde_json::from_value(serde_json::json!({
+            "IrcutFilterType": "night",
+        }))
+        .unwrap();
+
+        assert_eq!(
+            ImageSettings::from(&ic),
+            ImageSettings {
+                brightness: Some(50),
+                contrast: Some(60),
+                saturation: Some(50),
+                white_balance: Some(WhiteBalanceMode::Outdoor),
+                exposure_auto: Some(true),
+                day_night: Some(DayNightMode::BlackWhite),
+            }
+        );
+    }
+
+    #[test]
+    fn unreported_image_settings_stay_empty() {
+        let mut ic = image_channel();
+        ic.color = None;
+
+        assert_eq!(ImageSettings::from(&ic), ImageSettings::default());
+    }
+
+    #[tokio::test]
+    async fn image_settings_are_read_in_one_request() {
+        let (camera, mock) = mock_camera(vec![(
+            "GET /ISAPI/Image/channels/1",
+            200,
+            image_channel_xml(
+                "<Color><brightnessLevel>40</brightnessLevel><contrastLevel>55</contrastLevel>\
+                 <saturationLevel>60</saturationLevel></Color>\
+                 <Exposure><ExposureType>manual</ExposureType></Exposure>",
+            ),
+        )])
+        .await;
+
+        let settings = camera.get_image_settings().await.unwrap();
+
+        assert_eq!(
+            (settings.brightness, settings.contrast, settings.saturation),
+            (Some(40), Some(55), Some(60))
+        );
+        assert_eq!(settings.exposure_auto, Some(false));
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn svac_profile_body() {
+        let (camera, mock) = streaming_camera().await;
+
+        camera.set_svac_profile(SVACProfile::High).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        let body = &bodies[0];
+        assert!(body.contains("<SVACProfile>High</SVACProfile>"), "{body}");
+        assert!(
+            body.contains("<videoCodecType>H.264</videoCodecType>"),
+            "{body}"
+        );
+        assert!(body.contains("<maxFrameRate>2500</maxFrameRate>"), "{body}");
+        assert_resolution_kept(body);
+    }
+
+    #[tokio::test]
+    async fn unchanged_svac_profile_is_not_written() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Streaming/channels/101",
+                200,
+                streaming_channel_xml("<SVACProfile>Main</SVACProfile>"),
+            ),
+            put("PUT /ISAPI/Streaming/channels/101"),
+        ])
+        .await;
+
+        camera.set_svac_profile(SVACProfile::Main).await.unwrap();
+
+        assert!(mock.bodies("PUT").is_empty());
+    }
+
+    async fn iris_camera() -> (HikvisionHttp, MockCamera) {
+        mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(
+                    "<Iris><IrisLevel>40</IrisLevel><maxIrisLevelLimit>80</maxIrisLevelLimit>\
+                     <minIrisLevelLimit>10</minIrisLevelLimit></Iris>",
+                ),
+            ),
+            put("PUT /ISAPI/Image/channels/1/iris"),
+        ])
+        .await
+    }
+
+    #[tokio::test]
+    async fn iris_body_is_clamped() {
+        let (camera, mock) = iris_camera().await;
+
+        assert_eq!(camera.get_iris().await.unwrap(), 40);
+        camera.set_iris(95).await.unwrap();
+        camera.set_iris(60).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 2);
+        assert!(
+            bodies[0].contains("<IrisLevel>80</IrisLevel>"),
+            "{}",
+            bodies[0]
+        );
+        assert!(
+            bodies[0].contains("<maxIrisLevelLimit>80</maxIrisLevelLimit>"),
+            "{}",
+            bodies[0]
+        );
+        assert!(
+            bodies[1].contains("<IrisLevel>60</IrisLevel>"),
+            "{}",
+            bodies[1]
+        );
+    }
+
+    #[tokio::test]
+    async fn unchanged_iris_is_not_written() {
+        let (camera, mock) = iris_camera().await;
+
+        camera.set_iris(40).await.unwrap();
+
+        assert!(mock.bodies("PUT").is_empty());
+    }
+
+    #[tokio::test]
+    async fn iris_needs_a_p_iris_lens() {
+        let (camera, _mock) = mock_camera(vec![(
+            "GET /ISAPI/Image/channels/1",
+            200,
+            image_channel_xml(""),
+        )])
+        .await;
+
+        assert!(matches!(
+            camera.get_iris().await,
+            Err(IpCamerasError::NotAvialiableApi)
+        ));
+        assert!(matches!(
+            camera.set_iris(50).await,
+            Err(IpCamerasError::NotAvialiableApi)
+        ));
+    }
+
+    fn listed_channel(id: u32, codec: &str, (width, height): (i32, i32)) -> String {
+        format!(
+            "<StreamingChannel><id>{id}</id><channelName>Camera 01</channelName>\
+             <enabled>true</enabled><Video><enabled>true</enabled>\
+             <videoInputChannelID>1</videoInputChannelID><videoCodecType>{codec}</videoCodecType>\
+             <videoResolutionWidth>{width}</videoResolutionWidth>\
+             <videoResolutionHeight>{height}</videoResolutionHeight><fixedQuality>60</fixedQuality>\
+             <maxFrameRate>2500</maxFrameRate></Video></StreamingChannel>"
+        )
+    }
+
+    #[tokio::test]
+    async fn streaming_channels_are_listed() {
+        let (camera, _mock) = mock_camera(vec![(
+            "GET /ISAPI/Streaming/channels",
+            200,
+            format!(
+                "<StreamingChannelList version=\"2.0\">{}{}{}</StreamingChannelList>",
+                listed_channel(101, "H.265", (3840, 2160)),
+                listed_channel(102, "H.264", (640, 480)),
+                listed_channel(103, "H.264", (1280, 720)),
+            ),
+        )])
+        .await;
+
+        let channels = camera.list_streaming_channels().await.unwrap();
+
+        assert_eq!(
+            channels
+                .iter()
+                .map(|c| (
+                    c.id,
+                    c.video.video_codec_type.clone(),
+                    c.video.video_resolution_width,
+                    c.video.video_resolution_height
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (101, VideoEncoding::H265, 3840, 2160),
+                (102, VideoEncoding::H264, 640, 480),
+                (103, VideoEncoding::H264, 1280, 720),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_streaming_channel_list() {
+        let (camera, _mock) = mock_camera(vec![(
+            "GET /ISAPI/Streaming/channels",
+            200,
+            "<StreamingChannelList version=\"2.0\"></StreamingChannelList>".to_string(),
+        )])
+        .await;
+
+        assert!(camera.list_streaming_channels().await.unwrap().is_empty());
+    }
+
+    fn busy_lens() -> (&'static str, u16, String) {
+        (
+            "GET /ISAPI/System/Video/inputs/channels/1/focus/position",
+            200,
+            "<ResponseStatus><requestURL>/ISAPI/System/Video/inputs/channels/1/focus/position\
+             </requestURL><statusCode>2</statusCode><statusString>Device Busy</statusString>\
+             <subStatusCode>deviceBusy</subStatusCode><errorCode>536870916</errorCode>\
+             <errorMsg>deviceBusy</errorMsg></ResponseStatus>"
+                .to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn busy_lens_is_retried_until_timeout() {
+        let (camera, mock) = mock_camera(vec![busy_lens()]).await;
+
+        assert!(matches!(
+            camera.get_focus_absolute().await,
+            Err(IpCamerasError::Timeout)
+        ));
+        assert_eq!(mock.requests().len(), FOCUS_POLL_RETRIES);
+    }
+
+    #[tokio::test]
+    async fn unreadable_focus_position_is_not_retried() {
+        let (camera, mock) = mock_camera(vec![(
+            "GET /ISAPI/System/Video/inputs/channels/1/focus/position",
+            200,
+            "<FocusData><zoom>1</zoom></FocusData>".to_string(),
+        )])
+        .await;
+
+        assert!(matches!(
+            camera.get_focus_absolute().await,
+            Err(IpCamerasError::XmlParse { .. })
+        ));
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn busy_lens_is_read_once_free() {
+        let mock = MockCamera::start_sequenced(vec![
+            busy_lens(),
+            (
+                "GET /ISAPI/System/Video/inputs/channels/1/focus/position",
+                200,
+                "<FocusData><focus>250</focus></FocusData>".to_string(),
+            ),
+        ])
+        .await;
+        let camera = HikvisionHttp {
+            host: Some(mock.host.clone()),
+            ..initialized()
+        };
+
+        assert_eq!(camera.get_focus_absolute().await.unwrap(), 0.25);
+        // One busy answer, then two matching reads
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn focus_connection_error_is_not_retried() {
+        let (camera, paths) = hanging_up_camera().await;
+
+        assert!(matches!(
+            camera.get_focus_absolute().await,
+            Err(e) if !matches!(e, IpCamerasError::Timeout)
+        ));
+        let paths = paths.lock().unwrap();
+        assert!(paths.len() < FOCUS_POLL_RETRIES, "{paths:?}");
+    }
+
+    #[tokio::test]
+    async fn unknown_image_channel_elements_survive_a_whole_put() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Image/channels/1",
+                200,
+                image_channel_xml(
+                    "<enableImageLossDetection>false</enableImageLossDetection>\
+                     <DefogExt><enabled>true</enabled></DefogExt>",
+                ),
+            ),
+            put("PUT /ISAPI/Image/channels/1"),
+        ])
+        .await;
+
+        camera.set_image_loss_detection(true).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        assert!(
+            bodies[0].ends_with("<DefogExt><enabled>true</enabled></DefogExt></ImageChannel>"),
+            "{}",
+            bodies[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_video_elements_survive_a_streaming_put() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/Streaming/channels/101",
+                200,
+                streaming_channel_xml("<vendorTuning>3</vendorTuning>"),
+            ),
+            put("PUT /ISAPI/Streaming/channels/101"),
+        ])
+        .await;
+
+        camera.set_svac_profile(SVACProfile::Main).await.unwrap();
+
+        let bodies = mock.bodies("PUT");
+        assert_eq!(bodies.len(), 1);
+        let body = &bodies[0];
+        assert!(
+            body.contains("<vendorTuning>3</vendorTuning></Video></StreamingChannel>"),
+            "{body}"
+        );
+        assert_resolution_kept(body);
+    }
+
+    fn init_reads(mock: &MockCamera) -> usize {
+        mock.requests()
+            .iter()
+            .filter(|r| r.path.starts_with("/ISAPI/System/deviceInfo"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn switch_spotlight_runs_lazy_init_once() {
+        let (camera, mock) = itc_camera().await;
+
+        camera.switch_spotlight(true).await.unwrap();
+        let reads = init_reads(&mock);
+        camera.switch_spotlight(false).await.unwrap();
+
+        assert_eq!(camera.cached_state().initialized, Some(true));
+        assert!(reads > 0);
+        assert_eq!(init_reads(&mock), reads);
+        let requests = mock.requests();
+        assert!(
+            requests[0].path.starts_with("/ISAPI/System/deviceInfo"),
+            "{requests:?}"
+        );
+        assert_eq!(requests.last().unwrap().method, "PUT");
+    }
+
+    #[tokio::test]
+    async fn failed_lazy_init_sends_nothing() {
+        let (camera, mock) = itc_camera_with(vec![(
+            "GET /ISAPI/ITC/syncSignalOutput",
+            500,
+            String::new(),
+        )])
+        .await;
+
+        assert!(camera.switch_spotlight(true).await.is_err());
+        assert!(mock.bodies("PUT").is_empty());
+        assert_eq!(camera.cached_state().initialized, Some(false));
+    }
+
+    #[tokio::test]
+    async fn is_ptz_runs_lazy_init() {
+        let mock = MockCamera::start(vec![(
+            "GET /ISAPI/PTZCtrl/channels/1",
+            200,
+            "<PTZChannel><id>1</id><enabled>true</enabled><videoInputID>1</videoInputID>\
+             </PTZChannel>"
+                .to_string(),
+        )])
+        .await;
+        let camera = HikvisionHttp {
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        };
+
+        assert!(camera.is_ptz().await.unwrap());
+        assert_eq!(camera.cached_state().initialized, Some(true));
+        // PTZ cameras have no sync outputs to read
+        assert!(!mock
+            .requests()
+            .iter()
+            .any(|r| r.path.starts_with("/ISAPI/ITC/syncSignalOutput")));
+    }
+
+    #[tokio::test]
+    async fn unsupported_json_falls_back_to_xml() {
+        let (camera, mock) = mock_camera(vec![
+            (
+                "GET /ISAPI/System/time?format=json",
+                200,
+                "<ResponseStatus><statusCode>4</statusCode>\
+                 <statusString>Invalid Operation</statusString></ResponseStatus>"
+                    .to_string(),
+            ),
+            (
+                "GET /ISAPI/System/time",
+                200,
+                "<Time><timeMode>NTP</timeMode><localTime>2024-03-01T10:00:00+03:00</localTime>\
+                 <timeZone>CST-3:00:00</timeZone></Time>"
+                    .to_string(),
+            ),
+        ])
+        .await;
+
+        let time: Time = camera
+            .recieve_json(format!("http://{}/ISAPI/System/time", mock.host))
+            .await
+            .unwrap();
+
+        assert_eq!(time.time_zone, "CST-3:00:00");
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    // Camera that reads each request line, then hangs up without answering
+    async fn hanging_up_camera() -> (HikvisionHttp, Arc<Mutex<Vec<String>>>) {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        tokio::spawn({
+            let paths = paths.clone();
+            async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let mut buf = [0u8; 1024];
+                    let len = stream.read(&mut buf).await.unwrap_or(0);
+                    let head = String::from_utf8_lossy(&buf[..len]).to_string();
+                    if let Some(path) = head.split_whitespace().nth(1) {
+                        paths.lock().unwrap().push(path.to_string());
+                    }
+                }
+            }
+        });
+        let camera = HikvisionHttp {
+            host: Some(host),
+            ..initialized()
+        };
+
+        (camera, paths)
+    }
+
+    #[tokio::test]
+    async fn json_transport_error_is_not_retried_as_xml() {
+        let (camera, paths) = hanging_up_camera().await;
+
+        assert!(camera.retrieve_version_of_camera().await.is_err());
+        let paths = paths.lock().unwrap();
+        assert!(!paths.is_empty());
+        assert!(
+            paths.iter().all(|p| p.ends_with("?format=json")),
+            "{paths:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn capture_mode_capabilities_error_is_returned() {
+        let (camera, paths) = hanging_up_camera().await;
+
+        assert!(camera
+            .set_capture_mode("1920*1080@25fps".to_string())
+            .await
+            .is_err());
+        let paths = paths.lock().unwrap();
+        assert!(!paths.is_empty());
+        assert!(
+            paths
+                .iter()
+                .all(|p| p.ends_with("/captureMode/capabilities")),
+            "{paths:?}"
+        );
     }
 }
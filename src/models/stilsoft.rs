@@ -1,30 +1,55 @@
 use std::{
     io::ErrorKind,
-    sync::atomic::{AtomicBool, Ordering::Relaxed},
+    sync::atomic::{AtomicU8, Ordering::Relaxed},
+    time::Duration,
 };
 
 use async_trait::*;
 use regex::Regex;
+use tokio_util::sync::CancellationToken;
 
 use domain::{stream::Resource, CameraId};
-use onvif::{ok_or_explain, FpsValue, OnvifConnection, OnvifError, OnvifParams};
+use onvif::{ok_or_explain, FpsValue, OnvifConnection};
 use pulsar_core::prelude::*;
 
 use crate::{
-    utils::{handler::*, request::*},
-    IpCamerasError, DEFAULT_TIMEOUT,
+    utils::{
+        cache::TtlCache, handler::*, onvif_connection::connect_onvif, request::*,
+        serde::stilsoft::*, status::CachedState,
+    },
+    AdditionalConfiguration, IpCamerasError, DEFAULT_TIMEOUT,
 };
 
-#[derive(Debug)]
 pub struct StilsoftHttp {
     pub id: CameraId,
     pub host: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
     pub timeout: u64,
+    // Sent with every request, e.g. the User-Agent some firmwares check
+    pub headers: Vec<Header>,
     pub language: u32,
+    pub spotlight_ttl: u64,
 
-    pub spotlight_state: AtomicBool,
+    pub spotlight_state: TtlCache<bool>,
+    pub brightness: AtomicU8,
+}
+
+impl std::fmt::Debug for StilsoftHttp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StilsoftHttp")
+            .field("id", &self.id)
+            .field("host", &self.host)
+            .field("username", &self.username)
+            .field("password", &redact(&self.password))
+            .field("timeout", &self.timeout)
+            .field("headers", &redact_headers(&self.headers))
+            .field("language", &self.language)
+            .field("spotlight_ttl", &self.spotlight_ttl)
+            .field("spotlight_state", &self.spotlight_state)
+            .field("brightness", &self.brightness)
+            .finish()
+    }
 }
 
 impl Default for StilsoftHttp {
@@ -35,9 +60,12 @@ impl Default for StilsoftHttp {
             username: None,
             password: None,
             timeout: DEFAULT_TIMEOUT,
+            headers: Vec::new(),
             language: 3,
+            spotlight_ttl: DEFAULT_SPOTLIGHT_TTL,
 
-            spotlight_state: AtomicBool::new(false),
+            spotlight_state: TtlCache::new(),
+            brightness: AtomicU8::new(DEFAULT_BRIGHTNESS),
         }
     }
 }
@@ -77,6 +105,35 @@ impl ApiHandler for StilsoftHttp {
         }
     }
 
+    fn timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    fn extra_headers(&self) -> &[Header] {
+        &self.headers
+    }
+
+    fn cached_state(&self) -> CachedState {
+        CachedState {
+            spotlight_state: self.spotlight_state.last().ok().flatten(),
+            ..Default::default()
+        }
+    }
+
+    async fn init_onvif_cancellable(
+        &self,
+        cancel: &CancellationToken,
+    ) -> Result<OnvifConnection, IpCamerasError> {
+        connect_onvif(
+            self.host.clone(),
+            self.username.clone(),
+            self.password.clone(),
+            self.timeout,
+            cancel,
+        )
+        .await
+    }
+
     //EXTERNAL API
     async fn get_fps(&self) -> Result<FpsValue, IpCamerasError> {
         Ok(ok_or_explain!(self.init_onvif().await?.get_fps().await))
@@ -87,33 +144,138 @@ impl ApiHandler for StilsoftHttp {
     }
 
     async fn get_spotlight_state(&self) -> Result<bool, IpCamerasError> {
-        Ok(self.spotlight_state.load(Relaxed))
+        let ttl = Duration::from_secs(self.spotlight_ttl);
+        if let Some(state) = self.spotlight_state.get(ttl)? {
+            return Ok(state);
+        }
+
+        let web_id = self.get_id_from_camera().await?;
+        let state = self.read_spotlight_state(&web_id).await?;
+        self.spotlight_state.set(state)?;
+
+        Ok(state)
     }
 
     async fn switch_spotlight(&self, enabled: bool) -> Result<(), IpCamerasError> {
         let web_id = self.get_id_from_camera().await?;
-        let host = self.host();
         let value = (enabled as i32) + 1;
 
-        if self
-            .request(
-                format!("http://{host}/ajax/image_profile?id={web_id}&value={value}"),
-                None,
-                Method::GET,
-                None,
-            )
-            .await?
-            .contains("Success")
-        {
-            self.spotlight_state.store(enabled, Relaxed);
+        if self.ajax_request(&web_id, "image_profile", value).await? {
+            self.spotlight_state.set(enabled)?;
             Ok(())
         } else {
-            Err(IpCamerasError::Spotlight)
+            Err(IpCamerasError::spotlight(
+                "Stilsoft",
+                format!("image profile {value} not confirmed, `Success` missing in the answer"),
+            ))
         }
     }
+
+    async fn get_additional_configuration(
+        &self,
+    ) -> Result<AdditionalConfiguration, IpCamerasError> {
+        let web_id = self.get_id_from_camera().await?;
+
+        let night_mode = self.read_spotlight_state(&web_id).await?;
+        self.spotlight_state.set(night_mode)?;
+        let brightness = self.read_brightness(&web_id).await?;
+        self.brightness.store(brightness, Relaxed);
+
+        Ok(AdditionalConfiguration {
+            id: self.id,
+            default_settings: Some(false),
+            stilsoft: Some(StilsoftConfiguration {
+                brightness: Some(brightness),
+                night_mode: Some(night_mode),
+            }),
+
+            ..Default::default()
+        })
+    }
+
+    async fn set_additional_configuration(
+        &self,
+        configuration: AdditionalConfiguration,
+    ) -> Result<(), IpCamerasError> {
+        let configuration = unwrap_some!(configuration.stilsoft, return Ok(()));
+
+        // Nothing is sent unless the whole configuration is valid
+        if let Some(brightness) = configuration.brightness {
+            validate_brightness(brightness)?;
+        }
+
+        if let Some(night_mode) = configuration.night_mode {
+            self.switch_spotlight(night_mode).await?;
+        }
+
+        if let Some(brightness) = configuration.brightness {
+            let web_id = self.get_id_from_camera().await?;
+            if self.ajax_request(&web_id, "brightness", brightness).await? {
+                self.brightness.store(brightness, Relaxed);
+            } else {
+                return Err(IpCamerasError::spotlight(
+                    "Stilsoft",
+                    format!(
+                        "brightness {brightness} not confirmed, `Success` missing in the answer"
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_default_configuration(&self) -> Result<AdditionalConfiguration, IpCamerasError> {
+        Ok(AdditionalConfiguration {
+            id: self.id,
+            default_settings: Some(true),
+            stilsoft: Some(StilsoftConfiguration {
+                brightness: Some(DEFAULT_BRIGHTNESS),
+                night_mode: Some(false),
+            }),
+
+            ..Default::default()
+        })
+    }
 }
 
 impl StilsoftHttp {
+    // Timeout in seconds
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_credentials(mut self, username: Option<String>, password: Option<String>) -> Self {
+        self.username = username;
+        self.password = password;
+        self
+    }
+
+    // Replaces the default reqwest/curl agent
+    pub fn with_user_agent(mut self, agent: impl Into<String>) -> Self {
+        self.headers.retain(|h| !matches!(h, Header::UserAgent(_)));
+        self.headers.push(Header::UserAgent(agent.into()));
+        self
+    }
+
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers.extend(
+            headers
+                .into_iter()
+                .map(|(name, value)| Header::Custom(name, value)),
+        );
+        self
+    }
+
+    // For firmwares that break compressed answers
+    pub fn without_compression(mut self) -> Self {
+        if !self.headers.contains(&Header::NoCompression) {
+            self.headers.push(Header::NoCompression);
+        }
+        self
+    }
+
     async fn get_id_from_camera(&self) -> Result<String, IpCamerasError> {
         let host = self.host();
         let (user, password) = self.auth();
@@ -137,17 +299,249 @@ impl StilsoftHttp {
         }
     }
 
-    async fn init_onvif(&self) -> Result<OnvifConnection, OnvifError> {
-        let onvif_params = OnvifParams {
-            host: self.host.clone(),
-            username: self.username.clone(),
-            password: self.password.clone(),
-            dummy: false,
-            post_process_status: None,
+    // 2 is night, 1 is day
+    async fn read_spotlight_state(&self, web_id: &str) -> Result<bool, IpCamerasError> {
+        let response = self.read_ajax(web_id, "image_profile").await?;
+        let value = Self::parse_ajax_value(&response).ok_or_else(|| {
+            IpCamerasError::spotlight(
+                "Stilsoft",
+                format!("no image profile in the answer: {response}"),
+            )
+        })?;
+
+        Ok(value == 2)
+    }
+
+    async fn read_brightness(&self, web_id: &str) -> Result<u8, IpCamerasError> {
+        let response = self.read_ajax(web_id, "brightness").await?;
+
+        Self::parse_ajax_value(&response)
+            .and_then(|value| u8::try_from(value).ok())
+            .filter(|brightness| validate_brightness(*brightness).is_ok())
+            .ok_or_else(|| {
+                IpCamerasError::spotlight(
+                    "Stilsoft",
+                    format!("no brightness in the answer: {response}"),
+                )
+            })
+    }
+
+    // Without `value` the endpoints answer with the current one
+    async fn read_ajax(&self, web_id: &str, endpoint: &str) -> Result<String, IpCamerasError> {
+        let url = format!("http://{}/ajax/{endpoint}?id={web_id}", self.host());
+
+        Ok(self
+            .request(url, None, Method::GET, None)
+            .await?
+            .trim()
+            .to_string())
+    }
+
+    // The value is the last number of the answer
+    fn parse_ajax_value(response: &str) -> Option<i32> {
+        response
+            .rsplit(|ch: char| !ch.is_ascii_digit())
+            .find(|part| !part.is_empty())
+            .and_then(|value| value.parse().ok())
+    }
+
+    fn ajax_url(&self, web_id: &str, endpoint: &str, value: impl std::fmt::Display) -> String {
+        let host = self.host();
+        format!("http://{host}/ajax/{endpoint}?id={web_id}&value={value}")
+    }
+
+    // Returns whether the camera accepted the value
+    async fn ajax_request(
+        &self,
+        web_id: &str,
+        endpoint: &str,
+        value: impl std::fmt::Display + Send,
+    ) -> Result<bool, IpCamerasError> {
+        Ok(self
+            .request(
+                self.ajax_url(web_id, endpoint, value),
+                None,
+                Method::GET,
+                None,
+            )
+            .await?
+            .contains("Success"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::utils::mock::MockCamera;
+
+    // Camera in night mode, with the given spotlight cache lifetime
+    async fn night_camera(spotlight_ttl: u64) -> (StilsoftHttp, MockCamera) {
+        profile_camera("2", spotlight_ttl).await
+    }
+
+    // Camera answering `profile` to every image profile request
+    async fn profile_camera(profile: &str, spotlight_ttl: u64) -> (StilsoftHttp, MockCamera) {
+        let mock = MockCamera::start(vec![
+            (
+                "POST /goform/setLoginParam",
+                200,
+                "YWRtaW46YWRtaW4".to_string(),
+            ),
+            ("GET /ajax/image_profile", 200, profile.to_string()),
+        ])
+        .await;
+        let camera = StilsoftHttp {
+            host: Some(mock.host.clone()),
+            spotlight_ttl,
+            ..Default::default()
+        };
+
+        (camera, mock)
+    }
+
+    fn profile_reads(mock: &MockCamera) -> usize {
+        mock.requests()
+            .iter()
+            .filter(|r| r.path.starts_with("/ajax/image_profile"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn spotlight_state_is_cached_within_ttl() {
+        let (camera, mock) = night_camera(60).await;
+
+        assert!(camera.get_spotlight_state().await.unwrap());
+        assert!(camera.get_spotlight_state().await.unwrap());
+        assert_eq!(profile_reads(&mock), 1);
+    }
+
+    #[tokio::test]
+    async fn expired_spotlight_state_is_read_again() {
+        let (camera, mock) = night_camera(0).await;
+
+        assert!(camera.get_spotlight_state().await.unwrap());
+        assert!(camera.get_spotlight_state().await.unwrap());
+        assert_eq!(profile_reads(&mock), 2);
+    }
+
+    #[tokio::test]
+    async fn unconfirmed_profile_switch_names_the_vendor() {
+        let (camera, _mock) = profile_camera("Error", 0).await;
+
+        match camera.switch_spotlight(true).await {
+            Err(IpCamerasError::Spotlight { vendor, detail }) => {
+                assert_eq!(vendor, "Stilsoft");
+                assert!(detail.contains("image profile 2"), "{detail}");
+                assert!(detail.contains("`Success` missing"), "{detail}");
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn unreadable_profile_names_the_vendor() {
+        let (camera, _mock) = profile_camera("Error", 0).await;
+
+        match camera.get_spotlight_state().await {
+            Err(IpCamerasError::Spotlight { vendor, detail }) => {
+                assert_eq!(vendor, "Stilsoft");
+                assert_eq!(detail, "no image profile in the answer: Error");
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn ajax_request_url() {
+        let camera = StilsoftHttp {
+            host: Some("10.0.0.2".to_string()),
+            ..Default::default()
         };
 
-        let onvif_connection = ok_or_explain!(OnvifConnection::new(onvif_params).await);
+        assert_eq!(
+            camera.ajax_url("YWRtaW46YWRtaW4", "brightness", 70),
+            "http://10.0.0.2/ajax/brightness?id=YWRtaW46YWRtaW4&value=70"
+        );
+        assert_eq!(
+            camera.ajax_url("YWRtaW46YWRtaW4", "image_profile", 2),
+            "http://10.0.0.2/ajax/image_profile?id=YWRtaW46YWRtaW4&value=2"
+        );
+    }
+
+    #[test]
+    fn ajax_value_is_last_number() {
+        assert_eq!(StilsoftHttp::parse_ajax_value("2"), Some(2));
+        assert_eq!(StilsoftHttp::parse_ajax_value("image_profile=1"), Some(1));
+        assert_eq!(StilsoftHttp::parse_ajax_value("{\"value\":75}"), Some(75));
+        assert_eq!(StilsoftHttp::parse_ajax_value("Error"), None);
+    }
+
+    #[test]
+    fn brightness_is_validated() {
+        assert!(validate_brightness(0).is_ok());
+        assert!(validate_brightness(100).is_ok());
+        assert!(validate_brightness(101).is_err());
+    }
+
+    #[test]
+    fn configuration_round_trip() {
+        let configuration = AdditionalConfiguration {
+            id: 7,
+            stilsoft: Some(StilsoftConfiguration {
+                brightness: Some(70),
+                night_mode: Some(true),
+            }),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&configuration).unwrap();
+        let restored: AdditionalConfiguration = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.stilsoft, configuration.stilsoft);
+
+        let flat: BTreeMap<String, String> = configuration.to_flat().unwrap();
+        assert_eq!(
+            flat.get("stilsoft.brightness").map(String::as_str),
+            Some("70")
+        );
+        assert_eq!(
+            flat.get("stilsoft.night_mode").map(String::as_str),
+            Some("true")
+        );
+        let restored = AdditionalConfiguration::from_flat(&flat).unwrap();
+        assert_eq!(restored.stilsoft, configuration.stilsoft);
+    }
+
+    #[tokio::test]
+    async fn onvif_init_is_bounded_by_timeout() {
+        // TEST-NET-1, nothing answers there
+        let camera = StilsoftHttp {
+            host: Some("192.0.2.1".to_string()),
+            timeout: 1,
+            ..Default::default()
+        };
+
+        let result = tokio::time::timeout(Duration::from_secs(3), camera.init_onvif()).await;
+        assert!(
+            matches!(result, Ok(Err(_))),
+            "init_onvif didn't give up in time"
+        );
+    }
+
+    #[tokio::test]
+    async fn onvif_init_can_be_cancelled() {
+        let camera = StilsoftHttp {
+            host: Some("192.0.2.1".to_string()),
+            timeout: 60,
+            ..Default::default()
+        };
+        let cancel = CancellationToken::new();
+        cancel.cancel();
 
-        Ok(onvif_connection)
+        assert!(matches!(
+            camera.init_onvif_cancellable(&cancel).await,
+            Err(IpCamerasError::Cancelled)
+        ));
     }
 }
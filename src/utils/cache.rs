@@ -0,0 +1,44 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::IpCamerasError;
+
+// Last known value plus the moment it was stored; callers decide how old is too old
+#[derive(Debug, Default)]
+pub struct TtlCache<T: Copy> {
+    inner: Mutex<Option<(T, Instant)>>,
+}
+
+impl<T: Copy> TtlCache<T> {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(None),
+        }
+    }
+
+    // Value stored less than `ttl` ago
+    pub fn get(&self, ttl: Duration) -> Result<Option<T>, IpCamerasError> {
+        Ok(self
+            .inner
+            .lock()?
+            .filter(|(_, stored_at)| stored_at.elapsed() < ttl)
+            .map(|(value, _)| value))
+    }
+
+    // Last stored value regardless of its age
+    pub fn last(&self) -> Result<Option<T>, IpCamerasError> {
+        Ok(self.inner.lock()?.map(|(value, _)| value))
+    }
+
+    pub fn set(&self, value: T) -> Result<(), IpCamerasError> {
+        *self.inner.lock()? = Some((value, Instant::now()));
+        Ok(())
+    }
+
+    pub fn invalidate(&self) -> Result<(), IpCamerasError> {
+        *self.inner.lock()? = None;
+        Ok(())
+    }
+}
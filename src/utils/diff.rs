@@ -0,0 +1,88 @@
+use serde_json::Value;
+
+// Paths (`image_channel.Color.brightnessLevel`, `list[2]`) whose values differ between
+// two settings snapshots. A path missing on one side counts as changed.
+pub fn diff_settings(a: &Value, b: &Value) -> Vec<String> {
+    let mut changed = vec![];
+    diff_into(a, b, String::new(), &mut changed);
+
+    changed
+}
+
+fn diff_into(a: &Value, b: &Value, path: String, changed: &mut Vec<String>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                let child = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+
+                match (a.get(key), b.get(key)) {
+                    (Some(a), Some(b)) => diff_into(a, b, child, changed),
+                    _ => changed.push(child),
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                diff_into(a, b, format!("{path}[{i}]"), changed);
+            }
+        }
+        (a, b) if a != b => changed.push(path),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::utils::serde::hik::ImageChannel;
+
+    fn image_channel() -> ImageChannel {
+        serde_json::from_value(json!({
+            "id": 1,
+            "enabled": true,
+            "Color": {
+                "brightnessLevel": 50,
+                "contrastLevel": 50,
+                "saturationLevel": 50,
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn nested_image_channel_change() {
+        let baseline = image_channel();
+        let mut live = image_channel();
+        live.color.as_mut().unwrap().brightness_level = 70;
+
+        let changed = diff_settings(
+            &serde_json::to_value(&baseline).unwrap(),
+            &serde_json::to_value(&live).unwrap(),
+        );
+
+        assert_eq!(changed, vec!["Color.brightnessLevel"]);
+    }
+
+    #[test]
+    fn missing_keys_and_array_items() {
+        let a = json!({ "list": [1, 2, 3], "gone": 1, "same": { "x": true } });
+        let b = json!({ "list": [1, 5, 3], "added": 1, "same": { "x": true } });
+
+        assert_eq!(diff_settings(&a, &b), vec!["added", "gone", "list[1]"]);
+        assert_eq!(
+            diff_settings(&json!({ "list": [1] }), &json!({ "list": [1, 2] })),
+            vec!["list"]
+        );
+        assert!(diff_settings(&a, &a).is_empty());
+    }
+}
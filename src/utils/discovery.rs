@@ -0,0 +1,194 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use domain::CameraModelName;
+use pulsar_core::prelude::*;
+use regex::Regex;
+use tokio::{
+    net::UdpSocket,
+    time::{timeout_at, Duration, Instant},
+};
+
+use crate::IpCamerasError;
+
+const WS_DISCOVERY_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const WS_DISCOVERY_PORT: u16 = 3702;
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredCamera {
+    pub host: String,
+    pub model: Option<CameraModelName>,
+    pub xaddr: String,
+}
+
+// Sends a WS-Discovery probe for ONVIF devices and collects matches until `wait` expires
+pub async fn discover(wait: Duration) -> Result<Vec<DiscoveredCamera>, IpCamerasError> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket
+        .send_to(
+            probe_message().as_bytes(),
+            SocketAddr::from((WS_DISCOVERY_ADDR, WS_DISCOVERY_PORT)),
+        )
+        .await?;
+
+    let deadline = Instant::now() + wait;
+    let mut cameras: Vec<DiscoveredCamera> = vec![];
+    let mut buf = vec![0u8; 65535];
+
+    while let Ok(received) = timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let (len, from) = received?;
+        let response = String::from_utf8_lossy(&buf[..len]);
+
+        for camera in parse_probe_matches(&response)? {
+            if cameras.iter().any(|c| c.xaddr == camera.xaddr) {
+                continue;
+            }
+
+            trace!("Discovered {} from {}", camera.xaddr, from);
+            cameras.push(camera);
+        }
+    }
+
+    Ok(cameras)
+}
+
+fn probe_message() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<e:Envelope xmlns:e="http://www.w3.org/2003/05/soap-envelope" xmlns:w="http://schemas.xmlsoap.org/ws/2004/08/addressing" xmlns:d="http://schemas.xmlsoap.org/ws/2005/04/discovery" xmlns:dn="http://www.onvif.org/ver10/network/wsdl">
+<e:Header>
+<w:MessageID>uuid:{:08x}-0000-4000-8000-{:012x}</w:MessageID>
+<w:To e:mustUnderstand="true">urn:schemas-xmlsoap-org:ws:2005:04:discovery</w:To>
+<w:Action e:mustUnderstand="true">http://schemas.xmlsoap.org/ws/2005/04/discovery/Probe</w:Action>
+</e:Header>
+<e:Body><d:Probe><d:Types>dn:NetworkVideoTransmitter</d:Types></d:Probe></e:Body>
+</e:Envelope>"#,
+        (nanos >> 48) as u32,
+        nanos as u64 & 0xffff_ffff_ffff
+    )
+}
+
+// One response may carry several `ProbeMatch` elements, e.g. from an NVR answering for its channels
+pub fn parse_probe_matches(response: &str) -> Result<Vec<DiscoveredCamera>, IpCamerasError> {
+    let match_re = Regex::new(r"(?s)<(?:\w+:)?ProbeMatch>(.*?)</(?:\w+:)?ProbeMatch>")?;
+    let xaddrs_re = Regex::new(r"<(?:\w+:)?XAddrs>([^<]*)</")?;
+    let scopes_re = Regex::new(r"<(?:\w+:)?Scopes[^>]*>([^<]*)</")?;
+    let host_re = Regex::new(r"^\w+://([^/:\[\]]+)")?;
+
+    let mut cameras = vec![];
+    for probe_match in match_re.captures_iter(response).filter_map(|c| c.get(1)) {
+        let probe_match = probe_match.as_str();
+
+        let scopes = scopes_re
+            .captures(probe_match)
+            .and_then(|c| c.get(1))
+            .map(|m| m.as_str())
+            .unwrap_or_default();
+        let model = guess_model(scopes);
+
+        let xaddrs = unwrap_some!(
+            xaddrs_re.captures(probe_match).and_then(|c| c.get(1)),
+            continue
+        );
+
+        // A device may announce several addresses (e.g. IPv4 and IPv6), keep the parsable ones
+        cameras.extend(xaddrs.as_str().split_whitespace().filter_map(|xaddr| {
+            let host = host_re.captures(xaddr)?.get(1)?.as_str().to_string();
+            Some(DiscoveredCamera {
+                host,
+                model: model.clone(),
+                xaddr: xaddr.to_string(),
+            })
+        }));
+    }
+
+    Ok(cameras)
+}
+
+// Scopes look like `onvif://www.onvif.org/hardware/DS-2CD2143G0-I onvif://www.onvif.org/name/HIKVISION`
+fn guess_model(scopes: &str) -> Option<CameraModelName> {
+    let scopes = scopes.to_lowercase();
+
+    if scopes.contains("hikvision") {
+        Some(CameraModelName::Hikvision)
+    } else if scopes.contains("dahua") {
+        Some(CameraModelName::Dahua)
+    } else if scopes.contains("axis") {
+        Some(CameraModelName::Axis)
+    } else if scopes.contains("stilsoft") {
+        Some(CameraModelName::Stilsoft)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe_match(scopes: &str, xaddrs: &str) -> String {
+        format!(
+            "<d:ProbeMatch>\
+             <w:EndpointReference><w:Address>urn:uuid:1</w:Address></w:EndpointReference>\
+             <d:Types>dn:NetworkVideoTransmitter</d:Types>\
+             <d:Scopes>{scopes}</d:Scopes>\
+             <d:XAddrs>{xaddrs}</d:XAddrs>\
+             <d:MetadataVersion>1</d:MetadataVersion>\
+             </d:ProbeMatch>"
+        )
+    }
+
+    fn probe_matches(matches: &[String]) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <SOAP-ENV:Envelope><SOAP-ENV:Body><d:ProbeMatches>{}</d:ProbeMatches>\
+             </SOAP-ENV:Body></SOAP-ENV:Envelope>",
+            matches.concat()
+        )
+    }
+
+    #[test]
+    fn two_devices_are_discovered() {
+        let response = probe_matches(&[
+            probe_match(
+                "onvif://www.onvif.org/hardware/DS-2CD2143G0-I onvif://www.onvif.org/name/HIKVISION",
+                "http://192.168.1.64/onvif/device_service",
+            ),
+            probe_match(
+                "onvif://www.onvif.org/name/Dahua onvif://www.onvif.org/type/video_encoder",
+                "http://192.168.1.108:80/onvif/device_service",
+            ),
+        ]);
+
+        let cameras = parse_probe_matches(&response).unwrap();
+
+        assert_eq!(cameras.len(), 2);
+        assert_eq!(cameras[0].host, "192.168.1.64");
+        assert!(matches!(cameras[0].model, Some(CameraModelName::Hikvision)));
+        assert_eq!(cameras[1].host, "192.168.1.108");
+        assert_eq!(
+            cameras[1].xaddr,
+            "http://192.168.1.108:80/onvif/device_service"
+        );
+        assert!(matches!(cameras[1].model, Some(CameraModelName::Dahua)));
+    }
+
+    #[test]
+    fn unparsable_addresses_and_unknown_vendors() {
+        let response = probe_matches(&[probe_match(
+            "onvif://www.onvif.org/name/IPC",
+            "http://[fe80::1]/onvif/device_service http://10.0.0.5/onvif/device_service",
+        )]);
+
+        let cameras = parse_probe_matches(&response).unwrap();
+
+        assert_eq!(cameras.len(), 1);
+        assert_eq!(cameras[0].host, "10.0.0.5");
+        assert!(cameras[0].model.is_none());
+        assert!(parse_probe_matches("<Envelope/>").unwrap().is_empty());
+    }
+}
@@ -2,6 +2,8 @@ use domain::stream::StreamError;
 use domain::CameraId;
 use onvif::OnvifError;
 
+use crate::utils::serde::hik::ErrorCode;
+
 use thiserror::*;
 
 #[derive(Error, Debug)]
@@ -73,10 +75,133 @@ pub enum IpCamerasError {
     NotSet,
     #[error("api is not supported")]
     NotAvialiableApi,
-    #[error("error with setting|getting spotlight to camera")]
-    Spotlight,
+    #[error("{vendor} spotlight error: {detail}")]
+    Spotlight { vendor: String, detail: String },
     #[error("error with setting|getting fps to camera")]
     Fps,
+    #[error("camera did not respond in time")]
+    Timeout,
+    #[error("focus settled at {actual}, expected {expected}")]
+    FocusMismatch { expected: f32, actual: f32 },
+    #[error("request was cancelled")]
+    Cancelled,
+    #[error("operation is not permitted for the camera role")]
+    InsufficientRole,
+    #[error("{0} transport is disabled at compile time")]
+    TransportDisabled(&'static str),
+    #[error("camera redirected to another origin: {location}")]
+    Redirected { location: String },
+    #[error("invalid parameter: {0}")]
+    InvalidParam(String),
+    #[error("axis camera error: {message}")]
+    Axis { message: String },
+    #[error("camera responded with error: {0}")]
+    Camera(ErrorCode),
+    #[error("can't parse XML response: {source}, body: {body}")]
+    XmlParse {
+        source: serde_xml_rs::Error,
+        body: String,
+    },
+    #[error("can't parse JSON response: {source}, body: {body}")]
+    JsonParse {
+        source: serde_json::Error,
+        body: String,
+    },
+}
+
+// Enough of a response to recognize it in the logs
+const BODY_SNIPPET_LEN: usize = 256;
+
+fn body_snippet(body: &str) -> String {
+    match body.char_indices().nth(BODY_SNIPPET_LEN) {
+        Some((end, _)) => format!("{}...", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+impl IpCamerasError {
+    pub fn spotlight(vendor: &str, detail: impl Into<String>) -> Self {
+        Self::Spotlight {
+            vendor: vendor.to_string(),
+            detail: detail.into(),
+        }
+    }
+
+    pub fn xml_parse(source: serde_xml_rs::Error, body: &str) -> Self {
+        Self::XmlParse {
+            source,
+            body: body_snippet(body),
+        }
+    }
+
+    pub fn json_parse(source: serde_json::Error, body: &str) -> Self {
+        Self::JsonParse {
+            source,
+            body: body_snippet(body),
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        use IpCamerasError::*;
+
+        match self {
+            Timeout => true,
+            Reqwest { source } => source.is_timeout() || source.is_connect(),
+            Std { source } => matches!(
+                source.kind(),
+                std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::Interrupted
+            ),
+            Camera(code) => matches!(
+                code,
+                ErrorCode::DeviceBusy | ErrorCode::Upgrading | ErrorCode::ReConnectIpc
+            ),
+            _ => false,
+        }
+    }
+
+    // Transport or authentication failure: the camera may have rebooted or been swapped.
+    // Errors the camera answered with (like `DeviceBusy`) don't count.
+    pub fn is_connection_failure(&self) -> bool {
+        use IpCamerasError::*;
+
+        match self {
+            Timeout | Digest { .. } => true,
+            Reqwest { source } => {
+                source.is_timeout()
+                    || source.is_connect()
+                    || matches!(
+                        source.status(),
+                        Some(reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN)
+                    )
+            }
+            Std { source } => matches!(
+                source.kind(),
+                std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            ),
+            _ => false,
+        }
+    }
+
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, IpCamerasError::NotAvialiableApi)
+    }
+
+    // `Ok(None)` when the model lacks the API, so only real failures stay errors
+    pub fn unsupported_as_none<T>(result: Result<T, Self>) -> Result<Option<T>, Self> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error) if error.is_unsupported() => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
 }
 
 impl From<IpCamerasError> for StreamError {
@@ -101,3 +226,132 @@ impl<T> From<std::sync::PoisonError<T>> for IpCamerasError {
         Self::Sync
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transient_errors_are_retryable() {
+        let retryable = [
+            IpCamerasError::Timeout,
+            std::io::ErrorKind::TimedOut.into(),
+            std::io::ErrorKind::ConnectionReset.into(),
+            IpCamerasError::Camera(ErrorCode::DeviceBusy),
+            IpCamerasError::Camera(ErrorCode::Upgrading),
+            IpCamerasError::Camera(ErrorCode::ReConnectIpc),
+        ];
+
+        for error in retryable {
+            assert!(error.is_retryable(), "{error:?}");
+        }
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retryable() {
+        let json = serde_json::from_str::<u8>("x").unwrap_err();
+        let permanent = [
+            IpCamerasError::Sync,
+            IpCamerasError::NotAvialiableApi,
+            IpCamerasError::Cancelled,
+            IpCamerasError::InvalidParam("fps".to_string()),
+            IpCamerasError::SerdeJson { source: json },
+            std::io::ErrorKind::PermissionDenied.into(),
+            IpCamerasError::Camera(ErrorCode::DeviceError),
+        ];
+
+        for error in permanent {
+            assert!(!error.is_retryable(), "{error:?}");
+        }
+    }
+
+    #[test]
+    fn connection_failures() {
+        let lost = [
+            IpCamerasError::Timeout,
+            std::io::ErrorKind::ConnectionRefused.into(),
+            std::io::ErrorKind::ConnectionReset.into(),
+            std::io::ErrorKind::UnexpectedEof.into(),
+        ];
+        let answered = [
+            IpCamerasError::Camera(ErrorCode::DeviceBusy),
+            IpCamerasError::Camera(ErrorCode::Upgrading),
+            IpCamerasError::NotAvialiableApi,
+            IpCamerasError::Redirected {
+                location: "https://camera/".to_string(),
+            },
+            std::io::ErrorKind::InvalidData.into(),
+        ];
+
+        for error in lost {
+            assert!(error.is_connection_failure(), "{error:?}");
+        }
+        for error in answered {
+            assert!(!error.is_connection_failure(), "{error:?}");
+        }
+    }
+
+    #[test]
+    fn poisoned_lock_is_not_retryable() {
+        let lock = std::sync::Arc::new(std::sync::Mutex::new(()));
+        let poisoner = lock.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison");
+        })
+        .join();
+
+        let error: IpCamerasError = lock.lock().unwrap_err().into();
+        assert!(matches!(error, IpCamerasError::Sync));
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn only_unsupported_becomes_none() {
+        assert!(IpCamerasError::NotAvialiableApi.is_unsupported());
+        assert!(!IpCamerasError::Timeout.is_unsupported());
+
+        assert!(matches!(
+            IpCamerasError::unsupported_as_none(Ok(25)),
+            Ok(Some(25))
+        ));
+        assert!(matches!(
+            IpCamerasError::unsupported_as_none::<()>(Err(IpCamerasError::NotAvialiableApi)),
+            Ok(None)
+        ));
+        assert!(matches!(
+            IpCamerasError::unsupported_as_none::<()>(Err(IpCamerasError::Timeout)),
+            Err(IpCamerasError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn malformed_xml_error_shows_the_body() {
+        let body = "<html><body>502 Bad Gateway</body>";
+        let source =
+            serde_xml_rs::from_str::<crate::utils::serde::hik::Response>(body).unwrap_err();
+
+        let message = IpCamerasError::xml_parse(source, body).to_string();
+        assert!(message.contains("502 Bad Gateway"), "{message}");
+    }
+
+    #[test]
+    fn malformed_json_error_shows_the_body() {
+        let body = "<html>401 Unauthorized</html>";
+        let source = serde_json::from_str::<u8>(body).unwrap_err();
+
+        let message = IpCamerasError::json_parse(source, body).to_string();
+        assert!(message.contains("401 Unauthorized"), "{message}");
+    }
+
+    #[test]
+    fn long_bodies_are_truncated() {
+        let body = "x".repeat(BODY_SNIPPET_LEN * 4);
+
+        assert_eq!(
+            body_snippet(&body),
+            format!("{}...", "x".repeat(BODY_SNIPPET_LEN))
+        );
+        assert_eq!(body_snippet("short"), "short");
+    }
+}
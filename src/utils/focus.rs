@@ -89,6 +89,21 @@ impl FocusCapabilitiesAbsolute {
     pub fn new(min: FocusValue, max: FocusValue, step: FocusValue) -> Self {
         Self { min, max, step }
     }
+
+    // Maps a native value into `[0.0, 1.0]`
+    pub fn normalize(&self, value: FocusValue) -> FocusValue {
+        if self.max <= self.min {
+            return 0.;
+        }
+
+        ((value - self.min) / (self.max - self.min)).clamp(0., 1.)
+    }
+
+    // How far a read-back may drift from the requested value: a step,
+    // but never less than 1% of the range since motors rarely stop exactly
+    pub fn tolerance(&self) -> FocusValue {
+        self.step.max((self.max - self.min).abs() * 0.01)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -130,3 +145,40 @@ pub enum Direction {
     Forward,
     Backward,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mid_range_normalizes_to_half() {
+        let axis = FocusCapabilitiesAbsolute::new(0., 1., 0.001);
+        let dahua = FocusCapabilitiesAbsolute::new(100., 900., 1.);
+
+        assert_eq!(axis.normalize(0.5), 0.5);
+        assert_eq!(dahua.normalize(500.), 0.5);
+        assert_eq!(dahua.normalize(100.), 0.);
+        assert_eq!(dahua.normalize(900.), 1.);
+    }
+
+    #[test]
+    fn normalize_clamps_and_survives_empty_range() {
+        let capabilities = FocusCapabilitiesAbsolute::new(100., 900., 1.);
+
+        assert_eq!(capabilities.normalize(50.), 0.);
+        assert_eq!(capabilities.normalize(1000.), 1.);
+        assert_eq!(FocusCapabilitiesAbsolute::new(5., 5., 1.).normalize(5.), 0.);
+    }
+
+    #[test]
+    fn tolerance_is_step_or_one_percent() {
+        // Coarse steps win
+        assert_eq!(FocusCapabilitiesAbsolute::new(0., 100., 5.).tolerance(), 5.);
+        // Fine steps are widened to 1% of the range
+        assert_eq!(
+            FocusCapabilitiesAbsolute::new(0., 100., 0.5).tolerance(),
+            1.
+        );
+        assert!((FocusCapabilitiesAbsolute::new(0., 1., 0.001).tolerance() - 0.01).abs() < 1e-6);
+    }
+}
@@ -1,11 +1,34 @@
 use crate::{
-    utils::{focus::*, request::*},
-    AdditionalConfiguration, IpCamerasError,
+    utils::{
+        focus::*,
+        onvif_connection::connect_onvif,
+        poll::{cancellable, poll_until, poll_with_backoff},
+        ptz::*,
+        request::*,
+        status::{CachedState, SystemStatus},
+        transport::*,
+    },
+    AdditionalConfiguration, DayNightMode, GrayScaleMode, ImageAdjustment, ImageProfile,
+    ImageSettings, IpCamerasError, SpotlightMode, SupplementMode, TempRangeMode, WhiteBalanceMode,
+    DEFAULT_TIMEOUT,
 };
 
-use onvif::FpsValue;
+use onvif::{FpsValue, OnvifConnection};
+
+use std::time::Duration;
 
 use async_trait::*;
+use tokio_util::sync::CancellationToken;
+
+// Used by model `Debug` impls so that passwords never reach the logs
+pub fn redact(secret: &Option<String>) -> Option<&'static str> {
+    secret.as_ref().map(|_| "***")
+}
+
+// Custom header values may carry tokens, only the names are logged
+pub fn redact_headers(headers: &[Header]) -> Vec<&str> {
+    headers.iter().map(Header::name).collect()
+}
 
 #[async_trait]
 pub trait ApiHandler {
@@ -14,11 +37,58 @@ pub trait ApiHandler {
     fn host(&self) -> &str {
         "127.0.0.1"
     }
+    fn timeout(&self) -> u64 {
+        DEFAULT_TIMEOUT
+    }
+    // Sent with every request on top of the per-call ones
+    fn extra_headers(&self) -> &[Header] {
+        &[]
+    }
+    fn merge_headers(&self, headers: Option<Vec<Header>>) -> Option<Vec<Header>> {
+        let mut headers = headers.unwrap_or_default();
+        headers.extend_from_slice(self.extra_headers());
+
+        (!headers.is_empty()).then_some(headers)
+    }
+    // Whether the configured role allows changing the camera settings
+    fn can_write(&self) -> bool {
+        true
+    }
+    fn cached_state(&self) -> CachedState {
+        CachedState::default()
+    }
 
     //INIT
     async fn init(&self) -> Result<(), IpCamerasError> {
         Ok(())
     }
+    async fn ensure_initialized(&self) -> Result<(), IpCamerasError> {
+        Ok(())
+    }
+
+    //ONVIF
+    // Retry unsupported vendor calls over ONVIF (see `CameraModelHttp`)
+    fn onvif_fallback(&self) -> bool {
+        false
+    }
+    async fn init_onvif(&self) -> Result<OnvifConnection, IpCamerasError> {
+        self.init_onvif_cancellable(&CancellationToken::new()).await
+    }
+    async fn init_onvif_cancellable(
+        &self,
+        cancel: &CancellationToken,
+    ) -> Result<OnvifConnection, IpCamerasError> {
+        let (user, password) = self.auth();
+
+        connect_onvif(
+            Some(self.host().to_string()),
+            Some(user.to_string()),
+            Some(password.to_string()),
+            self.timeout(),
+            cancel,
+        )
+        .await
+    }
 
     //HTTP REQUEST TO CAMERA
     async fn request(
@@ -36,7 +106,57 @@ pub trait ApiHandler {
             params,
             (Some(user.to_string()), Some(password.to_string())),
             method,
-            headers,
+            self.merge_headers(headers),
+        )
+        .await
+    }
+    async fn check_credentials(&self, url: String) -> Result<bool, IpCamerasError> {
+        let (user, password) = self.auth();
+
+        check_credentials(
+            url,
+            (Some(user.to_string()), Some(password.to_string())),
+            self.merge_headers(None),
+        )
+        .await
+    }
+    // For long operations, quick calls go through `request`
+    async fn request_with_timeout(
+        &self,
+        url: String,
+        params: Option<String>,
+        method: Method,
+        headers: Option<Vec<Header>>,
+        timeout_override: Option<Duration>,
+    ) -> Result<String, IpCamerasError> {
+        let (user, password) = self.auth();
+
+        request_with_timeout(
+            RequestType::Reqwest,
+            url,
+            params,
+            (Some(user.to_string()), Some(password.to_string())),
+            method,
+            self.merge_headers(headers),
+            timeout_override,
+        )
+        .await
+    }
+    // Binary GET, e.g. a JPEG snapshot
+    async fn request_bytes(
+        &self,
+        url: String,
+        headers: Option<Vec<Header>>,
+        timeout_override: Option<Duration>,
+    ) -> Result<Vec<u8>, IpCamerasError> {
+        let (user, password) = self.auth();
+
+        request_bytes(
+            RequestType::Reqwest,
+            url,
+            (Some(user.to_string()), Some(password.to_string())),
+            self.merge_headers(headers),
+            timeout_override,
         )
         .await
     }
@@ -63,11 +183,115 @@ pub trait ApiHandler {
     async fn set_focus_absolute(&self, _: FocusValue) -> Result<(), IpCamerasError> {
         Err(IpCamerasError::NotAvialiableApi)
     }
+    // Some models poll the lens until it settles
+    async fn get_focus_absolute_cancellable(
+        &self,
+        cancel: &CancellationToken,
+    ) -> Result<FocusValue, IpCamerasError> {
+        cancellable(cancel, self.get_focus_absolute()).await
+    }
+    async fn get_focus_normalized(&self) -> Result<FocusValue, IpCamerasError> {
+        let capabilities = self
+            .get_focus_capabilities()
+            .await?
+            .absolute
+            .ok_or(IpCamerasError::NotAvialiableApi)?;
+
+        Ok(capabilities.normalize(self.get_focus_absolute().await?))
+    }
+    // Applies the daytime focus of a stored record and reads it back until it is
+    // within the capability tolerance. Continuous focus has no target to confirm.
+    async fn apply_and_confirm_focus(
+        &self,
+        settings: FocusSettings,
+    ) -> Result<FocusValue, IpCamerasError> {
+        const RETRIES: usize = 5;
+        const INTERVAL: u64 = 500;
+
+        let capabilities = self
+            .get_focus_capabilities()
+            .await?
+            .absolute
+            .ok_or(IpCamerasError::NotAvialiableApi)?;
+        let tolerance = capabilities.tolerance();
+
+        let expected = match settings.focus {
+            TypedFocus::Absolute(value) => {
+                self.set_focus_absolute(value).await?;
+                value
+            }
+            TypedFocus::Relative(step) => {
+                let current = self.get_focus_absolute().await?;
+                self.set_focus_relative(step).await?;
+                (current + step).clamp(capabilities.min, capabilities.max)
+            }
+            TypedFocus::Continuous(_) => {
+                return Err(IpCamerasError::InvalidParam(
+                    "continuous focus can't be confirmed".to_string(),
+                ))
+            }
+        };
+
+        let confirmed = poll_until(
+            || self.get_focus_absolute(),
+            |actual| (actual - expected).abs() <= tolerance,
+            RETRIES,
+            Duration::from_millis(INTERVAL),
+        )
+        .await;
+
+        match confirmed {
+            Err(IpCamerasError::Timeout) => Err(IpCamerasError::FocusMismatch {
+                expected,
+                actual: self.get_focus_absolute().await?,
+            }),
+            result => result,
+        }
+    }
+    async fn apply_and_confirm_focus_cancellable(
+        &self,
+        settings: FocusSettings,
+        cancel: &CancellationToken,
+    ) -> Result<FocusValue, IpCamerasError> {
+        cancellable(cancel, self.apply_and_confirm_focus(settings)).await
+    }
+
+    //PTZ FUNCTIONS
+    async fn is_ptz(&self) -> Result<bool, IpCamerasError> {
+        Ok(false)
+    }
+    async fn ptz_absolute_move(&self, _: PtzVector) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn ptz_relative_move(&self, _: PtzVector) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn ptz_continuous_move(&self, _: PtzVector) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn ptz_stop(&self) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn get_proportional_pan(&self) -> Result<bool, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_proportional_pan(&self, _: bool) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
 
     //DATE AND TIME FUNCTIONS
-    async fn set_date_time(&self, _: chrono::NaiveDateTime) -> Result<(), IpCamerasError> {
+    async fn set_date_time(
+        &self,
+        _: chrono::DateTime<chrono::FixedOffset>,
+    ) -> Result<(), IpCamerasError> {
         Err(IpCamerasError::NotAvialiableApi)
     }
+    // Host wall clock with its local offset, so zone-aware models keep the zone too
+    async fn sync_time_to_host(&self) -> Result<(), IpCamerasError> {
+        let now = chrono::Local::now();
+
+        self.set_date_time(now.with_timezone(now.offset())).await
+    }
 
     //FPS FUNCTIONS
     async fn get_fps(&self) -> Result<FpsValue, IpCamerasError> {
@@ -76,6 +300,34 @@ pub trait ApiHandler {
     async fn set_fps(&self, _: FpsValue) -> Result<(), IpCamerasError> {
         Err(IpCamerasError::NotAvialiableApi)
     }
+    async fn get_supported_fps(&self) -> Result<Vec<FpsValue>, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+
+    //STREAMING TRANSPORT FUNCTIONS
+    async fn get_transport(&self) -> Result<TransportInfo, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_transport(
+        &self,
+        _: bool,
+        _: Option<MulticastConfig>,
+    ) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn get_rtsp_port(&self) -> Result<u32, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_rtsp_port(&self, _: u32) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    // Models without an RTSP port API are probed on the standard port
+    async fn probe_stream(&self) -> Result<bool, IpCamerasError> {
+        let port = IpCamerasError::unsupported_as_none(self.get_rtsp_port().await)?
+            .unwrap_or(DEFAULT_RTSP_PORT);
+
+        Ok(probe_rtsp(self.host(), port, self.timeout()).await)
+    }
 
     //SWITCH AND GET SPOTIGHT FUNCTIONS
     async fn get_spotlight_state(&self) -> Result<bool, IpCamerasError> {
@@ -84,6 +336,172 @@ pub trait ApiHandler {
     async fn switch_spotlight(&self, _: bool) -> Result<(), IpCamerasError> {
         Err(IpCamerasError::NotAvialiableApi)
     }
+    // Costs an extra read, but skips the write when the light is already in the desired state
+    async fn switch_spotlight_if_changed(&self, enabled: bool) -> Result<(), IpCamerasError> {
+        match self.get_spotlight_state().await {
+            Ok(state) if state == enabled => Ok(()),
+            Ok(_) | Err(IpCamerasError::NotAvialiableApi) => self.switch_spotlight(enabled).await,
+            Err(e) => Err(e),
+        }
+    }
+    async fn set_spotlight_mode(&self, _: SpotlightMode) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_supplement_light(
+        &self,
+        _: SupplementMode,
+        _: Option<i32>,
+    ) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+
+    //DEVICE INFO FUNCTIONS
+    // `false` when the camera rejects the credentials, an error when it can't be reached
+    async fn verify_credentials(&self) -> Result<bool, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    // Includes both digest round-trips (challenge and authenticated request)
+    // and the rate limiter wait, as a client call would see it. Rejected
+    // credentials still complete the round-trip, so they are timed too.
+    async fn measure_latency(&self) -> Result<Duration, IpCamerasError> {
+        let started = std::time::Instant::now();
+        self.verify_credentials().await?;
+
+        Ok(started.elapsed())
+    }
+    async fn set_device_name(&self, _: &str) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_device_location(&self, _: &str) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    // After a restart: any answer, even rejected credentials, means the camera is up
+    async fn wait_until_online(&self, timeout: Duration) -> Result<(), IpCamerasError> {
+        poll_with_backoff(|| self.verify_credentials(), timeout)
+            .await
+            .map(|_| ())
+    }
+    async fn wait_until_online_cancellable(
+        &self,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<(), IpCamerasError> {
+        cancellable(cancel, self.wait_until_online(timeout)).await
+    }
+
+    //IMAGE FUNCTIONS
+    // JPEG of the main stream, fetched with `LONG_REQUEST_TIMEOUT`
+    async fn get_snapshot(&self) -> Result<Vec<u8>, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_jpeg_params(
+        &self,
+        _: Option<i32>,
+        _: Option<bool>,
+        _: Option<i32>,
+    ) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_overexpose_suppress(&self, _: bool, _: Option<i32>) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_face_exposure(&self, _: bool, _: Option<i32>) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn get_bright_enhance(&self) -> Result<i32, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_bright_enhance(&self, _: i32) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_car_window_enhancement(
+        &self,
+        _: bool,
+        _: i32,
+        _: i32,
+    ) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn get_iris(&self) -> Result<i32, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    // Clamped to the limits the lens reports
+    async fn set_iris(&self, _: i32) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_image_loss_detection(&self, _: bool) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_mirror(&self, _: bool) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_rotation(&self, _: i32) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn get_capture_mode(&self) -> Result<String, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_capture_mode(&self, _: String) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_dss(&self, _: bool, _: Option<String>) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_plate_bright(
+        &self,
+        _: bool,
+        _: Option<i32>,
+        _: Option<i32>,
+    ) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_white_balance(
+        &self,
+        _: WhiteBalanceMode,
+        _: Option<i32>,
+        _: Option<i32>,
+    ) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_day_night_mode(&self, _: DayNightMode) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    // One read for a whole settings panel
+    async fn get_image_settings(&self) -> Result<ImageSettings, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_image_adjustment(&self, _: ImageAdjustment) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_gray_scale(&self, _: GrayScaleMode) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    // Thermal channels only, the limits are required in manual mode
+    async fn set_temp_range(
+        &self,
+        _: TempRangeMode,
+        _: Option<i32>,
+        _: Option<i32>,
+    ) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+
+    //TIME FUNCTIONS
+    async fn get_time_zone(&self) -> Result<String, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    async fn set_time_zone(&self, _: String) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+
+    //DIAGNOSTICS
+    async fn get_system_status(&self) -> Result<SystemStatus, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+    // Live settings for drift detection, compare snapshots with `diff_settings`
+    async fn get_settings_json(&self) -> Result<serde_json::Value, IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
 
     //SET AND GET ADDITIONAL CONFIGURATION
     async fn get_additional_configuration(
@@ -97,7 +515,271 @@ pub trait ApiHandler {
     ) -> Result<(), IpCamerasError> {
         Err(IpCamerasError::NotAvialiableApi)
     }
+    async fn apply_profile(&self, _: ImageProfile) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
     async fn get_default_configuration(&self) -> Result<AdditionalConfiguration, IpCamerasError> {
         Err(IpCamerasError::NotAvialiableApi)
     }
+    // Sends the defaults tuned for the `CameraRole` the model was built with
+    async fn apply_role_defaults(&self) -> Result<(), IpCamerasError> {
+        Err(IpCamerasError::NotAvialiableApi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering::Relaxed},
+            Mutex,
+        },
+        time::Duration,
+    };
+
+    use pulsar_core::prelude::*;
+
+    use super::*;
+
+    // Lens that always stops `drift` away from the requested position
+    struct Lens {
+        position: Mutex<FocusValue>,
+        drift: FocusValue,
+    }
+
+    impl Lens {
+        fn new(drift: FocusValue) -> Self {
+            Self {
+                position: Mutex::new(0.),
+                drift,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ApiHandler for Lens {
+        fn auth(&self) -> (&str, &str) {
+            ("admin", "admin")
+        }
+
+        async fn get_focus_capabilities(&self) -> Result<FocusCapabilities, IpCamerasError> {
+            Ok(FocusCapabilities::new().absolute(0., 100., 0.5))
+        }
+
+        async fn get_focus_absolute(&self) -> Result<FocusValue, IpCamerasError> {
+            Ok(*self.position.lock()?)
+        }
+
+        async fn set_focus_absolute(&self, focus: FocusValue) -> Result<(), IpCamerasError> {
+            *self.position.lock()? = focus + self.drift;
+            Ok(())
+        }
+    }
+
+    fn absolute(focus: FocusValue) -> FocusSettings {
+        FocusSettings {
+            focus: TypedFocus::Absolute(focus),
+            ..FocusSettings::new(1)
+        }
+    }
+
+    #[tokio::test]
+    async fn focus_is_normalized_by_capabilities() {
+        let lens = Lens::new(0.);
+        lens.set_focus_absolute(50.).await.unwrap();
+
+        assert_eq!(lens.get_focus_normalized().await.unwrap(), 0.5);
+    }
+
+    #[tokio::test]
+    async fn focus_confirmed_within_tolerance() {
+        let lens = Lens::new(0.5);
+
+        let focus = lens.apply_and_confirm_focus(absolute(40.)).await.unwrap();
+
+        assert_eq!(focus, 40.5);
+    }
+
+    #[tokio::test]
+    async fn focus_outside_tolerance_is_mismatch() {
+        let lens = Lens::new(5.);
+
+        let result = lens.apply_and_confirm_focus(absolute(40.)).await;
+
+        assert!(matches!(
+            result,
+            Err(IpCamerasError::FocusMismatch {
+                expected,
+                actual,
+            }) if expected == 40. && actual == 45.
+        ));
+    }
+
+    #[tokio::test]
+    async fn focus_poll_is_cancellable() {
+        let lens = Lens::new(5.);
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            trigger.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let result = lens
+            .apply_and_confirm_focus_cancellable(absolute(40.), &cancel)
+            .await;
+
+        assert!(matches!(result, Err(IpCamerasError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    // Spotlight counting its writes, `None` when the state can't be read
+    struct Light {
+        state: Option<bool>,
+        writes: Mutex<Vec<bool>>,
+    }
+
+    impl Light {
+        fn new(state: Option<bool>) -> Self {
+            Self {
+                state,
+                writes: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ApiHandler for Light {
+        fn auth(&self) -> (&str, &str) {
+            ("admin", "admin")
+        }
+
+        async fn get_spotlight_state(&self) -> Result<bool, IpCamerasError> {
+            self.state.ok_or(IpCamerasError::NotAvialiableApi)
+        }
+
+        async fn switch_spotlight(&self, enabled: bool) -> Result<(), IpCamerasError> {
+            self.writes.lock()?.push(enabled);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn matching_spotlight_state_is_not_written() {
+        let light = Light::new(Some(true));
+
+        light.switch_spotlight_if_changed(true).await.unwrap();
+
+        assert!(light.writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn changed_spotlight_state_is_written() {
+        let light = Light::new(Some(true));
+        light.switch_spotlight_if_changed(false).await.unwrap();
+        assert_eq!(*light.writes.lock().unwrap(), [false]);
+
+        // Without a readable state the write always goes through
+        let light = Light::new(None);
+        light.switch_spotlight_if_changed(true).await.unwrap();
+        assert_eq!(*light.writes.lock().unwrap(), [true]);
+    }
+
+    // Clock recording every time it is set to
+    #[derive(Default)]
+    struct Clock {
+        set: Mutex<Vec<chrono::DateTime<chrono::FixedOffset>>>,
+    }
+
+    #[async_trait]
+    impl ApiHandler for Clock {
+        fn auth(&self) -> (&str, &str) {
+            ("admin", "admin")
+        }
+
+        async fn set_date_time(
+            &self,
+            date_time: chrono::DateTime<chrono::FixedOffset>,
+        ) -> Result<(), IpCamerasError> {
+            self.set.lock()?.push(date_time);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn time_is_synced_to_the_host_clock() {
+        let clock = Clock::default();
+
+        let before = chrono::Local::now();
+        clock.sync_time_to_host().await.unwrap();
+        let after = chrono::Local::now();
+
+        let set = clock.set.lock().unwrap();
+        assert_eq!(set.len(), 1);
+        assert!(before <= set[0] && set[0] <= after, "{}", set[0]);
+        assert_eq!(set[0].offset(), after.offset());
+    }
+
+    #[tokio::test]
+    async fn time_sync_needs_set_date_time() {
+        let light = Light::new(None);
+
+        let result = light.sync_time_to_host().await;
+
+        assert!(matches!(result, Err(IpCamerasError::NotAvialiableApi)));
+    }
+
+    // Camera that refuses connections for its first `offline` checks
+    struct Rebooting {
+        offline: usize,
+        checks: AtomicUsize,
+    }
+
+    impl Rebooting {
+        fn new(offline: usize) -> Self {
+            Self {
+                offline,
+                checks: Default::default(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ApiHandler for Rebooting {
+        fn auth(&self) -> (&str, &str) {
+            ("admin", "admin")
+        }
+
+        async fn verify_credentials(&self) -> Result<bool, IpCamerasError> {
+            let check = self.checks.fetch_add(1, Relaxed);
+            if check < self.offline {
+                return Err(std::io::ErrorKind::ConnectionRefused.into());
+            }
+
+            // Rejected credentials still mean the camera is up
+            Ok(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn online_once_the_camera_answers() {
+        let camera = Rebooting::new(2);
+
+        camera
+            .wait_until_online(Duration::from_secs(10))
+            .await
+            .unwrap();
+
+        assert_eq!(camera.checks.load(Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn offline_camera_times_out() {
+        let camera = Rebooting::new(usize::MAX);
+
+        let result = camera.wait_until_online(Duration::from_millis(100)).await;
+
+        assert!(matches!(result, Err(IpCamerasError::Timeout)));
+    }
 }
@@ -0,0 +1,142 @@
+use std::future::Future;
+
+use crate::{CameraModelHttp, IpCamerasError};
+
+// With the `tracing` feature every `CameraModelHttp` operation runs in a `camera_operation`
+// span carrying the camera id, vendor, operation name, outcome and latency
+#[cfg(feature = "tracing")]
+pub(crate) async fn traced<T, F>(
+    camera: &CameraModelHttp,
+    operation: &'static str,
+    future: F,
+) -> Result<T, IpCamerasError>
+where
+    F: Future<Output = Result<T, IpCamerasError>>,
+{
+    use tracing::{field, Instrument};
+
+    let span = tracing::info_span!(
+        "camera_operation",
+        camera_id = ?camera.id(),
+        vendor = %camera.name(),
+        operation,
+        outcome = field::Empty,
+        latency_ms = field::Empty,
+    );
+
+    let started = std::time::Instant::now();
+    let result = future.instrument(span.clone()).await;
+
+    span.record("latency_ms", started.elapsed().as_millis() as u64);
+    match &result {
+        Ok(_) => span.record("outcome", "ok"),
+        Err(e) => span.record("outcome", field::display(e)),
+    };
+
+    result
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) async fn traced<T, F>(
+    _: &CameraModelHttp,
+    _: &'static str,
+    future: F,
+) -> Result<T, IpCamerasError>
+where
+    F: Future<Output = Result<T, IpCamerasError>>,
+{
+    future.await
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    use super::*;
+    use crate::{models::axis::AxisHttp, utils::mock::MockCamera};
+
+    type Spans = Arc<Mutex<Vec<(&'static str, HashMap<String, String>)>>>;
+
+    // Keeps every span with its fields, recorded values included
+    struct Recorder(Spans);
+
+    struct Fields<'a>(&'a mut HashMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = HashMap::new();
+            span.record(&mut Fields(&mut fields));
+            spans.push((span.metadata().name(), fields));
+
+            span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+            let mut spans = self.0.lock().unwrap();
+            let (_, fields) = &mut spans[id.into_u64() as usize - 1];
+            values.record(&mut Fields(fields));
+        }
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[tokio::test]
+    async fn get_fps_emits_a_camera_operation_span() {
+        let mock = MockCamera::start(vec![(
+            "GET /axis-cgi/param.cgi",
+            200,
+            "root.Image.I0.Stream.FPS=25\n".to_string(),
+        )])
+        .await;
+        let camera = CameraModelHttp::Axis(AxisHttp {
+            id: 7,
+            host: Some(mock.host.clone()),
+            ..Default::default()
+        });
+        let spans = Spans::default();
+        let _guard = tracing::subscriber::set_default(Recorder(spans.clone()));
+
+        camera.get_fps().await.unwrap();
+
+        let spans = spans.lock().unwrap();
+        let operations: Vec<_> = spans
+            .iter()
+            .filter(|(name, _)| *name == "camera_operation")
+            .map(|(_, fields)| fields)
+            .collect();
+        assert_eq!(operations.len(), 1);
+        let fields = operations[0];
+        assert_eq!(fields["operation"], r#""get_fps""#);
+        assert_eq!(fields["camera_id"], "Some(7)");
+        assert_eq!(fields["vendor"], camera.name());
+        assert_eq!(fields["outcome"], r#""ok""#);
+        assert!(fields.contains_key("latency_ms"), "{fields:?}");
+    }
+}
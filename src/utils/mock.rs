@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use pulsar_core::prelude::*;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+// Challenge sent to requests without credentials, digest auth answers it before the real request
+const CHALLENGE: &str =
+    r#"Digest realm="mock", qop="auth", nonce="6e6f6e6365", opaque="6f7061717565""#;
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    // Path with the query string
+    pub path: String,
+    pub body: String,
+    // From the digest `Authorization` header
+    pub username: Option<String>,
+}
+
+// Camera stand-in for model tests. Answers authenticated requests with the first route
+// whose `"METHOD /path"` prefix matches, 404 otherwise, and records them in order.
+#[derive(Clone)]
+pub struct MockCamera {
+    pub host: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockCamera {
+    pub async fn start(routes: Vec<(&'static str, u16, String)>) -> Self {
+        Self::spawn(routes, Duration::ZERO, false).await
+    }
+
+    // Every authenticated answer is held back by `delay`, as a slow camera would
+    pub async fn start_delayed(routes: Vec<(&'static str, u16, String)>, delay: Duration) -> Self {
+        Self::spawn(routes, delay, false).await
+    }
+
+    // A route listed several times answers with each entry in turn, the last one repeats.
+    // For cameras that change their answer, e.g. a lens busy for the first reads.
+    pub async fn start_sequenced(routes: Vec<(&'static str, u16, String)>) -> Self {
+        Self::spawn(routes, Duration::ZERO, true).await
+    }
+
+    async fn spawn(
+        routes: Vec<(&'static str, u16, String)>,
+        delay: Duration,
+        sequenced: bool,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let routes = Arc::new(Routes {
+            routes,
+            hits: sequenced.then(Default::default),
+        });
+
+        tokio::spawn({
+            let requests = requests.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, routes.clone(), requests.clone(), delay));
+                }
+            }
+        });
+
+        Self { host, requests }
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    // Bodies of the authenticated requests with the given method
+    pub fn bodies(&self, method: &str) -> Vec<String> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.method == method)
+            .map(|r| r.body)
+            .collect()
+    }
+}
+
+struct Routes {
+    routes: Vec<(&'static str, u16, String)>,
+    // Answers served so far per route, only kept when sequenced
+    hits: Option<Mutex<HashMap<&'static str, usize>>>,
+}
+
+impl Routes {
+    fn answer(&self, request: &str) -> (u16, &str) {
+        let route = match self
+            .routes
+            .iter()
+            .find(|(route, _, _)| request.starts_with(route))
+        {
+            Some((route, _, _)) => *route,
+            None => return (404, ""),
+        };
+        let entries: Vec<_> = self.routes.iter().filter(|(r, _, _)| *r == route).collect();
+
+        let turn = match &self.hits {
+            Some(hits) => {
+                let mut hits = hits.lock().unwrap();
+                let hit = hits.entry(route).or_default();
+                *hit += 1;
+                (*hit - 1).min(entries.len() - 1)
+            }
+            None => 0,
+        };
+        let (_, status, answer) = entries[turn];
+
+        (*status, answer.as_str())
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    routes: Arc<Routes>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    delay: Duration,
+) {
+    let mut buf = Vec::new();
+
+    // Keep-alive: several requests may come over one connection
+    while let Some((head, body)) = read_request(&mut stream, &mut buf).await {
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+        let method = request_line.next().unwrap_or_default().to_string();
+        let path = request_line.next().unwrap_or_default().to_string();
+        let authorization = lines.find(|l| l.to_ascii_lowercase().starts_with("authorization:"));
+
+        let response = if authorization.is_none() {
+            format!(
+                "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: {CHALLENGE}\r\nContent-Length: 0\r\n\r\n"
+            )
+        } else {
+            let request = format!("{method} {path}");
+            let (status, answer) = routes.answer(&request);
+
+            let username = authorization
+                .and_then(|l| l.split("username=\"").nth(1))
+                .and_then(|rest| rest.split('"').next())
+                .map(str::to_string);
+            requests.lock().unwrap().push(MockRequest {
+                method,
+                path,
+                body,
+                username,
+            });
+            tokio::time::sleep(delay).await;
+
+            format!(
+                "HTTP/1.1 {status} Mock\r\nContent-Length: {}\r\n\r\n{answer}",
+                answer.len()
+            )
+        };
+
+        if stream.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+// Head and body of the next request, `None` once the client hangs up
+async fn read_request(stream: &mut TcpStream, buf: &mut Vec<u8>) -> Option<(String, String)> {
+    let mut chunk = [0u8; 4096];
+
+    let head_end = loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return None,
+            Ok(len) => buf.extend_from_slice(&chunk[..len]),
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let body_len = head
+        .lines()
+        .find_map(|l| {
+            let (name, value) = l.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())?
+        })
+        .unwrap_or(0);
+
+    while buf.len() < head_end + body_len {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return None,
+            Ok(len) => buf.extend_from_slice(&chunk[..len]),
+        }
+    }
+
+    let body = String::from_utf8_lossy(&buf[head_end..head_end + body_len]).to_string();
+    buf.drain(..head_end + body_len);
+
+    Some((head, body))
+}
@@ -1,5 +1,19 @@
+pub mod cache;
+pub mod diff;
+pub mod discovery;
 pub mod focus;
 pub mod handler;
+pub mod instrument;
+#[cfg(test)]
+pub mod mock;
+pub mod onvif_connection;
+pub mod poll;
+pub mod ptz;
+pub mod rate_limit;
+pub mod registry;
 pub mod request;
 pub mod serde;
+pub mod status;
+pub mod time_zone;
+pub mod transport;
 pub mod error;
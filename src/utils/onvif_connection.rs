@@ -0,0 +1,41 @@
+use onvif::{ok_or_explain, OnvifConnection, OnvifParams};
+use pulsar_core::prelude::*;
+use tokio::time::{timeout, Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::{utils::poll::cancellable, IpCamerasError};
+
+// `OnvifConnection::new` probes the device and would hang on an unreachable host
+pub async fn connect_onvif(
+    host: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    timeout_secs: u64,
+    cancel: &CancellationToken,
+) -> Result<OnvifConnection, IpCamerasError> {
+    let onvif_params = OnvifParams {
+        host: host.clone(),
+        username,
+        password,
+        dummy: false,
+        post_process_status: None,
+    };
+
+    let connect = async {
+        timeout(
+            Duration::from_secs(timeout_secs),
+            OnvifConnection::new(onvif_params),
+        )
+        .await
+        .map_err(|_| {
+            warn!(
+                "ONVIF connection to {} timed out",
+                host.as_deref().unwrap_or("unknown")
+            );
+            IpCamerasError::Timeout
+        })
+    };
+    let onvif_connection = cancellable(cancel, connect).await?;
+
+    Ok(ok_or_explain!(onvif_connection))
+}
@@ -0,0 +1,230 @@
+use std::future::Future;
+
+use futures::stream::{self, Stream};
+use pulsar_core::prelude::*;
+use tokio::time::{sleep, timeout_at, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::IpCamerasError;
+
+// Runs `operation` until it finishes or `cancel` fires, which yields `Cancelled`.
+// The dropped future releases what it holds, a spawned curl child included (see `r_curl`).
+pub async fn cancellable<T>(
+    cancel: &CancellationToken,
+    operation: impl Future<Output = Result<T, IpCamerasError>>,
+) -> Result<T, IpCamerasError> {
+    tokio::select! {
+        biased;
+
+        _ = cancel.cancelled() => {
+            trace!("operation cancelled");
+            Err(IpCamerasError::Cancelled)
+        }
+        result = operation => result,
+    }
+}
+
+// Repeats `op` until its output satisfies `predicate`, sleeping `interval` between tries.
+// Errors from `op` are returned immediately; running out of retries yields `Timeout`.
+pub async fn poll_until<T, F, Fut, P>(
+    mut op: F,
+    predicate: P,
+    retries: usize,
+    interval: Duration,
+) -> Result<T, IpCamerasError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, IpCamerasError>>,
+    P: Fn(&T) -> bool,
+{
+    for attempt in 1..=retries {
+        let value = op().await?;
+
+        if predicate(&value) {
+            return Ok(value);
+        }
+
+        if attempt < retries {
+            sleep(interval).await;
+        }
+    }
+
+    warn!("polled value did not settle after {} tries", retries);
+    Err(IpCamerasError::Timeout)
+}
+
+const BACKOFF_START: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(10);
+
+// Repeats `op` until it succeeds, doubling the pause between tries up to `BACKOFF_MAX`.
+// Failed tries are retried, except unsupported APIs which can't start succeeding;
+// reaching `timeout` (including inside a hung try) yields `Timeout`.
+pub async fn poll_with_backoff<T, F, Fut>(mut op: F, timeout: Duration) -> Result<T, IpCamerasError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, IpCamerasError>>,
+{
+    let deadline = Instant::now() + timeout;
+    let mut interval = BACKOFF_START;
+
+    loop {
+        match timeout_at(deadline, op()).await {
+            Ok(Ok(value)) => return Ok(value),
+            Ok(Err(e)) if e.is_unsupported() => return Err(e),
+            Ok(Err(e)) => trace!("poll try failed: {e}"),
+            Err(_) => break,
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        sleep(interval.min(remaining)).await;
+        interval = (interval * 2).min(BACKOFF_MAX);
+    }
+
+    warn!("poll did not succeed within {:?}", timeout);
+    Err(IpCamerasError::Timeout)
+}
+
+// Yields `op` every `interval`. The first error is yielded and ends the stream;
+// dropping the stream stops polling.
+pub fn poll_stream<T, F, Fut>(
+    op: F,
+    interval: Duration,
+) -> impl Stream<Item = Result<T, IpCamerasError>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, IpCamerasError>>,
+{
+    stream::unfold(
+        (op, true, false),
+        move |(mut op, first, failed)| async move {
+            if failed {
+                return None;
+            }
+
+            if !first {
+                sleep(interval).await;
+            }
+
+            let value = op().await;
+            let failed = value.is_err();
+
+            Some((value, (op, false, failed)))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(1);
+
+    #[tokio::test]
+    async fn poll_until_stabilizes_after_k_tries() {
+        let calls = AtomicUsize::new(0);
+
+        let value = poll_until(
+            || async { Ok(calls.fetch_add(1, Relaxed) + 1) },
+            |value| *value >= 3,
+            5,
+            TICK,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(value, 3);
+        assert_eq!(calls.load(Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn poll_until_gives_up_after_retries() {
+        let calls = AtomicUsize::new(0);
+
+        let result = poll_until(
+            || async {
+                calls.fetch_add(1, Relaxed);
+                Ok(0)
+            },
+            |value| *value > 0,
+            4,
+            TICK,
+        )
+        .await;
+
+        assert!(matches!(result, Err(IpCamerasError::Timeout)));
+        assert_eq!(calls.load(Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn poll_until_returns_errors_at_once() {
+        let calls = AtomicUsize::new(0);
+
+        let result: Result<usize, _> = poll_until(
+            || async {
+                calls.fetch_add(1, Relaxed);
+                Err(IpCamerasError::NotAvialiableApi)
+            },
+            |_| true,
+            4,
+            TICK,
+        )
+        .await;
+
+        assert!(matches!(result, Err(IpCamerasError::NotAvialiableApi)));
+        assert_eq!(calls.load(Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn cancellable_passes_the_result_through() {
+        let cancel = CancellationToken::new();
+
+        assert_eq!(cancellable(&cancel, async { Ok(5) }).await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn cancellable_stops_a_pending_operation() {
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            trigger.cancel();
+        });
+
+        let result = cancellable(&cancel, async {
+            sleep(Duration::from_secs(30)).await;
+            Ok(())
+        })
+        .await;
+
+        assert!(matches!(result, Err(IpCamerasError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn poll_stream_ends_after_the_first_error() {
+        use futures::StreamExt;
+
+        let calls = AtomicUsize::new(0);
+        let samples: Vec<_> = poll_stream(
+            || async {
+                match calls.fetch_add(1, Relaxed) {
+                    n if n < 2 => Ok(n),
+                    _ => Err(IpCamerasError::Timeout),
+                }
+            },
+            TICK,
+        )
+        .collect()
+        .await;
+
+        assert_eq!(samples.len(), 3);
+        assert!(matches!(samples[..2], [Ok(0), Ok(1)]));
+        assert!(matches!(samples[2], Err(IpCamerasError::Timeout)));
+        assert_eq!(calls.load(Relaxed), 3);
+    }
+}
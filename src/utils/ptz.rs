@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+// Normalized PTZ coordinates: pan and tilt in [-1.0, 1.0], zoom in [0.0, 1.0].
+// For relative moves and speeds every axis is in [-1.0, 1.0].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PtzVector {
+    pub pan: f32,
+    pub tilt: f32,
+    pub zoom: f32,
+}
+
+impl PtzVector {
+    pub fn new(pan: f32, tilt: f32, zoom: f32) -> Self {
+        Self { pan, tilt, zoom }
+    }
+
+    pub fn clamped(self, min: f32) -> Self {
+        Self {
+            pan: self.pan.clamp(-1., 1.),
+            tilt: self.tilt.clamp(-1., 1.),
+            zoom: self.zoom.clamp(min, 1.),
+        }
+    }
+}
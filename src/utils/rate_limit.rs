@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use pulsar_core::prelude::*;
+use tokio::{
+    sync::{Mutex as AsyncMutex, OwnedSemaphorePermit, Semaphore},
+    time::{sleep_until, Duration, Instant},
+};
+
+use crate::IpCamerasError;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub requests_per_second: Option<u32>,
+    pub max_in_flight: usize,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            requests_per_second: None,
+            max_in_flight: 1,
+        }
+    }
+}
+
+struct HostLimiter {
+    in_flight: Arc<Semaphore>,
+    min_interval: Option<Duration>,
+    next_slot: AsyncMutex<Instant>,
+}
+
+impl HostLimiter {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            in_flight: Arc::new(Semaphore::new(limit.max_in_flight.max(1))),
+            min_interval: limit
+                .requests_per_second
+                .filter(|rps| *rps > 0)
+                .map(|rps| Duration::from_secs(1) / rps),
+            next_slot: AsyncMutex::new(Instant::now()),
+        }
+    }
+
+    async fn acquire(&self) -> Result<OwnedSemaphorePermit, IpCamerasError> {
+        let permit = self
+            .in_flight
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| IpCamerasError::Sync)?;
+
+        if let Some(interval) = self.min_interval {
+            let mut next_slot = self.next_slot.lock().await;
+            sleep_until(*next_slot).await;
+            *next_slot = Instant::now() + interval;
+        }
+
+        Ok(permit)
+    }
+}
+
+type Limiters = Mutex<HashMap<String, Arc<HostLimiter>>>;
+
+fn limiters() -> &'static Limiters {
+    static LIMITERS: OnceLock<Limiters> = OnceLock::new();
+    LIMITERS.get_or_init(Default::default)
+}
+
+// Hosts without a configured limit are not throttled
+pub fn set_rate_limit(host: &str, limit: RateLimit) -> Result<(), IpCamerasError> {
+    limiters()
+        .lock()?
+        .insert(host.to_string(), Arc::new(HostLimiter::new(limit)));
+    Ok(())
+}
+
+pub fn remove_rate_limit(host: &str) -> Result<(), IpCamerasError> {
+    limiters().lock()?.remove(host);
+    Ok(())
+}
+
+// The returned permit must be held for the whole request
+pub(crate) async fn acquire(url: &str) -> Result<Option<OwnedSemaphorePermit>, IpCamerasError> {
+    let host = match reqwest::Url::parse(url) {
+        Ok(url) => unwrap_some!(url.host_str().map(str::to_string), return Ok(None)),
+        Err(_) => return Ok(None),
+    };
+
+    let limiter = unwrap_some!(limiters().lock()?.get(&host).cloned(), return Ok(None));
+    trace!("Waiting for rate limit slot on {}", host);
+
+    Ok(Some(limiter.acquire().await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+    use tokio::time::sleep;
+
+    use super::*;
+
+    fn in_flight(max_in_flight: usize) -> RateLimit {
+        RateLimit {
+            max_in_flight,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn single_slot_requests_do_not_overlap() {
+        set_rate_limit("serial.test", in_flight(1)).unwrap();
+        let running = Arc::new(AtomicUsize::new(0));
+        let overlapped = Arc::new(AtomicUsize::new(0));
+
+        let requests = (0..2).map(|_| {
+            let (running, overlapped) = (running.clone(), overlapped.clone());
+            tokio::spawn(async move {
+                let _permit = acquire("http://serial.test/ISAPI/Image/channels/1")
+                    .await
+                    .unwrap();
+                if running.fetch_add(1, SeqCst) > 0 {
+                    overlapped.fetch_add(1, SeqCst);
+                }
+                sleep(Duration::from_millis(50)).await;
+                running.fetch_sub(1, SeqCst);
+            })
+        });
+        for request in requests.collect::<Vec<_>>() {
+            request.await.unwrap();
+        }
+
+        assert_eq!(overlapped.load(SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn hosts_are_limited_separately() {
+        set_rate_limit("first.test", in_flight(1)).unwrap();
+        set_rate_limit("second.test", in_flight(1)).unwrap();
+
+        let _busy = acquire("http://first.test/").await.unwrap();
+        let other = tokio::time::timeout(
+            Duration::from_millis(100),
+            acquire("http://second.test:8080/"),
+        )
+        .await;
+
+        assert!(matches!(other, Ok(Ok(Some(_)))));
+    }
+
+    #[tokio::test]
+    async fn unknown_hosts_are_not_throttled() {
+        assert!(acquire("http://free.test/").await.unwrap().is_none());
+
+        set_rate_limit("removed.test", in_flight(1)).unwrap();
+        remove_rate_limit("removed.test").unwrap();
+        assert!(acquire("http://removed.test/").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn requests_per_second_spaces_requests() {
+        set_rate_limit(
+            "paced.test",
+            RateLimit {
+                requests_per_second: Some(10),
+                max_in_flight: 2,
+            },
+        )
+        .unwrap();
+
+        let start = Instant::now();
+        drop(acquire("http://paced.test/").await.unwrap());
+        drop(acquire("http://paced.test/").await.unwrap());
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
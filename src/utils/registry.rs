@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use domain::{stream::Resource, CameraId};
+use pulsar_core::prelude::*;
+
+use crate::{CameraModelHttp, IpCamerasError};
+
+struct Entry {
+    camera: Arc<CameraModelHttp>,
+    initialized_at: Instant,
+}
+
+// Initialized cameras shared by id, so short-lived callers skip `init()` round-trips.
+// An entry older than `max_age` is built and initialized again on the next `get`.
+pub struct CameraRegistry {
+    cameras: Mutex<HashMap<CameraId, Entry>>,
+    max_age: Duration,
+}
+
+impl CameraRegistry {
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            cameras: Mutex::new(HashMap::new()),
+            max_age,
+        }
+    }
+
+    pub async fn get(&self, resource: &Resource) -> Result<Arc<CameraModelHttp>, IpCamerasError> {
+        self.get_or_build(resource.id, || CameraModelHttp::from(resource.clone()))
+            .await
+    }
+
+    async fn get_or_build(
+        &self,
+        id: CameraId,
+        build: impl FnOnce() -> CameraModelHttp,
+    ) -> Result<Arc<CameraModelHttp>, IpCamerasError> {
+        let cached = self
+            .cameras
+            .lock()?
+            .get(&id)
+            .filter(|entry| entry.initialized_at.elapsed() < self.max_age)
+            .map(|entry| entry.camera.clone());
+        if let Some(camera) = cached {
+            return Ok(camera);
+        }
+
+        // Concurrent misses may both initialize, the last one is kept
+        let camera = build();
+        camera.init().await?;
+        trace!("Camera {id} initialized for the registry");
+
+        let camera = Arc::new(camera);
+        self.cameras.lock()?.insert(
+            id,
+            Entry {
+                camera: camera.clone(),
+                initialized_at: Instant::now(),
+            },
+        );
+
+        Ok(camera)
+    }
+
+    // Handed-out references stay usable, only the next `get` builds a new instance
+    pub fn evict(&self, id: CameraId) -> Result<Option<Arc<CameraModelHttp>>, IpCamerasError> {
+        Ok(self.cameras.lock()?.remove(&id).map(|entry| entry.camera))
+    }
+
+    // Returns how many entries were dropped
+    pub fn evict_stale(&self) -> Result<usize, IpCamerasError> {
+        let mut cameras = self.cameras.lock()?;
+        let before = cameras.len();
+        cameras.retain(|_, entry| entry.initialized_at.elapsed() < self.max_age);
+
+        Ok(before - cameras.len())
+    }
+
+    pub fn clear(&self) -> Result<(), IpCamerasError> {
+        self.cameras.lock()?.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    use super::*;
+    use crate::models::axis::AxisHttp;
+
+    async fn get(
+        registry: &CameraRegistry,
+        id: CameraId,
+        builds: &AtomicUsize,
+    ) -> Arc<CameraModelHttp> {
+        registry
+            .get_or_build(id, || {
+                builds.fetch_add(1, Relaxed);
+                CameraModelHttp::Axis(AxisHttp {
+                    id,
+                    ..Default::default()
+                })
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn same_id_reuses_the_initialized_camera() {
+        let registry = CameraRegistry::new(Duration::from_secs(60));
+        let builds = AtomicUsize::new(0);
+
+        let first = get(&registry, 1, &builds).await;
+        let second = get(&registry, 1, &builds).await;
+        let other = get(&registry, 2, &builds).await;
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &other));
+        assert_eq!(builds.load(Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn stale_cameras_are_rebuilt() {
+        let registry = CameraRegistry::new(Duration::ZERO);
+        let builds = AtomicUsize::new(0);
+
+        let first = get(&registry, 1, &builds).await;
+        let second = get(&registry, 1, &builds).await;
+
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(builds.load(Relaxed), 2);
+        assert_eq!(registry.evict_stale().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn evicted_cameras_are_rebuilt() {
+        let registry = CameraRegistry::new(Duration::from_secs(60));
+        let builds = AtomicUsize::new(0);
+
+        let first = get(&registry, 1, &builds).await;
+        let evicted = registry.evict(1).unwrap().unwrap();
+        assert!(Arc::ptr_eq(&first, &evicted));
+        assert!(registry.evict(1).unwrap().is_none());
+
+        let second = get(&registry, 1, &builds).await;
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(builds.load(Relaxed), 2);
+    }
+}
@@ -1,8 +1,17 @@
+use std::{sync::OnceLock, time::Duration};
+
 use digest::DigestAuth;
 use pulsar_core::prelude::*;
-use reqwest::Client;
+use reqwest::{header::LOCATION, redirect, Client, RequestBuilder, Response, StatusCode};
+
+use crate::{utils::rate_limit, IpCamerasError};
+
+// Quick calls keep the transport defaults, long operations (snapshot) pass
+// `LONG_REQUEST_TIMEOUT` or their own value to `request_with_timeout`/`request_bytes`
+pub const CURL_DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+pub const LONG_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
 
-use crate::IpCamerasError;
+const MAX_REDIRECTS: usize = 10;
 
 #[derive(Debug, Clone, Copy)]
 #[allow(dead_code)]
@@ -12,18 +21,42 @@ pub enum RequestType {
     All,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum Header {
     JSON,
+    AcceptJSON,
+    AcceptXML,
+    // For firmwares that send broken compressed bodies
+    NoCompression,
+    UserAgent(String),
+    Custom(String, String),
 }
 
 impl Header {
-    pub fn to_curl(&self) -> &str {
+    pub fn name(&self) -> &str {
         use Header::*;
 
         match self {
-            JSON => "Content-Type: application/json",
+            JSON => "Content-Type",
+            AcceptJSON | AcceptXML => "Accept",
+            NoCompression => "Accept-Encoding",
+            UserAgent(_) => "User-Agent",
+            Custom(name, _) => name.as_str(),
+        }
+    }
+
+    #[cfg(feature = "curl-transport")]
+    pub fn to_curl(&self) -> String {
+        use Header::*;
+
+        match self {
+            JSON => "Content-Type: application/json".to_string(),
+            AcceptJSON => "Accept: application/json".to_string(),
+            AcceptXML => "Accept: application/xml".to_string(),
+            NoCompression => "Accept-Encoding: identity".to_string(),
+            UserAgent(agent) => format!("User-Agent: {agent}"),
+            Custom(name, value) => format!("{name}: {value}"),
         }
     }
 }
@@ -67,61 +100,170 @@ pub async fn request(
     auth: (Option<String>, Option<String>),
     method: Method,
     headers: Option<Vec<Header>>,
+) -> Result<String, IpCamerasError> {
+    request_with_timeout(rt, url, params, auth, method, headers, None).await
+}
+
+pub async fn request_with_timeout(
+    rt: RequestType,
+    url: String,
+    params: Option<String>,
+    auth: (Option<String>, Option<String>),
+    method: Method,
+    headers: Option<Vec<Header>>,
+    timeout_override: Option<Duration>,
 ) -> Result<String, IpCamerasError> {
     use RequestType::*;
+
+    let _permit = rate_limit::acquire(&url).await?;
+
     match rt {
-        Reqwest => r_reqwest(url, params, auth, method, headers).await,
-        Curl => r_curl(url, params, auth, method, headers).await,
+        Reqwest => r_reqwest(url, params, auth, method, headers, timeout_override).await,
+        #[cfg(not(feature = "curl-transport"))]
+        Curl | All => Err(IpCamerasError::TransportDisabled("curl")),
+        #[cfg(feature = "curl-transport")]
+        Curl => r_curl(url, params, auth, method, headers, timeout_override).await,
+        #[cfg(feature = "curl-transport")]
         All => r_curl(
             url.clone(),
             params.clone(),
             auth.clone(),
             method,
             headers.clone(),
+            timeout_override,
         )
         .await
-        .and(r_reqwest(url, params, auth, method, headers).await),
+        .and(r_reqwest(url, params, auth, method, headers, timeout_override).await),
     }
 }
 
+// GET returning the raw body, for binary payloads that aren't valid UTF-8
+pub async fn request_bytes(
+    rt: RequestType,
+    url: String,
+    auth: (Option<String>, Option<String>),
+    headers: Option<Vec<Header>>,
+    timeout_override: Option<Duration>,
+) -> Result<Vec<u8>, IpCamerasError> {
+    use RequestType::*;
+
+    let _permit = rate_limit::acquire(&url).await?;
+
+    match rt {
+        Reqwest => {
+            let response =
+                reqwest_send(url, None, auth, Method::GET, headers, timeout_override).await?;
+
+            Ok(response.bytes().await?.to_vec())
+        }
+        #[cfg(not(feature = "curl-transport"))]
+        Curl | All => Err(IpCamerasError::TransportDisabled("curl")),
+        #[cfg(feature = "curl-transport")]
+        Curl | All => curl_output(url, None, auth, Method::GET, headers, timeout_override).await,
+    }
+}
+
+// Authenticated GET that only looks at the status: `false` on 401/403,
+// transport failures stay errors so bad credentials and unreachable differ
+pub async fn check_credentials(
+    url: String,
+    auth: (Option<String>, Option<String>),
+    headers: Option<Vec<Header>>,
+) -> Result<bool, IpCamerasError> {
+    let _permit = rate_limit::acquire(&url).await?;
+
+    let rb = reqwest_builder(client(), url, None, Method::GET, headers, None)?;
+    let rb = match auth {
+        (Some(username), Some(password)) => rb.digest_auth(&username, &password).await?,
+        _ => rb,
+    };
+    let status = not_redirected(rb.send().await?)?.status();
+
+    Ok(!matches!(
+        status,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+    ))
+}
+
+// Spawns the `curl` binary, compiled out without the `curl-transport` feature.
+// The child is killed when the future is dropped, e.g. by `poll::cancellable`.
+#[cfg(feature = "curl-transport")]
 pub async fn r_curl(
     url: String,
     params: Option<String>,
     auth: (Option<String>, Option<String>),
     method: Method,
     headers: Option<Vec<Header>>,
+    timeout_override: Option<Duration>,
 ) -> Result<String, IpCamerasError> {
+    let output = curl_output(url, params, auth, method, headers, timeout_override).await?;
+
+    Ok(String::from_utf8(output).map_err(|source| IpCamerasError::Utf8 { source })?)
+}
+
+#[cfg(feature = "curl-transport")]
+async fn curl_output(
+    url: String,
+    params: Option<String>,
+    auth: (Option<String>, Option<String>),
+    method: Method,
+    headers: Option<Vec<Header>>,
+    timeout_override: Option<Duration>,
+) -> Result<Vec<u8>, IpCamerasError> {
     let mut cmd = tokio::process::Command::new("curl");
+    cmd.kill_on_drop(true);
+    cmd.args(curl_args(
+        url,
+        params,
+        auth,
+        method,
+        headers,
+        timeout_override,
+    ));
+
+    Ok(cmd
+        .output()
+        .await
+        .map_err(|source| IpCamerasError::Std { source })?
+        .stdout)
+}
 
-    cmd.arg(url).arg("-X").arg(method.to_string());
+#[cfg(feature = "curl-transport")]
+fn curl_args(
+    url: String,
+    params: Option<String>,
+    auth: (Option<String>, Option<String>),
+    method: Method,
+    headers: Option<Vec<Header>>,
+    timeout_override: Option<Duration>,
+) -> Vec<String> {
+    let mut args = vec![url, "-X".to_string(), method.to_string()];
 
-    if params.is_some() {
-        cmd.arg("-d").arg(params.unwrap());
+    if let Some(params) = params {
+        args.extend(["-d".to_string(), params]);
     }
 
-    if headers.is_some() {
-        let headers = headers.unwrap();
+    let headers = headers.unwrap_or_default();
+    for h in headers.iter() {
+        args.extend(["-H".to_string(), h.to_curl()]);
+    }
 
-        for h in headers {
-            cmd.arg("-H").arg(h.to_curl());
-        }
+    if !headers.iter().any(|h| matches!(h, Header::NoCompression)) {
+        args.push("--compressed".to_string());
     }
 
-    // Wait for 5 second
-    cmd.arg("--max-time").arg(5u32.to_string());
+    let timeout = timeout_override.unwrap_or(CURL_DEFAULT_TIMEOUT);
+    args.extend(["--max-time".to_string(), timeout.as_secs_f32().to_string()]);
 
     if let (Some(username), Some(password)) = auth {
-        let auth = format!("{}:{}", username, password);
-        cmd.arg("--digest").arg("--user").arg(auth);
+        args.extend([
+            "--digest".to_string(),
+            "--user".to_string(),
+            format!("{}:{}", username, password),
+        ]);
     }
 
-    let output = cmd
-        .output()
-        .await
-        .map_err(|source| IpCamerasError::Std { source })?
-        .stdout;
-
-    Ok(String::from_utf8(output).map_err(|source| IpCamerasError::Utf8 { source })?)
+    args
 }
 
 pub async fn r_reqwest(
@@ -130,15 +272,47 @@ pub async fn r_reqwest(
     auth: (Option<String>, Option<String>),
     method: Method,
     headers: Option<Vec<Header>>,
+    timeout_override: Option<Duration>,
 ) -> Result<String, IpCamerasError> {
+    Ok(
+        reqwest_send(url, params, auth, method, headers, timeout_override)
+            .await?
+            .text()
+            .await?,
+    )
+}
+
+async fn reqwest_send(
+    url: String,
+    params: Option<String>,
+    auth: (Option<String>, Option<String>),
+    method: Method,
+    headers: Option<Vec<Header>>,
+    timeout_override: Option<Duration>,
+) -> Result<Response, IpCamerasError> {
+    let rb = reqwest_builder(client(), url, params, method, headers, timeout_override)?;
+
+    let rb = match auth {
+        (Some(username), Some(password)) => rb.digest_auth(&username, &password).await?,
+        _ => rb,
+    };
+
+    not_redirected(rb.send().await?)
+}
+
+fn reqwest_builder(
+    client: &Client,
+    url: String,
+    params: Option<String>,
+    method: Method,
+    headers: Option<Vec<Header>>,
+    timeout_override: Option<Duration>,
+) -> Result<RequestBuilder, IpCamerasError> {
     use Method::*;
 
-    let client = Client::new();
     let params = params.unwrap_or_default();
 
-    let (username, password) = auth;
-
-    let rb = match method {
+    let mut rb = match method {
         GET => client.get(url),
         PUT => client.put(url).body(params),
         POST => client.post(url).body(params),
@@ -150,23 +324,343 @@ pub async fn r_reqwest(
         }
     };
 
-    let mut rb = if let Some(h_s) = headers {
-        let mut rb_h = rb;
-        for h in h_s {
-            rb_h = match h {
-                Header::JSON => rb_h.header(reqwest::header::CONTENT_TYPE, "application/json"),
-            };
-        }
+    for h in headers.unwrap_or_default() {
+        rb = match h {
+            Header::JSON => rb.header(reqwest::header::CONTENT_TYPE, "application/json"),
+            Header::AcceptJSON => rb.header(reqwest::header::ACCEPT, "application/json"),
+            Header::AcceptXML => rb.header(reqwest::header::ACCEPT, "application/xml"),
+            Header::NoCompression => rb.header(reqwest::header::ACCEPT_ENCODING, "identity"),
+            Header::UserAgent(agent) => rb.header(reqwest::header::USER_AGENT, agent),
+            Header::Custom(name, value) => rb.header(name, value),
+        };
+    }
 
-        rb_h
-    } else {
-        rb
-    };
+    if let Some(timeout) = timeout_override {
+        rb = rb.timeout(timeout);
+    }
 
-    rb = match (username.as_ref(), password.as_ref()) {
-        (Some(username), Some(password)) => rb.digest_auth(&username, &password).await?,
-        _ => rb,
+    Ok(rb)
+}
+
+// A redirect that reached the caller was stopped by the client policy (see `client`)
+fn not_redirected(response: Response) -> Result<Response, IpCamerasError> {
+    if !response.status().is_redirection() {
+        return Ok(response);
+    }
+
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|l| l.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    warn!("{} redirects to {}", response.url(), location);
+
+    Err(IpCamerasError::Redirected { location })
+}
+
+// Shared between requests so the connection pool is reused; gzip/deflate bodies are decoded transparently
+// Redirects are only followed within the same origin: digest auth isn't re-applied across
+// scheme or host changes (typically HTTP to HTTPS), so those surface as `Redirected` instead.
+fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .gzip(true)
+            .deflate(true)
+            .redirect(redirect::Policy::custom(|attempt| {
+                let same_origin = attempt.previous().last().map_or(true, |previous| {
+                    previous.scheme() == attempt.url().scheme()
+                        && previous.host_str() == attempt.url().host_str()
+                        && previous.port_or_known_default() == attempt.url().port_or_known_default()
+                });
+
+                if !same_origin {
+                    attempt.stop()
+                } else if attempt.previous().len() > MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()
+            .unwrap_or_else(|e| {
+                warn!("Can't build HTTP client: {}. Fallback to default.", e);
+                Client::new()
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::oneshot,
     };
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::utils::poll::cancellable;
+
+    const OK: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+
+    const GZIP_XML: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: application/xml\r\nContent-Encoding: gzip\r\nContent-Length: 52\r\n\r\n\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\xff\xb3\x09\xc9\xcc\x4d\xb5\xb3\x29\x01\x92\x51\xf9\x79\xa9\x76\xce\xc1\x21\xba\xc6\x56\x06\x06\x40\x64\xa3\x0f\x17\xb5\xd1\x07\x2b\x03\x00\x20\x5e\x1d\xd3\x2d\x00\x00\x00";
+
+    const TO_HTTPS: &[u8] = b"HTTP/1.1 302 Found\r\nLocation: https://192.0.2.1/ISAPI/System/deviceInfo\r\nContent-Length: 0\r\n\r\n";
+
+    // Serves one connection with `response` and hands back the request head, lowercased
+    async fn mock_http(response: &'static [u8]) -> (String, oneshot::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let (tx, rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buf).await.unwrap() {
+                    0 => break,
+                    len => head.extend_from_slice(&buf[..len]),
+                }
+            }
+            stream.write_all(response).await.unwrap();
+            let _ = tx.send(String::from_utf8_lossy(&head).to_lowercase());
+        });
+
+        (url, rx)
+    }
+
+    #[tokio::test]
+    async fn custom_headers_reach_the_camera() {
+        let (url, head) = mock_http(OK).await;
+        let headers = vec![
+            Header::UserAgent("pulsar/1.0".to_string()),
+            Header::Custom("X-Api-Key".to_string(), "secret".to_string()),
+        ];
+
+        let body = r_reqwest(url, None, (None, None), Method::GET, Some(headers), None)
+            .await
+            .unwrap();
+        let head = head.await.unwrap();
+
+        assert_eq!(body, "ok");
+        assert!(head.contains("user-agent: pulsar/1.0"), "{head}");
+        assert!(head.contains("x-api-key: secret"), "{head}");
+    }
+
+    #[tokio::test]
+    async fn accept_header_is_sent() {
+        let (url, head) = mock_http(OK).await;
+        let headers = Some(vec![Header::AcceptJSON]);
+
+        r_reqwest(url, None, (None, None), Method::GET, headers, None)
+            .await
+            .unwrap();
+
+        assert!(head.await.unwrap().contains("accept: application/json"));
+    }
 
-    Ok(rb.send().await?.text().await?)
+    #[tokio::test]
+    async fn gzip_answer_is_decoded() {
+        let (url, head) = mock_http(GZIP_XML).await;
+
+        let body = r_reqwest(url, None, (None, None), Method::GET, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(body, "<Time><timeZone>CST-3:00:00</timeZone></Time>");
+        assert!(head
+            .await
+            .unwrap()
+            .contains("accept-encoding: gzip, deflate"));
+    }
+
+    #[tokio::test]
+    async fn compression_opt_out() {
+        let (url, head) = mock_http(OK).await;
+        let headers = Some(vec![Header::NoCompression]);
+
+        r_reqwest(url, None, (None, None), Method::GET, headers, None)
+            .await
+            .unwrap();
+        let head = head.await.unwrap();
+
+        assert!(head.contains("accept-encoding: identity"), "{head}");
+        assert!(!head.contains("gzip"), "{head}");
+    }
+
+    #[tokio::test]
+    async fn redirect_to_https_is_reported() {
+        let (url, _head) = mock_http(TO_HTTPS).await;
+
+        let result = r_reqwest(url, None, (None, None), Method::GET, None, None).await;
+
+        assert!(
+            matches!(
+                &result,
+                Err(IpCamerasError::Redirected { location })
+                    if location == "https://192.0.2.1/ISAPI/System/deviceInfo"
+            ),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn redirected_credentials_check_is_an_error() {
+        let (url, _head) = mock_http(TO_HTTPS).await;
+
+        let result = check_credentials(url, (None, None), None).await;
+
+        assert!(
+            matches!(result, Err(IpCamerasError::Redirected { .. })),
+            "{result:?}"
+        );
+    }
+
+    #[cfg(not(feature = "curl-transport"))]
+    #[tokio::test]
+    async fn disabled_curl_is_refused() {
+        let url = || "http://192.0.2.1/ISAPI/System/deviceInfo".to_string();
+
+        for rt in [RequestType::Curl, RequestType::All] {
+            assert!(matches!(
+                request(rt, url(), None, (None, None), Method::GET, None).await,
+                Err(IpCamerasError::TransportDisabled("curl"))
+            ));
+            assert!(matches!(
+                request_bytes(rt, url(), (None, None), None, None).await,
+                Err(IpCamerasError::TransportDisabled("curl"))
+            ));
+        }
+    }
+
+    #[cfg(feature = "curl-transport")]
+    #[tokio::test]
+    async fn cancelling_kills_curl() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let cancel = CancellationToken::new();
+
+        let request = tokio::spawn({
+            let cancel = cancel.clone();
+            async move {
+                let curl = r_curl(
+                    url,
+                    None,
+                    (None, None),
+                    Method::GET,
+                    None,
+                    Some(LONG_REQUEST_TIMEOUT),
+                );
+                cancellable(&cancel, curl).await
+            }
+        });
+
+        // curl is connected and waits for an answer that never comes
+        let (mut stream, _) = listener.accept().await.unwrap();
+        cancel.cancel();
+        assert!(matches!(
+            request.await.unwrap(),
+            Err(IpCamerasError::Cancelled)
+        ));
+
+        // The connection only closes once the child is gone
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            let mut buf = [0u8; 1024];
+            while stream.read(&mut buf).await.unwrap_or(0) > 0 {}
+        })
+        .await;
+        assert!(closed.is_ok());
+    }
+
+    #[cfg(feature = "curl-transport")]
+    #[test]
+    fn compression_opt_out_in_curl_args() {
+        let args = |headers| {
+            curl_args(
+                "http://192.0.2.1/".to_string(),
+                None,
+                (None, None),
+                Method::GET,
+                headers,
+                None,
+            )
+        };
+
+        assert!(args(None).contains(&"--compressed".to_string()));
+        let args = args(Some(vec![Header::NoCompression]));
+        assert!(!args.contains(&"--compressed".to_string()));
+        assert!(args
+            .windows(2)
+            .any(|a| a == ["-H", "Accept-Encoding: identity"]));
+    }
+
+    #[cfg(feature = "curl-transport")]
+    #[test]
+    fn custom_headers_in_curl_args() {
+        let args = curl_args(
+            "http://192.0.2.1/".to_string(),
+            None,
+            (None, None),
+            Method::GET,
+            Some(vec![
+                Header::UserAgent("pulsar/1.0".to_string()),
+                Header::Custom("X-Api-Key".to_string(), "secret".to_string()),
+            ]),
+            None,
+        );
+
+        assert!(args
+            .windows(2)
+            .any(|a| a == ["-H", "User-Agent: pulsar/1.0"]));
+        assert!(args.windows(2).any(|a| a == ["-H", "X-Api-Key: secret"]));
+    }
+
+    fn built(timeout_override: Option<Duration>) -> reqwest::Request {
+        reqwest_builder(
+            &Client::new(),
+            "http://192.0.2.1/ISAPI/Streaming/channels/101/picture".to_string(),
+            None,
+            Method::GET,
+            None,
+            timeout_override,
+        )
+        .unwrap()
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn reqwest_timeout_override() {
+        assert_eq!(
+            built(Some(LONG_REQUEST_TIMEOUT)).timeout(),
+            Some(&LONG_REQUEST_TIMEOUT)
+        );
+        assert_eq!(built(None).timeout(), None);
+    }
+
+    #[cfg(feature = "curl-transport")]
+    fn max_time(timeout_override: Option<Duration>) -> String {
+        let args = curl_args(
+            "http://192.0.2.1/".to_string(),
+            None,
+            (None, None),
+            Method::GET,
+            None,
+            timeout_override,
+        );
+        let position = args.iter().position(|a| a == "--max-time").unwrap();
+
+        args[position + 1].clone()
+    }
+
+    #[cfg(feature = "curl-transport")]
+    #[test]
+    fn curl_timeout_override() {
+        assert_eq!(max_time(Some(LONG_REQUEST_TIMEOUT)), "60");
+        assert_eq!(max_time(None), "5");
+    }
 }
@@ -1,9 +1,15 @@
 pub mod external {
+    pub use super::axis::{IoPort, OpticsSetupState, PortState};
+    pub use super::dahua::{parse_dahua_config, StreamKind};
     pub use super::hik::{dublicates, *};
+    pub use super::stilsoft::StilsoftConfiguration;
+
+    use std::collections::BTreeMap;
 
     use diesel_db::MultipleSettingsData;
     use domain::{stream::Resource, CameraId};
     use serde::{Deserialize, Serialize};
+    use serde_json::{Map, Value};
     use utoipa::ToSchema;
 
     pub const DEFAULT_TIMEOUT: u64 = 4;
@@ -60,6 +66,76 @@ pub mod external {
         AcquisitionTriggerWait,
     }
 
+    // Named image tunings that each vendor maps onto its native settings
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ImageProfile {
+        Default,
+        LicensePlate,
+        LowLight,
+    }
+
+    // Native image settings, tagged by vendor
+    #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+    #[serde(tag = "vendor", content = "settings", rename_all = "snake_case")]
+    pub enum VendorImageSettings {
+        Hikvision(ImageChannel),
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum WhiteBalanceMode {
+        Auto,
+        Indoor,
+        Outdoor,
+        // Uses the red/blue gains
+        Manual,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum DayNightMode {
+        // Switches by scene brightness
+        Auto,
+        Color,
+        BlackWhite,
+    }
+
+    pub const IMAGE_LEVEL_MIN: i32 = 0;
+    pub const IMAGE_LEVEL_MAX: i32 = 100;
+
+    // `None` keeps the current value
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct ImageAdjustment {
+        pub brightness: Option<i32>,
+        pub contrast: Option<i32>,
+        pub saturation: Option<i32>,
+        pub hue: Option<i32>,
+    }
+
+    // Vendor-neutral view of the image settings read in one pass,
+    // fields the camera doesn't report (or that have no neutral equivalent) stay `None`
+    #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct ImageSettings {
+        pub brightness: Option<i32>,
+        pub contrast: Option<i32>,
+        pub saturation: Option<i32>,
+        pub white_balance: Option<WhiteBalanceMode>,
+        // Anything but fully manual exposure counts as automatic
+        pub exposure_auto: Option<bool>,
+        pub day_night: Option<DayNightMode>,
+    }
+
+    pub fn validate_image_level(field: &str, value: i32) -> Result<(), crate::IpCamerasError> {
+        if (IMAGE_LEVEL_MIN..=IMAGE_LEVEL_MAX).contains(&value) {
+            Ok(())
+        } else {
+            Err(crate::IpCamerasError::InvalidParam(format!(
+                "{field} must be in {IMAGE_LEVEL_MIN}..={IMAGE_LEVEL_MAX}, got {value}"
+            )))
+        }
+    }
+
     #[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
     #[schema(as = api::source::SpotlightConfiguration)]
     pub struct SpotlightConfiguration {
@@ -92,6 +168,10 @@ pub mod external {
         //`Hikvision` config
         #[schema(value_type = api::source::HikvisionConfiguration)]
         pub hikvision: Option<HikvisionConfiguration>,
+        //`Stilsoft` config
+        #[serde(default)]
+        #[schema(value_type = api::source::StilsoftConfiguration)]
+        pub stilsoft: Option<StilsoftConfiguration>,
     }
 
     impl Default for AdditionalConfiguration {
@@ -103,6 +183,7 @@ pub mod external {
 
                 spotlight: Some(Default::default()),
                 hikvision: None,
+                stilsoft: None,
             }
         }
     }
@@ -116,6 +197,7 @@ pub mod external {
 
                 spotlight: None,
                 hikvision: None,
+                stilsoft: None,
             }
         }
     }
@@ -131,6 +213,7 @@ pub mod external {
         pub fn is_empty(&self) -> bool {
             self.spotlight.is_none()
                 && self.hikvision.is_none()
+                && self.stilsoft.is_none()
                 && self.is_day_now.is_none()
                 && self.default_settings.is_none()
         }
@@ -140,6 +223,7 @@ pub mod external {
                 id,
                 spotlight: None,
                 hikvision: None,
+                stilsoft: None,
                 is_day_now: None,
                 default_settings: None,
             }
@@ -153,6 +237,92 @@ pub mod external {
 
             Some(hik)
         }
+
+        // Dotted keys (`hikvision.image_channel.Color.brightnessLevel`, `list.0`) with
+        // JSON-encoded leaves, so `"5"` and `5` stay distinct. A `None` field has no key,
+        // and a missing key reads back as `None`; empty lists and maps are kept as leaves.
+        pub fn to_flat(&self) -> Result<BTreeMap<String, String>, crate::IpCamerasError> {
+            let mut flat = BTreeMap::new();
+            flatten_into(serde_json::to_value(self)?, String::new(), &mut flat);
+
+            Ok(flat)
+        }
+
+        pub fn from_flat(flat: &BTreeMap<String, String>) -> Result<Self, crate::IpCamerasError> {
+            let mut root = Value::Object(Map::new());
+            for (key, leaf) in flat {
+                let mut node = &mut root;
+                for part in key.split('.') {
+                    node = match node {
+                        Value::Object(map) => {
+                            map.entry(part).or_insert_with(|| Value::Object(Map::new()))
+                        }
+                        _ => {
+                            return Err(crate::IpCamerasError::InvalidParam(format!(
+                                "flat key {key} goes through a leaf"
+                            )))
+                        }
+                    };
+                }
+                *node = serde_json::from_str(leaf)?;
+            }
+
+            Ok(serde_json::from_value(unflatten_lists(root))?)
+        }
+    }
+
+    fn flatten_into(value: Value, path: String, flat: &mut BTreeMap<String, String>) {
+        let child = |key: &str| {
+            if path.is_empty() {
+                key.to_string()
+            } else {
+                format!("{path}.{key}")
+            }
+        };
+
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (key, value) in map {
+                    if !value.is_null() {
+                        flatten_into(value, child(&key), flat);
+                    }
+                }
+            }
+            Value::Array(list) if !list.is_empty() => {
+                for (i, value) in list.into_iter().enumerate() {
+                    flatten_into(value, child(&i.to_string()), flat);
+                }
+            }
+            leaf => {
+                flat.insert(path, leaf.to_string());
+            }
+        }
+    }
+
+    // Field names are never numeric, so a map keyed by `0..n` was a list
+    fn unflatten_lists(value: Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let is_list =
+                    !map.is_empty() && (0..map.len()).all(|i| map.contains_key(&i.to_string()));
+                let map: Map<String, Value> = map
+                    .into_iter()
+                    .map(|(key, value)| (key, unflatten_lists(value)))
+                    .collect();
+
+                if is_list {
+                    let mut map = map;
+                    Value::Array(
+                        (0..map.len())
+                            .filter_map(|i| map.remove(&i.to_string()))
+                            .collect(),
+                    )
+                } else {
+                    Value::Object(map)
+                }
+            }
+            value => value,
+        }
     }
 
     impl MultipleSettingsData for AdditionalConfiguration {
@@ -206,7 +376,7 @@ pub mod axis {
     #[derive(Serialize)]
     #[serde(rename_all = "camelCase")]
     pub struct Port {
-        pub port: &'static str,
+        pub port: String,
         // usage: String,
         // direction: PortDirection, //"input"|"output",
         // name: String,
@@ -248,6 +418,60 @@ pub mod axis {
         pub items: Vec<ProjectorsItem>,
     }
 
+    #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+    pub struct IoPort {
+        pub port: String,
+        pub state: PortState,
+        pub normal_state: PortState,
+    }
+
+    impl From<ProjectorsItem> for IoPort {
+        fn from(item: ProjectorsItem) -> Self {
+            Self {
+                port: item.port,
+                state: item.state,
+                normal_state: item.normal_state,
+            }
+        }
+    }
+
+    // Attributes of `<opticsSetupState>` in the `opticssetup.cgi?monitor=poll` answer,
+    // positions the lens doesn't report stay `None`
+    #[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+    pub struct OpticsSetupState {
+        pub focus_position: Option<f32>,
+        pub zoom_position: Option<f32>,
+        pub iris_position: Option<f32>,
+    }
+
+    impl OpticsSetupState {
+        pub fn parse(body: &str) -> Option<Self> {
+            use xml::reader::{EventReader, XmlEvent};
+
+            let attributes = EventReader::new(body.as_bytes())
+                .into_iter()
+                .flatten()
+                .find_map(|event| match event {
+                    XmlEvent::StartElement {
+                        name, attributes, ..
+                    } if name.local_name == "opticsSetupState" => Some(attributes),
+                    _ => None,
+                })?;
+            let position = |key: &str| {
+                attributes
+                    .iter()
+                    .find(|a| a.name.local_name == key)
+                    .and_then(|a| a.value.parse().ok())
+            };
+
+            Some(Self {
+                focus_position: position("focusPosition"),
+                zoom_position: position("zoomPosition"),
+                iris_position: position("irisPosition"),
+            })
+        }
+    }
+
     #[derive(Deserialize, Debug)]
     pub struct SwitchData {
         #[allow(dead_code)]
@@ -262,10 +486,40 @@ pub mod axis {
         pub method: RequestParams<P>,
     }
 
+    // `setTimeZone` takes IANA ids, POSIX strings have their own method
     #[derive(Serialize)]
     #[serde(rename_all = "camelCase")]
-    pub struct SetTimeZoneRequest {
-        pub time_zone: String,
+    pub struct SetPosixTimeZoneRequest {
+        pub posix_time_zone: String,
+    }
+
+    impl From<SetPosixTimeZoneRequest> for GenericApiRequest<SetPosixTimeZoneRequest> {
+        fn from(r: SetPosixTimeZoneRequest) -> Self {
+            GenericApiRequest {
+                api_version: ApiVersion,
+                method: "setPosixTimeZone",
+                params: r,
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct GetDateTimeInfoRequest {}
+
+    impl From<GetDateTimeInfoRequest> for GenericApiRequest<GetDateTimeInfoRequest> {
+        fn from(r: GetDateTimeInfoRequest) -> Self {
+            GenericApiRequest {
+                api_version: ApiVersion,
+                method: "getDateTimeInfo",
+                params: r,
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    pub struct DateTimeInfo {
+        pub posix_time_zone: String,
     }
 
     pub struct SetDateTimeRequest {
@@ -297,11 +551,21 @@ pub mod axis {
         }
     }
 
-    impl From<SetTimeZoneRequest> for GenericApiRequest<SetTimeZoneRequest> {
-        fn from(r: SetTimeZoneRequest) -> Self {
+    pub const DYNAMIC_OVERLAY_ID_MIN: u32 = 1;
+    pub const DYNAMIC_OVERLAY_ID_MAX: u32 = 32;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct SetDynamicOverlayTextRequest {
+        pub identity: u32,
+        pub text: String,
+    }
+
+    impl From<SetDynamicOverlayTextRequest> for GenericApiRequest<SetDynamicOverlayTextRequest> {
+        fn from(r: SetDynamicOverlayTextRequest) -> Self {
             GenericApiRequest {
                 api_version: ApiVersion,
-                method: "setTimeZone",
+                method: "setText",
                 params: r,
             }
         }
@@ -333,9 +597,13 @@ pub mod hik {
     };
     use utoipa::ToSchema;
 
-    use std::fmt::Display;
+    use std::{collections::HashMap, fmt::Display};
     use thiserror::Error;
 
+    // Elements the structs don't model, kept so a read-modify-write doesn't reset them.
+    // Only collected on read, `with_unknown_fields` writes them back into the sent document.
+    pub type UnknownFields = HashMap<String, serde_value::Value>;
+
     const NIGHT_TO_DAY_FILTER_LEVEL_PTZ: u32 = 2;
 
     pub mod dublicates {
@@ -386,6 +654,8 @@ pub mod hik {
         pub flow_ctrl: String,
     }
 
+    pub const PTZ_BAUD_RATES: [i32; 8] = [1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200];
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct PTZChannel {
@@ -647,6 +917,10 @@ pub mod hik {
         pub long_ir_distance_level: Option<i32>,
     }
 
+    // `DistanceLevel` range accepted by the exposure endpoint
+    pub const OVEREXPOSE_DISTANCE_LEVEL_MIN: i32 = 0;
+    pub const OVEREXPOSE_DISTANCE_LEVEL_MAX: i32 = 100;
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
     #[schema(as = api::source::Plris)]
     pub struct Plris {
@@ -671,6 +945,9 @@ pub mod hik {
         pub sensitivity: Option<i32>,
     }
 
+    pub const FACE_EXPOSURE_SENSITIVITY_MIN: i32 = 0;
+    pub const FACE_EXPOSURE_SENSITIVITY_MAX: i32 = 100;
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
     #[serde(rename_all = "PascalCase")]
     #[schema(as = api::source::Exposure)]
@@ -769,6 +1046,9 @@ pub mod hik {
         pub bright_enhance_level: i32,
     }
 
+    pub const BRIGHT_ENHANCE_LEVEL_MIN: i32 = 0;
+    pub const BRIGHT_ENHANCE_LEVEL_MAX: i32 = 100;
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
     #[serde(rename_all = "PascalCase")]
     #[schema(as = api::source::ImageModeList)]
@@ -864,6 +1144,9 @@ pub mod hik {
         pub dss_level: Option<String>,
     }
 
+    // Slow shutter multipliers accepted as `DSSLevel`
+    pub const DSS_LEVELS: [&str; 9] = ["2", "3", "4", "6", "8", "12", "16", "24", "32"];
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
     #[serde(rename_all = "camelCase")]
     #[schema(as = api::source::IrLight)]
@@ -889,6 +1172,20 @@ pub mod hik {
         pub min_iris_level_limit: Option<i32>,
     }
 
+    impl Iris {
+        // A missing or inverted limit leaves that side unclamped
+        pub fn clamp_level(&self, level: i32) -> i32 {
+            let min = self.min_iris_level_limit.unwrap_or(i32::MIN);
+            let max = self.max_iris_level_limit.unwrap_or(i32::MAX);
+
+            if min <= max {
+                level.clamp(min, max)
+            } else {
+                level
+            }
+        }
+    }
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
     #[serde(rename_all = "camelCase")]
     #[schema(as = api::source::ImageFreeze)]
@@ -903,6 +1200,26 @@ pub mod hik {
         pub enabled: bool,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum SupplementMode {
+        Auto,
+        Manual,
+    }
+
+    // `ISAPI/Image/channels/1/supplementLight`
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct SupplementLight {
+        pub supplement_light_mode: Option<String>,
+        pub mixed_light_brightness_regulat_mode: Option<SupplementMode>,
+        pub white_light_brightness: Option<i32>,
+        pub ir_light_brightness: Option<i32>,
+    }
+
+    pub const SUPPLEMENT_LIGHT_BRIGHTNESS_MIN: i32 = 0;
+    pub const SUPPLEMENT_LIGHT_BRIGHTNESS_MAX: i32 = 100;
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct LaserLight {
@@ -1088,6 +1405,9 @@ pub mod hik {
         pub defog_level: i32,
     }
 
+    pub const CAR_WINDOW_LEVEL_MIN: i32 = 0;
+    pub const CAR_WINDOW_LEVEL_MAX: i32 = 100;
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
     #[serde(rename_all = "PascalCase")]
     #[schema(as = api::source::ITCImageSnap)]
@@ -1234,6 +1554,11 @@ pub mod hik {
         pub correct_factor: Option<i32>,
     }
 
+    pub const PLATE_BRIGHT_SENSITIVITY_MIN: i32 = 0;
+    pub const PLATE_BRIGHT_SENSITIVITY_MAX: i32 = 100;
+    pub const PLATE_BRIGHT_CORRECTION_MIN: i32 = 0;
+    pub const PLATE_BRIGHT_CORRECTION_MAX: i32 = 100;
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
     #[serde(rename_all = "camelCase")]
     #[schema(as = api::source::HLC)]
@@ -1404,6 +1729,9 @@ pub mod hik {
         pub iris: Option<Iris>,
         #[schema(value_type = api::source::Proportionalpan)]
         pub proportionalpan: Option<Proportionalpan>,
+        #[serde(flatten, skip_serializing)]
+        #[schema(value_type = Object)]
+        pub extra: UnknownFields,
     }
 
     #[derive(Debug, Clone, Deserialize, PartialEq, Serialize, ToSchema)]
@@ -1412,7 +1740,7 @@ pub mod hik {
         pub mode: Option<String>,
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
     pub struct FocusData {
         pub focus: i32,
     }
@@ -1425,6 +1753,27 @@ pub mod hik {
         }
     }
 
+    // Native lens position range used by the absolute focus endpoint
+    pub const FOCUS_POSITION_MIN: i32 = 0;
+    pub const FOCUS_POSITION_MAX: i32 = 1000;
+
+    impl FocusData {
+        pub fn from_normalized(value: FocusValue) -> Self {
+            let range = (FOCUS_POSITION_MAX - FOCUS_POSITION_MIN) as FocusValue;
+
+            Self {
+                focus: FOCUS_POSITION_MIN + (value.clamp(0., 1.) * range).round() as i32,
+            }
+        }
+
+        pub fn to_normalized(&self) -> FocusValue {
+            let range = (FOCUS_POSITION_MAX - FOCUS_POSITION_MIN) as FocusValue;
+            let focus = self.focus.clamp(FOCUS_POSITION_MIN, FOCUS_POSITION_MAX);
+
+            (focus - FOCUS_POSITION_MIN) as FocusValue / range
+        }
+    }
+
     #[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
     #[serde(rename_all = "camelCase")]
     pub enum StatusCode {
@@ -1438,10 +1787,16 @@ pub mod hik {
         BadXmlContent,
         RebootRequired,
         AdditionalError,
-        #[serde(rename = "Unknow")]
+        #[serde(rename = "Unknow", other)]
         Unknow,
     }
 
+    impl Default for StatusCode {
+        fn default() -> Self {
+            StatusCode::Unknow
+        }
+    }
+
     pub type SubStatusCode = StatusCode;
 
     impl From<u8> for StatusCode {
@@ -1622,15 +1977,25 @@ pub mod hik {
         }
     }
 
+    // `ResponseStatus`; firmwares differ in which fields they send and in their case
     #[derive(Debug, Clone, Deserialize, PartialEq)]
     #[serde(rename_all = "camelCase")]
     pub struct Response {
-        #[serde(rename = "requestURL")]
-        pub request_url: String,
-        pub status_code: u8,
+        #[serde(
+            rename = "requestURL",
+            alias = "requestUrl",
+            alias = "RequestURL",
+            default
+        )]
+        pub request_url: Option<String>,
+        #[serde(alias = "StatusCode", default)]
+        pub status_code: Option<u8>,
+        #[serde(alias = "StatusString", default)]
         pub status_string: String,
+        #[serde(alias = "SubStatusString")]
         pub sub_status_string: Option<String>,
         pub id: Option<u32>,
+        #[serde(alias = "SubStatusCode", default)]
         pub sub_status_code: SubStatusCode,
         pub error_code: Option<u64>,
         pub error_msg: Option<String>,
@@ -1638,8 +2003,17 @@ pub mod hik {
     }
 
     impl Response {
+        // Without a numeric code the status string decides
+        pub fn status(&self) -> StatusCode {
+            match self.status_code {
+                Some(code) => code.into(),
+                None if self.status_string.eq_ignore_ascii_case("ok") => StatusCode::OK,
+                None => StatusCode::Unknow,
+            }
+        }
+
         pub fn is_ok(&self) -> bool {
-            self.status_code == 0 || self.status_code == 1
+            self.status() == StatusCode::OK
         }
     }
 
@@ -1681,8 +2055,8 @@ pub mod hik {
     impl From<Response> for SimpleResponse {
         fn from(r: Response) -> Self {
             Self {
-                request_url: r.request_url,
-                status_code: r.status_code.into(),
+                status_code: r.status(),
+                request_url: r.request_url.unwrap_or_default(),
                 status_string: r.status_string,
                 sub_status_code: r.sub_status_code,
             }
@@ -1706,7 +2080,7 @@ pub mod hik {
         Low,
     }
 
-    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
     #[serde(rename_all = "camelCase")]
     pub enum OutputStatus {
         High,
@@ -1733,7 +2107,7 @@ pub mod hik {
         }
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct SyncSignalOutput {
         pub id: u8,
@@ -1829,18 +2203,42 @@ pub mod hik {
         }
     }
 
-    #[derive(Debug, Deserialize, PartialEq, Serialize, Clone, Copy)]
+    // Unlisted versions are kept as `Other` instead of failing the whole `DeviceInfo`
+    #[derive(Debug, Deserialize, PartialEq, Eq, Serialize, Clone)]
+    #[serde(from = "String", into = "String")]
     pub enum FirmwareVerison {
-        #[serde(rename = "V5.7.3")]
         V573,
-        #[serde(rename = "V5.0.2")]
         V502,
-        #[serde(rename = "V5.1.4")]
         V514,
-        #[serde(rename = "V5.5.820")]
         V55820,
-        #[serde(rename = "V5.5.800")]
         V55800,
+        Other(String),
+    }
+
+    impl From<String> for FirmwareVerison {
+        fn from(value: String) -> Self {
+            match value.as_str() {
+                "V5.7.3" => FirmwareVerison::V573,
+                "V5.0.2" => FirmwareVerison::V502,
+                "V5.1.4" => FirmwareVerison::V514,
+                "V5.5.820" => FirmwareVerison::V55820,
+                "V5.5.800" => FirmwareVerison::V55800,
+                _ => FirmwareVerison::Other(value),
+            }
+        }
+    }
+
+    impl From<FirmwareVerison> for String {
+        fn from(value: FirmwareVerison) -> Self {
+            match value {
+                FirmwareVerison::V573 => "V5.7.3".to_string(),
+                FirmwareVerison::V502 => "V5.0.2".to_string(),
+                FirmwareVerison::V514 => "V5.1.4".to_string(),
+                FirmwareVerison::V55820 => "V5.5.820".to_string(),
+                FirmwareVerison::V55800 => "V5.5.800".to_string(),
+                FirmwareVerison::Other(other) => other,
+            }
+        }
     }
 
     impl Default for FirmwareVerison {
@@ -1849,6 +2247,171 @@ pub mod hik {
         }
     }
 
+    pub const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+    pub const XML_NAMESPACE: &str = "http://www.hikvision.com/ver20/XMLSchema";
+
+    // Prepends the XML declaration and puts `namespace` on the root element, unless already there
+    pub fn to_hik_xml<S: Serialize>(
+        value: &S,
+        namespace: Option<&str>,
+    ) -> Result<String, crate::IpCamerasError> {
+        let body = serde_xml_rs::to_string(value)?;
+        let body = match body.trim_start().strip_prefix("<?xml") {
+            Some(rest) => rest
+                .split_once("?>")
+                .map(|(_, rest)| rest.trim_start().to_string())
+                .unwrap_or_default(),
+            None => body,
+        };
+
+        let body = match namespace {
+            Some(namespace) => with_root_namespace(body, namespace),
+            None => body,
+        };
+
+        Ok(format!("{XML_DECLARATION}{body}"))
+    }
+
+    fn with_root_namespace(mut body: String, namespace: &str) -> String {
+        if let Some(name_end) = root_name_end(&body) {
+            body.insert_str(name_end, &format!(r#" xmlns="{namespace}""#));
+        }
+
+        body
+    }
+
+    // End of the root element name, `None` if it already declares a namespace
+    fn root_name_end(body: &str) -> Option<usize> {
+        let root = body
+            .match_indices('<')
+            .map(|(i, _)| i)
+            .find(|&i| !matches!(body[i + 1..].chars().next(), Some('?') | Some('!')))?;
+        let tag_end = body[root..].find('>')? + root;
+        if body[root..tag_end].contains("xmlns=") {
+            return None;
+        }
+
+        Some(
+            body[root + 1..tag_end]
+                .find(|c: char| c.is_whitespace() || c == '/')
+                .map_or(tag_end, |i| i + root + 1),
+        )
+    }
+
+    // Appends `unknown` to the children of the first `element` in `body`, sorted by name
+    pub fn with_unknown_fields(mut body: String, element: &str, unknown: &UnknownFields) -> String {
+        let mut names: Vec<&String> = unknown.keys().collect();
+        names.sort();
+
+        let mut children = String::new();
+        for name in names {
+            write_unknown_element(&mut children, name, &unknown[name]);
+        }
+
+        if let Some(end) = body.find(&format!("</{element}>")) {
+            body.insert_str(end, &children);
+        }
+
+        body
+    }
+
+    fn write_unknown_element(out: &mut String, name: &str, value: &serde_value::Value) {
+        match value {
+            // Repeated elements are read as a sequence
+            serde_value::Value::Seq(items) => items
+                .iter()
+                .for_each(|item| write_unknown_element(out, name, item)),
+            value => {
+                out.push_str(&format!("<{name}>"));
+                write_unknown_content(out, value);
+                out.push_str(&format!("</{name}>"));
+            }
+        }
+    }
+
+    fn write_unknown_content(out: &mut String, value: &serde_value::Value) {
+        use serde_value::Value;
+
+        match value {
+            Value::Map(children) => {
+                for (name, child) in children {
+                    match name {
+                        // Text next to child elements
+                        Value::String(name) if name == "$value" => {
+                            write_unknown_content(out, child)
+                        }
+                        Value::String(name) => write_unknown_element(out, name, child),
+                        _ => {}
+                    }
+                }
+            }
+            Value::Seq(items) => items
+                .iter()
+                .for_each(|item| write_unknown_content(out, item)),
+            Value::Option(Some(value)) | Value::Newtype(value) => write_unknown_content(out, value),
+            Value::Unit | Value::Option(None) => {}
+            Value::String(text) => out.push_str(&xml::escape::escape_str_pcdata(text)),
+            scalar => match serde_json::to_value(scalar) {
+                Ok(serde_json::Value::String(text)) => {
+                    out.push_str(&xml::escape::escape_str_pcdata(&text))
+                }
+                Ok(scalar) => out.push_str(&scalar.to_string()),
+                Err(_) => {}
+            },
+        }
+    }
+
+    impl FirmwareVerison {
+        // Namespace the firmware expects on PUT bodies
+        pub fn xml_namespace(&self) -> &'static str {
+            XML_NAMESPACE
+        }
+
+        // (major, minor, patch), so feature gates can compare ranges
+        pub fn as_semver(&self) -> (u16, u16, u16) {
+            match self {
+                FirmwareVerison::V502 => (5, 0, 2),
+                FirmwareVerison::V514 => (5, 1, 4),
+                FirmwareVerison::V55800 => (5, 5, 800),
+                FirmwareVerison::V55820 => (5, 5, 820),
+                FirmwareVerison::V573 => (5, 7, 3),
+                FirmwareVerison::Other(other) => Self::parse_semver(other),
+            }
+        }
+
+        // `V5.6.2 build 210308` -> (5, 6, 2), missing or garbled parts are 0
+        fn parse_semver(raw: &str) -> (u16, u16, u16) {
+            let version = raw
+                .trim()
+                .trim_start_matches(['V', 'v'])
+                .split_whitespace()
+                .next()
+                .unwrap_or_default();
+            let mut parts = version.split('.').map(|p| p.parse().unwrap_or_default());
+
+            (
+                parts.next().unwrap_or_default(),
+                parts.next().unwrap_or_default(),
+                parts.next().unwrap_or_default(),
+            )
+        }
+    }
+
+    impl PartialOrd for FirmwareVerison {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for FirmwareVerison {
+        // Unlisted strings that parse to the same numbers fall back to a textual compare
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.as_semver()
+                .cmp(&other.as_semver())
+                .then_with(|| String::from(self.clone()).cmp(&String::from(other.clone())))
+        }
+    }
+
     #[derive(Debug, Default)]
     pub struct SPSettings {
         pub enabled: bool,
@@ -1941,6 +2504,140 @@ pub mod hik {
         pub customized_info: String,
     }
 
+    // `ISAPI/System/status`
+    #[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+    #[serde(rename_all = "camelCase")]
+    pub struct DeviceStatus {
+        pub current_device_time: Option<String>,
+        pub device_up_time: Option<u64>,
+        #[serde(rename = "CPUList", default)]
+        pub cpu_list: CPUList,
+        #[serde(rename = "MemoryList", default)]
+        pub memory_list: MemoryList,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+    pub struct CPUList {
+        #[serde(rename = "CPU", default)]
+        pub cpu: Vec<CPU>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+    #[serde(rename_all = "camelCase")]
+    pub struct CPU {
+        pub cpu_utilization: Option<f32>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+    pub struct MemoryList {
+        #[serde(rename = "Memory", default)]
+        pub memory: Vec<Memory>,
+    }
+
+    // Both values are in MB
+    #[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Memory {
+        pub memory_usage: Option<f32>,
+        pub memory_available: Option<f32>,
+    }
+
+    impl From<&ImageChannel> for crate::ImageSettings {
+        fn from(ic: &ImageChannel) -> Self {
+            use crate::{DayNightMode, WhiteBalanceMode};
+
+            let white_balance = ic.white_balance.as_ref().and_then(|wb| {
+                use WhiteBalanceStyle::*;
+
+                match wb.white_balance_style {
+                    AUTO | AUTO0 | AUTO1 | AUTO2 | AUTOTRACE => Some(WhiteBalanceMode::Auto),
+                    INDOOR => Some(WhiteBalanceMode::Indoor),
+                    OUTDOOR => Some(WhiteBalanceMode::Outdoor),
+                    MANUAL => Some(WhiteBalanceMode::Manual),
+                    _ => None,
+                }
+            });
+            let day_night = ic
+                .ircut_filter
+                .as_ref()
+                .map(|filter| match filter.ircut_filter_type {
+                    IrcutFilterTypes::Auto => DayNightMode::Auto,
+                    IrcutFilterTypes::Day => DayNightMode::Color,
+                    IrcutFilterTypes::Night => DayNightMode::BlackWhite,
+                });
+
+            Self {
+                brightness: ic.color.as_ref().map(|c| c.brightness_level),
+                contrast: ic.color.as_ref().map(|c| c.contrast_level),
+                saturation: ic.color.as_ref().map(|c| c.saturation_level),
+                white_balance,
+                exposure_auto: ic
+                    .exposure
+                    .as_ref()
+                    .map(|e| e.exposure_type != ExposureType::MANUAL),
+                day_night,
+            }
+        }
+    }
+
+    impl From<DeviceStatus> for crate::SystemStatus {
+        fn from(ds: DeviceStatus) -> Self {
+            let cpu: Vec<f32> = ds
+                .cpu_list
+                .cpu
+                .iter()
+                .filter_map(|c| c.cpu_utilization)
+                .collect();
+            let cpu_usage = (!cpu.is_empty()).then(|| cpu.iter().sum::<f32>() / cpu.len() as f32);
+
+            let (used, available) = ds
+                .memory_list
+                .memory
+                .iter()
+                .filter_map(|m| Some((m.memory_usage?, m.memory_available?)))
+                .fold((0., 0.), |(u, a), (mu, ma)| (u + mu, a + ma));
+            let memory_usage = (used + available > 0.).then(|| used / (used + available) * 100.);
+
+            Self {
+                uptime: ds.device_up_time,
+                cpu_usage,
+                memory_usage,
+                current_time: ds.current_device_time,
+            }
+        }
+    }
+
+    // Partial `DeviceInfo` for PUT, only the given fields are changed
+    #[derive(Debug, Serialize, PartialEq, Clone, Default)]
+    #[serde(rename = "DeviceInfo", rename_all = "camelCase")]
+    pub struct DeviceInfoUpdate {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub device_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub device_location: Option<String>,
+    }
+
+    pub const DEVICE_INFO_MAX_LEN: usize = 32;
+
+    pub fn validate_device_info(field: &str, value: &str) -> Result<(), crate::IpCamerasError> {
+        let len = value.chars().count();
+        if len == 0 || len > DEVICE_INFO_MAX_LEN {
+            return Err(crate::IpCamerasError::InvalidParam(format!(
+                "{field} must be 1..={DEVICE_INFO_MAX_LEN} characters, got {len}"
+            )));
+        }
+        if value
+            .chars()
+            .any(|ch| ch.is_control() || "<>&\"'".contains(ch))
+        {
+            return Err(crate::IpCamerasError::InvalidParam(format!(
+                "{field} contains forbidden characters: {value}"
+            )));
+        }
+
+        Ok(())
+    }
+
     #[derive(Debug, Deserialize, Serialize, PartialEq, Clone, ToSchema)]
     #[serde(rename_all = "camelCase")]
     #[schema(as = api::source::ICRCtrlMode)]
@@ -2134,6 +2831,24 @@ pub mod hik {
         Extended,
     }
 
+    // Hikvision keeps `maxFrameRate` in hundredths of a frame: 2500 is 25 fps, 2997 is 29.97 fps
+    pub const FPS_RAW_SCALE: FpsValue = 100;
+
+    pub fn fps_from_raw(raw: FpsValue) -> FpsValue {
+        (raw + FPS_RAW_SCALE / 2) / FPS_RAW_SCALE
+    }
+
+    pub fn fps_to_raw(fps: FpsValue) -> FpsValue {
+        fps * FPS_RAW_SCALE
+    }
+
+    // I-frame interval in frames
+    pub const GOV_LENGTH_MIN: u32 = 1;
+    pub const GOV_LENGTH_MAX: u32 = 400;
+
+    // Values accepted as `rotationDegree`
+    pub const ROTATION_DEGREES: [i32; 4] = [0, 90, 180, 270];
+
     #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, ToSchema)]
     #[serde(rename_all = "camelCase")]
     #[schema(as = api::source::Video)]
@@ -2165,6 +2880,10 @@ pub mod hik {
         #[serde(rename = "H264Profile")]
         #[schema(value_type = api::source::H264Profile)]
         pub h264_profile: Option<H264Profile>,
+        // Same profile names as H.264, but H.265 only takes `Main`
+        #[serde(rename = "H265Profile")]
+        #[schema(value_type = api::source::H264Profile)]
+        pub h265_profile: Option<H264Profile>,
         #[serde(rename = "SVACProfile")]
         #[schema(value_type = api::source::SVACProfile)]
         pub svac_profile: Option<SVACProfile>,
@@ -2174,6 +2893,9 @@ pub mod hik {
         #[serde(rename = "SmartCodec")]
         #[schema(value_type = api::source::SmartCodec)]
         pub smart_codec: Option<SmartCodec>,
+        #[serde(flatten, skip_serializing)]
+        #[schema(value_type = Object)]
+        pub extra: UnknownFields,
     }
 
     #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, ToSchema)]
@@ -2257,6 +2979,41 @@ pub mod hik {
         pub security: Option<Security>,
     }
 
+    impl From<Transport> for crate::TransportInfo {
+        fn from(t: Transport) -> Self {
+            use crate::{MulticastConfig, StreamProtocol};
+
+            let multicast = t.multicast.filter(|mc| mc.enabled).and_then(|mc| {
+                match (mc.dest_ip_address, mc.video_dest_port_no) {
+                    (Some(address), Some(port)) => Some(MulticastConfig { address, port }),
+                    _ => None,
+                }
+            });
+
+            let protocol = if multicast.is_some() {
+                StreamProtocol::Multicast
+            } else {
+                match t.unicast.and_then(|u| u.rtp_transport_type) {
+                    Some(RtpTransportType::UDP) => StreamProtocol::Udp,
+                    _ => StreamProtocol::Tcp,
+                }
+            };
+
+            Self {
+                rtsp_port: t.rtsp_port_no,
+                protocol,
+                multicast,
+            }
+        }
+    }
+
+    // `ISAPI/Streaming/channels`: main, sub and third streams of every input
+    #[derive(Debug, Deserialize)]
+    pub struct StreamingChannelList {
+        #[serde(rename = "StreamingChannel", default)]
+        pub streaming_channel: Vec<StreamingChannel>,
+    }
+
     #[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
     #[serde(rename_all = "camelCase")]
     #[schema(as = api::source::StreamingChannel)]
@@ -2269,6 +3026,15 @@ pub mod hik {
         pub video: Video,
     }
 
+    // Hikvision uses POSIX-like zones with the sign inverted: UTC+08:00 is `CST-8:00:00`
+    pub fn hik_time_zone(offset: &chrono::FixedOffset) -> String {
+        let seconds = offset.local_minus_utc();
+        let inverted = if seconds >= 0 { '-' } else { '+' };
+        let (hours, minutes) = (seconds.abs() / 3600, seconds.abs() % 3600 / 60);
+
+        format!("CST{inverted}{hours}:{minutes:02}:00")
+    }
+
     #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
     #[serde(rename_all = "camelCase")]
     pub struct Time {
@@ -2299,17 +3065,108 @@ pub mod hik {
     }
 }
 pub mod dahua {
-    use serde::Serialize;
+    use std::collections::HashMap;
+
+    use serde::{ser::SerializeMap, Serialize};
+
+    // `getConfig`-style bodies, one `table.<key>=<value>` or `<key>=<value>` per line.
+    // Keys are returned without the `table.` prefix, as built by `keys`; lines without
+    // `=` are skipped.
+    pub fn parse_dahua_config(body: &str) -> HashMap<String, String> {
+        body.lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| {
+                let key = key.trim();
+                let key = key.strip_prefix(keys::TABLE_PREFIX).unwrap_or(key);
+
+                (key.to_string(), value.trim().to_string())
+            })
+            .collect()
+    }
+
+    // Parameter names of `configManager.cgi`, shared by the setters and the response parsers
+    pub mod keys {
+        use super::StreamKind;
+
+        // Keys are echoed back as `table.<key>=<value>` by `getConfig`
+        pub const TABLE_PREFIX: &str = "table.";
+
+        pub const ENCODE: &str = "Encode";
+        pub const ALARM_OUT: &str = "AlarmOut";
+        pub const RTSP: &str = "RTSP";
+        pub const RTSP_PORT: &str = "RTSP.Port";
+        pub const MACHINE_NAME: &str = "General.MachineName";
+        pub const FLASHLIGHT_ENABLE: &str = "FlashLight.Enable";
+        pub const FLASHLIGHT_BRIGHTNESS: &str = "FlashLight.Brightness";
+
+        pub const VIDEO_IN_WHITE_BALANCE: &str = "VideoInWhiteBalance";
+        pub const VIDEO_IN_DAY_NIGHT: &str = "VideoInDayNight";
+        pub const VIDEO_COLOR: &str = "VideoColor";
+        // Day, night and normal configs of a video input
+        pub const VIDEO_IN_PROFILES: usize = 3;
+
+        pub const NTP: &str = "NTP";
+        pub const NTP_TIME_ZONE: &str = "NTP.TimeZone";
+
+        pub const FPS: &str = "FPS";
+        pub const FPS_MAX: &str = "FPSMax";
+
+        // `Encode`, 0 -> `Encode[0]`
+        pub fn indexed(name: &str, index: usize) -> String {
+            format!("{name}[{index}]")
+        }
+
+        // `Encode[0].MainFormat[0].Video.<field>`
+        pub fn encode_video(channel: usize, stream: StreamKind, field: &str) -> String {
+            format!(
+                "{}.{}",
+                indexed(ENCODE, channel),
+                stream_video(stream, field)
+            )
+        }
+
+        // `MainFormat[0].Video.<field>`, as found in both configs and caps
+        pub fn stream_video(stream: StreamKind, field: &str) -> String {
+            format!("{}.Video.{field}", indexed(stream.format_name(), 0))
+        }
 
-    #[derive(Clone, Copy)]
+        pub fn alarm_out_mode(index: usize) -> String {
+            format!("{}.Mode", indexed(ALARM_OUT, index))
+        }
+
+        pub fn alarm_out_name(index: usize) -> String {
+            format!("{}.Name", indexed(ALARM_OUT, index))
+        }
+
+        // `VideoColor`, 0, 1, `Brightness` -> `VideoColor[0][1].Brightness`
+        pub fn video_in(name: &str, channel: usize, profile: usize, field: &str) -> String {
+            format!("{}[{profile}].{field}", indexed(name, channel))
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
     #[repr(u8)]
     pub enum AlarmMode {
         Automatic = 0,
         ForceOn = 1,
-        #[allow(dead_code)]
         ForceOff = 2,
     }
 
+    impl AlarmMode {
+        // `Automatic` leaves the output to the camera's capture triggers, which is how
+        // Dahua pulses a strobe; modes that wait on external triggers have no equivalent
+        pub fn from_spotlight_mode(mode: &super::external::SpotlightMode) -> Option<Self> {
+            use super::external::SpotlightMode::*;
+
+            match mode {
+                Off => Some(Self::ForceOff),
+                AlwaysOn => Some(Self::ForceOn),
+                Strobe => Some(Self::Automatic),
+                ExposureActive | FrameTriggerWait | AcquisitionTriggerWait => None,
+            }
+        }
+    }
+
     impl Default for AlarmMode {
         fn default() -> Self {
             Self::Automatic
@@ -2337,31 +3194,126 @@ pub mod dahua {
         }
     }
 
-    #[derive(Default, Serialize)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum StreamKind {
+        Main,
+        Extra,
+    }
+
+    impl StreamKind {
+        pub fn format_name(&self) -> &'static str {
+            match self {
+                StreamKind::Main => "MainFormat",
+                StreamKind::Extra => "ExtraFormat",
+            }
+        }
+
+        pub fn fps_key(&self, channel: usize) -> String {
+            keys::encode_video(channel, *self, keys::FPS)
+        }
+    }
+
+    #[derive(Default)]
     pub struct Config {
-        #[serde(rename = "FlashLight.Enable")]
-        #[serde(skip_serializing_if = "Option::is_none")]
+        // `Encode` index the FPS keys target
+        pub channel: usize,
         pub spotlight: Option<bool>,
-        #[serde(rename = "FlashLight.Brightness")]
-        #[serde(skip_serializing_if = "Option::is_none")]
         pub brightness: Option<u8>,
-        #[serde(rename = "Encode[0].MainFormat[0].Video.FPS")]
-        #[serde(skip_serializing_if = "Option::is_none")]
         pub fps: Option<f64>,
+        pub extra_fps: Option<f64>,
 
-        #[serde(flatten)]
-        #[serde(skip_serializing_if = "Option::is_none")]
         pub external_spotlight: Option<ExternalSpotlight>,
     }
 
-    #[derive(Serialize)]
+    // Written by hand so that every key comes from `keys`
+    impl Serialize for Config {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            let mut map = serializer.serialize_map(None)?;
+
+            if let Some(spotlight) = self.spotlight {
+                map.serialize_entry(keys::FLASHLIGHT_ENABLE, &spotlight)?;
+            }
+            if let Some(brightness) = self.brightness {
+                map.serialize_entry(keys::FLASHLIGHT_BRIGHTNESS, &brightness)?;
+            }
+            if let Some(fps) = self.fps {
+                map.serialize_entry(&StreamKind::Main.fps_key(self.channel), &fps)?;
+            }
+            if let Some(fps) = self.extra_fps {
+                map.serialize_entry(&StreamKind::Extra.fps_key(self.channel), &fps)?;
+            }
+            if let Some(external_spotlight) = &self.external_spotlight {
+                map.serialize_entry(&keys::alarm_out_mode(0), &external_spotlight.alarm_mode)?;
+                map.serialize_entry(&keys::alarm_out_name(0), &external_spotlight.alarm_name)?;
+            }
+
+            map.end()
+        }
+    }
+
     pub struct ExternalSpotlight {
-        #[serde(rename = "AlarmOut[0].Mode")]
         pub alarm_mode: AlarmMode,
-        #[serde(rename = "AlarmOut[0].Name")]
         pub alarm_name: AlarmName,
     }
 
+    pub fn white_balance_mode(mode: super::external::WhiteBalanceMode) -> &'static str {
+        use super::external::WhiteBalanceMode::*;
+
+        match mode {
+            Auto => "Auto",
+            Indoor => "Indoor",
+            Outdoor => "Outdoor",
+            Manual => "Manual",
+        }
+    }
+
+    pub fn day_night_mode(mode: super::external::DayNightMode) -> &'static str {
+        use super::external::DayNightMode::*;
+
+        match mode {
+            Auto => "Brightness",
+            Color => "Color",
+            BlackWhite => "BlackWhite",
+        }
+    }
+
+    // `NTP.TimeZone` is an index into this table of UTC offsets, in minutes
+    pub const TIME_ZONE_OFFSETS: [i32; 33] = [
+        0, 60, 120, 180, 210, 240, 270, 300, 330, 345, 360, 390, 420, 480, 540, 570, 600, 660, 720,
+        780, -60, -120, -180, -210, -240, -300, -360, -420, -480, -540, -600, -660, -720,
+    ];
+
+    pub fn time_zone_index(offset: &chrono::FixedOffset) -> Option<usize> {
+        let minutes = offset.local_minus_utc() / 60;
+        TIME_ZONE_OFFSETS.iter().position(|&o| o == minutes)
+    }
+
+    pub fn time_zone_offset(index: usize) -> Option<chrono::FixedOffset> {
+        chrono::FixedOffset::east_opt(TIME_ZONE_OFFSETS.get(index)? * 60)
+    }
+
+    pub const MACHINE_NAME_MAX_LEN: usize = 31;
+
+    // Dahua only accepts plain ASCII names
+    pub fn validate_machine_name(name: &str) -> Result<(), crate::IpCamerasError> {
+        let valid = !name.is_empty()
+            && name.len() <= MACHINE_NAME_MAX_LEN
+            && name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+
+        if valid {
+            Ok(())
+        } else {
+            Err(crate::IpCamerasError::InvalidParam(format!(
+                "Dahua machine name must be 1..={MACHINE_NAME_MAX_LEN} of [A-Za-z0-9_-], got {name}"
+            )))
+        }
+    }
+
     impl From<bool> for ExternalSpotlight {
         fn from(value: bool) -> Self {
             match value {
@@ -2373,18 +3325,422 @@ pub mod dahua {
 
     impl ExternalSpotlight {
         pub fn enabled() -> Self {
-            Self {
-                alarm_mode: AlarmMode::ForceOn,
-                alarm_name: AlarmName,
-            }
+            Self::with_mode(AlarmMode::ForceOn)
         }
 
         pub fn disabled() -> Self {
+            Self::with_mode(AlarmMode::Automatic)
+        }
+
+        pub fn with_mode(alarm_mode: AlarmMode) -> Self {
             Self {
-                alarm_mode: AlarmMode::Automatic,
+                alarm_mode,
                 alarm_name: AlarmName,
             }
         }
     }
 }
-pub mod stilsoft {}
+pub mod stilsoft {
+    use serde::{Deserialize, Serialize};
+    use utoipa::ToSchema;
+
+    pub const DEFAULT_BRIGHTNESS: u8 = 50;
+    // Seconds the cached day/night state is trusted before the camera is asked again
+    pub const DEFAULT_SPOTLIGHT_TTL: u64 = 10;
+
+    pub fn validate_brightness(brightness: u8) -> Result<(), crate::IpCamerasError> {
+        if brightness <= 100 {
+            Ok(())
+        } else {
+            Err(crate::IpCamerasError::InvalidParam(format!(
+                "Stilsoft brightness must be in 0..=100, got {brightness}"
+            )))
+        }
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, ToSchema)]
+    #[schema(as = api::source::StilsoftConfiguration)]
+    pub struct StilsoftConfiguration {
+        // 0..=100
+        pub brightness: Option<u8>,
+        // `image_profile` 2 is night, 1 is day
+        pub night_mode: Option<bool>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::FixedOffset;
+
+    use super::*;
+    use crate::utils::time_zone::{posix_time_zone, validate_time_zone};
+
+    fn hours(hours: i32) -> FixedOffset {
+        FixedOffset::east_opt(hours * 3600).unwrap()
+    }
+
+    #[test]
+    fn axis_optics_setup_poll_is_parsed() {
+        let state = axis::OpticsSetupState::parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <opticsSetupResponse>
+              <opticsSetupState source="1" focusPosition="0.4521" zoomPosition="0.1"
+                irisPosition="0.75" focusState="idle" zoomState="idle"/>
+            </opticsSetupResponse>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            state,
+            axis::OpticsSetupState {
+                focus_position: Some(0.4521),
+                zoom_position: Some(0.1),
+                iris_position: Some(0.75),
+            }
+        );
+    }
+
+    #[test]
+    fn axis_optics_setup_without_some_positions() {
+        let state =
+            axis::OpticsSetupState::parse(r#"<opticsSetupState focusPosition="0.2"/>"#).unwrap();
+        assert_eq!(state.focus_position, Some(0.2));
+        assert_eq!(state.zoom_position, None);
+        assert_eq!(state.iris_position, None);
+
+        assert_eq!(axis::OpticsSetupState::parse("<opticsSetupError/>"), None);
+    }
+
+    #[test]
+    fn axis_posix_time_zone_round_trip() {
+        let tz = posix_time_zone(&hours(3));
+        let request: axis::GenericApiRequest<_> = axis::SetPosixTimeZoneRequest {
+            posix_time_zone: tz.clone(),
+        }
+        .into();
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "apiVersion": "1.0",
+                "method": "setPosixTimeZone",
+                "params": { "posixTimeZone": "<+0300>-3:00" },
+            })
+        );
+
+        let info: axis::DateTimeInfo =
+            serde_json::from_value(serde_json::json!({ "posixTimeZone": tz })).unwrap();
+        assert_eq!(validate_time_zone(&info.posix_time_zone).unwrap(), hours(3));
+    }
+
+    #[test]
+    fn axis_date_time_keeps_offset() {
+        let date_time = chrono::DateTime::parse_from_rfc3339("2024-05-01T12:00:00+03:00").unwrap();
+
+        let zone: axis::GenericApiRequest<_> = axis::SetPosixTimeZoneRequest {
+            posix_time_zone: posix_time_zone(date_time.offset()),
+        }
+        .into();
+        let time: axis::GenericApiRequest<_> = axis::SetDateTimeRequest {
+            date_time: date_time.naive_utc(),
+        }
+        .into();
+
+        assert_eq!(
+            serde_json::to_value(&zone).unwrap()["params"],
+            serde_json::json!({ "posixTimeZone": "<+0300>-3:00" })
+        );
+        assert_eq!(
+            serde_json::to_value(&time).unwrap(),
+            serde_json::json!({
+                "apiVersion": "1.0",
+                "method": "setDateTime",
+                "params": { "dateTime": "2024-05-01T09:00:00Z" },
+            })
+        );
+    }
+
+    #[test]
+    fn hikvision_time_zone_round_trip() {
+        assert_eq!(hik::hik_time_zone(&hours(3)), "CST-3:00:00");
+        assert_eq!(hik::hik_time_zone(&hours(-5)), "CST+5:00:00");
+
+        for offset in [-12, -5, 0, 3, 12] {
+            let tz = hik::hik_time_zone(&hours(offset));
+            assert_eq!(validate_time_zone(&tz).unwrap(), hours(offset), "{tz}");
+        }
+    }
+
+    #[test]
+    fn hikvision_fps_is_symmetric() {
+        for (fps, raw) in [(25, 2500), (30, 3000), (29, 2900)] {
+            assert_eq!(hik::fps_to_raw(fps), raw);
+            assert_eq!(hik::fps_from_raw(raw), fps);
+            assert_eq!(hik::fps_from_raw(hik::fps_to_raw(fps)), fps);
+        }
+
+        // NTSC rates are stored as hundredths and round to the nearest frame
+        assert_eq!(hik::fps_from_raw(2997), 30);
+        assert_eq!(hik::fps_from_raw(2949), 29);
+    }
+
+    #[test]
+    fn hikvision_focus_native_range() {
+        use hik::{FocusData, FOCUS_POSITION_MAX, FOCUS_POSITION_MIN};
+
+        assert_eq!(FocusData::from_normalized(0.).focus, FOCUS_POSITION_MIN);
+        assert_eq!(FocusData::from_normalized(1.).focus, FOCUS_POSITION_MAX);
+        assert_eq!(FocusData::from_normalized(0.25).focus, 250);
+        assert_eq!(FocusData::from_normalized(0.25).to_normalized(), 0.25);
+
+        // Out of range values are clamped on both sides
+        assert_eq!(FocusData::from_normalized(1.5).focus, FOCUS_POSITION_MAX);
+        assert_eq!(FocusData { focus: -10 }.to_normalized(), 0.);
+        assert_eq!(FocusData { focus: 5000 }.to_normalized(), 1.);
+    }
+
+    fn nested_hikvision_configuration() -> external::AdditionalConfiguration {
+        let image_channel = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "enabled": true,
+            "Color": {
+                "brightnessLevel": 50,
+                "contrastLevel": 40,
+                "saturationLevel": 60,
+            },
+            "Shutter": { "ShutterLevel": "1/25" },
+            "JPEGParam": { "JPEGSize": 300 },
+        }))
+        .unwrap();
+
+        external::AdditionalConfiguration {
+            is_day_now: Some(true),
+            hikvision: Some(external::HikvisionConfiguration {
+                external_projector: false,
+                image_channel: Some(image_channel),
+                ..Default::default()
+            }),
+            ..external::AdditionalConfiguration::new(3)
+        }
+    }
+
+    #[test]
+    fn flat_configuration_round_trip() {
+        let config = nested_hikvision_configuration();
+
+        let flat = config.to_flat().unwrap();
+        let back = external::AdditionalConfiguration::from_flat(&flat).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&back).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+    }
+
+    #[test]
+    fn flat_configuration_keys() {
+        let flat = nested_hikvision_configuration().to_flat().unwrap();
+
+        let leaf = |key: &str| flat.get(key).map(String::as_str);
+        assert_eq!(leaf("id"), Some("3"));
+        assert_eq!(leaf("hikvision.external_projector"), Some("false"));
+        assert_eq!(
+            leaf("hikvision.image_channel.Color.brightnessLevel"),
+            Some("50")
+        );
+        // Leaves stay JSON, so strings keep their quotes
+        assert_eq!(
+            leaf("hikvision.image_channel.Shutter.ShutterLevel"),
+            Some(r#""1/25""#)
+        );
+        // `None` fields have no key at all
+        assert_eq!(leaf("stilsoft"), None);
+        assert_eq!(leaf("hikvision.streaming_channel"), None);
+        assert_eq!(
+            leaf("hikvision.image_channel.JPEGParam.MergeJPEGSize"),
+            None
+        );
+    }
+
+    #[test]
+    fn hikvision_response_variants_are_parsed() {
+        let full: hik::Response = serde_xml_rs::from_str(
+            "<ResponseStatus><requestURL>/ISAPI/Image/channels/1</requestURL>\
+             <statusCode>1</statusCode><statusString>OK</statusString>\
+             <subStatusCode>ok</subStatusCode></ResponseStatus>",
+        )
+        .unwrap();
+        assert!(full.is_ok());
+        assert_eq!(full.request_url.as_deref(), Some("/ISAPI/Image/channels/1"));
+
+        // Older firmwares skip the url and capitalize the fields
+        let capitalized: hik::Response = serde_xml_rs::from_str(
+            "<ResponseStatus><StatusCode>1</StatusCode><StatusString>OK</StatusString>\
+             </ResponseStatus>",
+        )
+        .unwrap();
+        assert!(capitalized.is_ok());
+        assert_eq!(capitalized.request_url, None);
+
+        let string_only: hik::Response = serde_xml_rs::from_str(
+            "<ResponseStatus><statusString>OK</statusString></ResponseStatus>",
+        )
+        .unwrap();
+        assert!(string_only.is_ok());
+
+        let failed: hik::Response = serde_xml_rs::from_str(
+            "<ResponseStatus><requestUrl>/ISAPI/ITC/syncSignalOutput</requestUrl>\
+             <statusCode>4</statusCode><statusString>Invalid Operation</statusString>\
+             </ResponseStatus>",
+        )
+        .unwrap();
+        assert!(!failed.is_ok());
+        assert_eq!(failed.status(), hik::StatusCode::InvalidOperation);
+    }
+
+    #[test]
+    fn hikvision_body_has_declaration_and_namespace() {
+        let be = hik::BrightEnhance {
+            bright_enhance_level: 30,
+        };
+
+        let body = hik::to_hik_xml(&be, Some(hik::XML_NAMESPACE)).unwrap();
+
+        assert!(body.starts_with(hik::XML_DECLARATION), "{body}");
+        assert_eq!(body.matches("<?xml").count(), 1, "{body}");
+        assert!(
+            body.contains(&format!(
+                r#"<BrightEnhance xmlns="{}">"#,
+                hik::XML_NAMESPACE
+            )),
+            "{body}"
+        );
+        assert!(
+            body.contains("<brightEnhanceLevel>30</brightEnhanceLevel>"),
+            "{body}"
+        );
+
+        let plain = hik::to_hik_xml(&be, None).unwrap();
+        assert!(plain.starts_with(hik::XML_DECLARATION), "{plain}");
+        assert!(!plain.contains("xmlns="), "{plain}");
+    }
+
+    #[test]
+    fn hikvision_firmware_versions_are_ordered() {
+        use hik::FirmwareVerison::{self, *};
+
+        let mut versions = vec![V573, V55820, V502, V55800, V514];
+        versions.sort();
+        assert_eq!(versions, [V502, V514, V55800, V55820, V573]);
+
+        let unlisted = FirmwareVerison::from("V5.6.2 build 210308".to_string());
+        assert_eq!(unlisted.as_semver(), (5, 6, 2));
+        assert!(unlisted > V55820 && unlisted < V573);
+        assert!(V55800 >= FirmwareVerison::from("V5.5.800".to_string()));
+    }
+
+    #[test]
+    fn garbled_hikvision_firmware_is_zero() {
+        let garbled = hik::FirmwareVerison::from("unknown".to_string());
+
+        assert_eq!(garbled.as_semver(), (0, 0, 0));
+        assert!(garbled < hik::FirmwareVerison::V502);
+    }
+
+    #[test]
+    fn dahua_time_zone_index() {
+        let nepal = FixedOffset::east_opt(5 * 3600 + 45 * 60).unwrap();
+
+        assert_eq!(dahua::time_zone_index(&hours(0)), Some(0));
+        assert_eq!(dahua::time_zone_index(&hours(3)), Some(3));
+        assert_eq!(dahua::time_zone_index(&nepal), Some(9));
+        assert_eq!(dahua::time_zone_index(&hours(-12)), Some(32));
+        assert_eq!(dahua::time_zone_index(&hours(13)), Some(19));
+        assert_eq!(dahua::time_zone_index(&hours(14)), None);
+
+        for index in 0..dahua::TIME_ZONE_OFFSETS.len() {
+            let offset = dahua::time_zone_offset(index).unwrap();
+            assert_eq!(dahua::time_zone_index(&offset), Some(index));
+        }
+    }
+
+    #[test]
+    fn iris_level_is_clamped_to_the_reported_limits() {
+        let iris = |min, max| hik::Iris {
+            iris_level: Some(50),
+            max_iris_level_limit: max,
+            min_iris_level_limit: min,
+        };
+
+        assert_eq!(iris(Some(10), Some(80)).clamp_level(90), 80);
+        assert_eq!(iris(Some(10), Some(80)).clamp_level(5), 10);
+        assert_eq!(iris(Some(10), Some(80)).clamp_level(40), 40);
+        assert_eq!(iris(None, Some(80)).clamp_level(-5), -5);
+        assert_eq!(iris(Some(10), None).clamp_level(500), 500);
+        assert_eq!(iris(Some(80), Some(10)).clamp_level(90), 90);
+    }
+
+    #[test]
+    fn dahua_config_body_is_parsed() {
+        let config = dahua::parse_dahua_config(
+            "table.Encode[0].MainFormat[0].Video.FPS=25.000000\r\n\
+             table.Encode[0].ExtraFormat[0].Video.FPS=12\r\n\
+             table.AlarmOut[1].Mode = 2\r\n\
+             General.MachineName=Gate 3\n\
+             Error\r\n\
+             \r\n",
+        );
+
+        assert_eq!(config.len(), 4);
+        assert_eq!(
+            config["Encode[0].MainFormat[0].Video.FPS"],
+            "25.000000".to_string()
+        );
+        assert_eq!(config["Encode[0].ExtraFormat[0].Video.FPS"], "12");
+        assert_eq!(config["AlarmOut[1].Mode"], "2");
+        assert_eq!(config["General.MachineName"], "Gate 3");
+    }
+
+    #[test]
+    fn unknown_image_channel_elements_are_written_back() {
+        let ic: hik::ImageChannel = serde_xml_rs::from_str(
+            "<ImageChannel><id>1</id><enabled>true</enabled>\
+             <Color><brightnessLevel>40</brightnessLevel><contrastLevel>50</contrastLevel>\
+             <saturationLevel>50</saturationLevel></Color>\
+             <NoiseReduceExt><mode>advanced</mode><level>3 &amp; up</level></NoiseReduceExt>\
+             <vendorFlag>on</vendorFlag></ImageChannel>",
+        )
+        .unwrap();
+        assert_eq!(ic.extra.len(), 2);
+
+        let body = hik::with_unknown_fields(
+            hik::to_hik_xml(&ic, None).unwrap(),
+            "ImageChannel",
+            &ic.extra,
+        );
+
+        assert!(
+            body.ends_with(
+                "<NoiseReduceExt><level>3 &amp; up</level><mode>advanced</mode>\
+                 </NoiseReduceExt><vendorFlag>on</vendorFlag></ImageChannel>"
+            ),
+            "{body}"
+        );
+        assert!(
+            body.contains("<brightnessLevel>40</brightnessLevel>"),
+            "{body}"
+        );
+        let read_back: hik::ImageChannel = serde_xml_rs::from_str(&body).unwrap();
+        assert_eq!(read_back.extra, ic.extra);
+    }
+
+    #[test]
+    fn without_unknown_elements_the_body_is_unchanged() {
+        let body = "<StreamingChannel><Video><enabled>true</enabled></Video></StreamingChannel>";
+
+        assert_eq!(
+            hik::with_unknown_fields(body.to_string(), "Video", &Default::default()),
+            body
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+// Vendor-neutral health snapshot, fields the camera doesn't report stay `None`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SystemStatus {
+    // Seconds since boot
+    pub uptime: Option<u64>,
+    // Percent, 0..=100
+    pub cpu_usage: Option<f32>,
+    // Percent, 0..=100
+    pub memory_usage: Option<f32>,
+    // As reported by the camera, in its own format and time zone
+    pub current_time: Option<String>,
+}
+
+// In-memory state a model holds between calls, for inspecting caches without the network.
+// Concepts a model doesn't cache stay `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CachedState {
+    pub initialized: Option<bool>,
+    pub is_ptz: Option<bool>,
+    pub firmware_version: Option<String>,
+    pub projector_lines: Option<Vec<u8>>,
+    pub spotlight_state: Option<bool>,
+    // Next continuous focus move
+    pub focus_interval: Option<usize>,
+    pub focus_forward: Option<bool>,
+}
@@ -0,0 +1,99 @@
+use chrono::FixedOffset;
+use regex::Regex;
+
+use crate::IpCamerasError;
+
+// Time zones cross the API as POSIX TZ strings (`UTC`, `<+0300>-3:00`, `CST-8:00:00`,
+// `CET-1CEST,M3.5.0,M10.5.0/3`), which every supported vendor understands natively
+// or can be mapped from. IANA ids (`Europe/Moscow`) are rejected: a DST part is only
+// accepted after the standard offset. Returns the standard (non-DST) offset.
+pub fn validate_time_zone(tz: &str) -> Result<FixedOffset, IpCamerasError> {
+    let re = Regex::new(
+        r"^(?:<[+-]?[0-9A-Za-z]+>|[A-Za-z]{3,})(?:([+-]?)(\d{1,2})(?::(\d{2}))?(?::(\d{2}))?((?:<[+-]?[0-9A-Za-z]+>|[A-Za-z]{3,})[0-9A-Za-z+\-:,./]*)?)?$",
+    )?;
+    let invalid = || IpCamerasError::InvalidParam(format!("invalid POSIX time zone: {tz}"));
+
+    let captures = re.captures(tz).ok_or_else(invalid)?;
+    let number = |i: usize| -> i32 {
+        captures
+            .get(i)
+            .and_then(|m| m.as_str().parse().ok())
+            .unwrap_or_default()
+    };
+
+    let (hours, minutes, seconds) = (number(2), number(3), number(4));
+    if hours > 24 || minutes > 59 || seconds > 59 {
+        return Err(invalid());
+    }
+
+    // POSIX offsets are west of UTC, so `-3` means UTC+03:00
+    let west = hours * 3600 + minutes * 60 + seconds;
+    let east = match captures.get(1).map(|m| m.as_str()) {
+        Some("-") => west,
+        _ => -west,
+    };
+
+    FixedOffset::east_opt(east).ok_or_else(invalid)
+}
+
+// POSIX TZ strings have the sign inverted: UTC+03:00 is `<+0300>-3:00`
+pub fn posix_time_zone(offset: &FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    if seconds == 0 {
+        return "UTC".to_string();
+    }
+
+    let (sign, inverted) = if seconds > 0 { ('+', '-') } else { ('-', '+') };
+    let (hours, minutes) = (seconds.abs() / 3600, seconds.abs() % 3600 / 60);
+
+    format!("<{sign}{hours:02}{minutes:02}>{inverted}{hours}:{minutes:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(hours: f32) -> FixedOffset {
+        FixedOffset::east_opt((hours * 3600.) as i32).unwrap()
+    }
+
+    #[test]
+    fn parses_posix_time_zones() {
+        for (tz, offset) in [
+            ("UTC", 0.),
+            ("<+0300>-3:00", 3.),
+            ("CST-8:00:00", 8.),
+            ("<+0545>-5:45", 5.75),
+            ("CET-1CEST,M3.5.0,M10.5.0/3", 1.),
+            ("EST5EDT,M3.2.0,M11.1.0", -5.),
+        ] {
+            assert_eq!(validate_time_zone(tz).unwrap(), hours(offset), "{tz}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_time_zones() {
+        for tz in [
+            "",
+            "Europe/Moscow",
+            "America/New_York",
+            "+03:00",
+            "UTC+25",
+            "UTC-3:60",
+        ] {
+            assert!(validate_time_zone(tz).is_err(), "{tz}");
+        }
+    }
+
+    #[test]
+    fn posix_time_zone_round_trip() {
+        assert_eq!(posix_time_zone(&hours(0.)), "UTC");
+        assert_eq!(posix_time_zone(&hours(3.)), "<+0300>-3:00");
+        assert_eq!(posix_time_zone(&hours(-3.5)), "<-0330>+3:30");
+
+        for offset in [0., 3., -3.5, 5.75, 12., -12.] {
+            let tz = posix_time_zone(&hours(offset));
+            assert_eq!(validate_time_zone(&tz).unwrap(), hours(offset), "{tz}");
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use pulsar_core::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::{timeout, Duration},
+};
+
+use crate::IpCamerasError;
+
+pub const DEFAULT_RTSP_PORT: u32 = 554;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamProtocol {
+    Tcp,
+    Udp,
+    Multicast,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MulticastConfig {
+    pub address: String,
+    pub port: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransportInfo {
+    pub rtsp_port: u32,
+    pub protocol: StreamProtocol,
+    pub multicast: Option<MulticastConfig>,
+}
+
+pub fn validate_rtsp_port(port: u32) -> Result<(), IpCamerasError> {
+    if (1..=65535).contains(&port) {
+        Ok(())
+    } else {
+        Err(IpCamerasError::InvalidParam(format!(
+            "RTSP port must be in 1..=65535, got {port}"
+        )))
+    }
+}
+
+// Sends a bare RTSP `OPTIONS` and checks that something RTSP answers.
+// `host` may carry the HTTP port (`10.0.0.1:8080`), it is dropped.
+pub async fn probe_rtsp(host: &str, port: u32, timeout_secs: u64) -> bool {
+    let host = match host.rsplit_once(':') {
+        Some((name, http_port)) if http_port.parse::<u16>().is_ok() && !name.contains(':') => name,
+        _ => host,
+    };
+    let request = format!("OPTIONS rtsp://{host}:{port}/ RTSP/1.0\r\nCSeq: 1\r\n\r\n");
+
+    let probe = async {
+        let mut stream = TcpStream::connect(format!("{host}:{port}")).await?;
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut buf = [0u8; 64];
+        let len = stream.read(&mut buf).await?;
+
+        Ok::<_, std::io::Error>(buf[..len].starts_with(b"RTSP/1.0"))
+    };
+
+    matches!(
+        timeout(Duration::from_secs(timeout_secs), probe).await,
+        Ok(Ok(true))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    // Answers a single connection with `reply` once the request is read
+    async fn mock_server(reply: &'static [u8]) -> u32 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 256];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream.write_all(reply).await;
+        });
+
+        port as u32
+    }
+
+    #[tokio::test]
+    async fn probe_rtsp_options() {
+        let port = mock_server(b"RTSP/1.0 200 OK\r\nCSeq: 1\r\n\r\n").await;
+
+        assert!(probe_rtsp("127.0.0.1:80", port, 1).await);
+    }
+
+    #[tokio::test]
+    async fn probe_rtsp_rejects_other_protocols() {
+        let port = mock_server(b"HTTP/1.1 400 Bad Request\r\n\r\n").await;
+
+        assert!(!probe_rtsp("127.0.0.1", port, 1).await);
+    }
+}